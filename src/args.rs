@@ -1,14 +1,20 @@
 use std::path::{Path, PathBuf};
 
 use clap::{
-    ArgAction, Args, Parser, Subcommand, ValueEnum,
-    builder::{PossibleValue, Styles, styling::AnsiColor},
+    ArgAction, Args, ColorChoice, Parser, Subcommand, ValueEnum,
+    builder::{BoolishValueParser, PossibleValue, Styles, styling::AnsiColor},
 };
 
-use crate::config::{BackendType, SteamId};
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+    config::{BackendType, DEFAULT_FTP_PORT, SteamId, SteamIdParseError, WebDavAuth},
+    exit_code::EXIT_CODES_HELP,
+    platform::{LUTRIS_GAME_UUID, STEAM_APP_ID_VARS},
+};
 
 #[derive(Parser, Debug)]
-#[clap(styles = style())]
+#[clap(styles = style(), after_help = EXIT_CODES_HELP)]
 pub struct CliArgs {
     /// Update the manifest
     ///
@@ -30,18 +36,79 @@ pub struct CliArgs {
     /// This obviousely has no effect for some commands that are purely
     /// query, e.g. listing backends
     ///
-    /// Note this does NOT affect things like the manifest cache it is only for
-    /// the files related to syncing
+    /// With `--update` the manifest is fetched and checked but the manifest cache
+    /// isn't written, other commands may still fill an empty cache
+    ///
+    /// A launch still reads the backend, so any lock or conflict a real run would ask about is
+    /// reported along with the files it would transfer
     #[arg(long, short = 'n', required = false, default_value_t = false)]
     pub dry_run: bool,
 
+    /// Don't ask for confirmation, e.g. for `--upload-only` or when local saves are newer
+    ///
+    /// Confirmations will go with the default, for a sync conflict that means the local files
+    /// are kept and uploaded when the game closes
+    #[arg(long = "yes", short = 'y', default_value_t = false)]
+    pub yes: bool,
+
+    /// When to use colours in the output
+    ///
+    /// auto uses colours if stdout is a terminal and `NO_COLOR` is not set
+    #[arg(long = "color", alias = "colour", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Log more, -v for debug and -vv for trace
+    ///
+    /// Logs go to the log file and, for commands other than launch, stderr
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        conflicts_with = "verbose",
+        default_value_t = false
+    )]
+    pub quiet: bool,
+
+    /// Format of the log file
+    #[arg(long = "log-format", default_value = "pretty")]
+    pub log_format: LogFormat,
+
+    /// Format of what the commands which only show information print, e.g. `games list`,
+    /// `games info`, `backends list` and `verify`
+    ///
+    /// json is for programs wrapping cinc, logs are still written to stderr as text
+    #[arg(long = "output", default_value = "text")]
+    pub output: OutputFormat,
+
     /// Specify a config file to use
     #[arg(long = "config")]
     pub config_path: Option<PathBuf>,
+
+    /// Keep the config, cache, data and logs under this directory rather than the system ones,
+    /// e.g. to carry cinc around on a usb stick
+    #[arg(long = "data-root", env = "CINC_HOME")]
+    pub data_root: Option<PathBuf>,
     #[command(subcommand)]
     pub op: Option<Operation>,
 }
 
+impl CliArgs {
+    /// Log level set by the user with --verbose or --quiet, if any
+    pub fn log_level(&self) -> Option<LevelFilter> {
+        if self.quiet {
+            return Some(LevelFilter::WARN);
+        }
+        match self.verbose {
+            0 => None,
+            1 => Some(LevelFilter::DEBUG),
+            _ => Some(LevelFilter::TRACE),
+        }
+    }
+}
+
 #[derive(Subcommand, Clone, Debug)]
 pub enum Operation {
     /// For launching a game
@@ -49,6 +116,12 @@ pub enum Operation {
     /// This will download the files from the specified (or default) backend before launching the game,
     /// and upload them after. It may be used with steam as `cinc launch -- %command%`
     Launch(LaunchArgs),
+    /// Show what `launch` finds for a game, without syncing or running it
+    ///
+    /// Takes the same arguments as launch, e.g. `cinc resolve -- <command>` with the command from
+    /// the launcher. Prints the platform, game, backend and where the save paths are on this
+    /// machine, for checking cinc detects the right things
+    Resolve(LaunchArgs),
     #[command(hide = true)]
     DebugSyncDialog {
         #[arg(default_value = "debug remote", long)]
@@ -67,12 +140,134 @@ pub enum Operation {
     /// Show a password input and echo it, for debugging
     #[command(hide = true)]
     DebugPskInput,
+    /// Print a shell completion script to stdout
+    ///
+    /// e.g. for bash `cinc completions bash > ~/.local/share/bash-completion/completions/cinc`
+    Completions {
+        /// Shell to generate completions for
+        #[arg()]
+        shell: CompletionShell,
+    },
+    /// Query the games in the manifest
+    #[command(name = "games", subcommand)]
+    Games(GamesArgs),
     /// Configure backends
     ///
     /// all others are just mirrors that
     /// are uploaded to. The one used for downloading can be specifically selected with --backend
     #[command(name = "backends", subcommand)]
     BackendsConfig(BackendsArgs),
+    /// Remove the saves of games that are no longer wanted from a backend
+    ///
+    /// Every game folder on the backend which isn't for a game in the manifest, or in --keep if it
+    /// is given, is removed after asking for each one. With --dry-run they are only listed
+    Prune {
+        /// Backend to prune, the default backend if not given
+        #[arg(long = "backend")]
+        backend: Option<String>,
+
+        /// Games to keep, every other game is removed. Can be given more than once
+        #[arg(long = "keep")]
+        keep: Vec<String>,
+    },
+    /// Upload the saves from ludusavi backups to the default backend
+    ///
+    /// The latest full backup of each game is used. Games which already have saves on the
+    /// backend are skipped unless --yes is given
+    Import {
+        /// ludusavi's backup directory
+        from: PathBuf,
+
+        /// Only import these games. Can be given more than once
+        #[arg(long = "game")]
+        games: Vec<String>,
+    },
+    /// Write a game's saves to a zip, with each file at its path on this machine
+    ///
+    /// The saves come from the default backend unless --local is given
+    Export {
+        /// Name of the game as it appears in the manifest
+        #[arg(long = "game")]
+        game: String,
+
+        /// Zip file to write
+        #[arg(long = "out")]
+        out: PathBuf,
+
+        /// Export the saves on this machine rather than the ones on the backend
+        #[arg(long = "local", default_value_t = false)]
+        local: bool,
+    },
+    /// Extract a game's saves on the default backend to a directory, as they are stored there
+    ///
+    /// This is for looking at what is on the backend, the saves on this machine are not touched
+    Extract {
+        /// Name of the game as it appears in the manifest
+        #[arg(long = "game")]
+        game: String,
+
+        /// Directory to extract to, it is created if it doesn't exist
+        #[arg(long = "dest")]
+        dest: PathBuf,
+    },
+    /// Check a game's saves on the default backend are the same as the ones on this machine
+    ///
+    /// Lists the files which differ, are only on the backend or were never uploaded. Nothing is
+    /// changed
+    Verify {
+        /// Name of the game as it appears in the manifest
+        #[arg(long = "game")]
+        game: String,
+    },
+    /// Restore files from the default backend which are never downloaded by a sync
+    ///
+    /// The local copies are backed up first, like they are before a download
+    #[command(name = "restore", subcommand)]
+    Restore(RestoreArgs),
+    /// Remove cinc's cached files, e.g. the manifest, they are fetched again when next needed
+    ///
+    /// For when the cache is causing problems, the log files are kept
+    ClearCache,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum RestoreArgs {
+    /// Restore the config files uploaded with the `backup_config_files` config option
+    ///
+    /// Saves can't be restored this way, they are downloaded on launch
+    Config {
+        /// Name of the game as it appears in the manifest
+        #[arg(long = "game")]
+        game: String,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum GamesArgs {
+    /// List the names of all the games in the manifest
+    List {
+        /// Only list games which have save files
+        #[arg(long = "with-saves", default_value = "false")]
+        with_saves: bool,
+
+        /// List the games most recently played on this machine first, with when they were played
+        #[arg(long = "sort-by-recent", default_value = "false")]
+        sort_by_recent: bool,
+    },
+    /// Search for games in the manifest by name
+    ///
+    /// Matching is a case-insensitive substring match
+    Search {
+        /// What to search for
+        query: String,
+    },
+    /// Show the manifest entry for a game and where its save files are on this machine
+    ///
+    /// The platform is steam if the game is installed there, otherwise it is treated as native
+    Info {
+        /// Name of the game as it appears in the manifest
+        name: String,
+    },
 }
 
 #[derive(Subcommand, Clone, Debug)]
@@ -94,20 +289,142 @@ pub enum BackendsArgs {
         /// - for WebDav it is relative to the url given,
         ///
         /// - for the filesystem it is relative to /
-        #[arg(long = "root", default_value = "/")]
-        root: PathBuf,
+        ///
+        /// - for rclone it is relative to the remote
+        ///
+        /// - for git it is the path of the repository, which is created if it doesn't exist
+        ///
+        /// - for ftp it is relative to the directory the server puts you in after logging in,
+        ///   unless it starts with a /
+        ///
+        /// Each game's saves go in a directory named after it under the root, except for git.
+        /// Put `{game}` in the root to choose where the game's name goes instead, e.g.
+        /// `/saves/{game}/cinc`
+        ///
+        /// Defaults to / for every backend other than git
+        #[arg(long = "root")]
+        root: Option<PathBuf>,
 
         /// Set this backend as the default after adding it
         #[arg(long = "set-default", default_value = "false")]
         set_default: bool,
 
+        /// Name of the rclone remote, as in `rclone listremotes`, required when type is rclone
+        #[arg(long = "rclone-remote")]
+        rclone_remote: Option<String>,
+
+        /// Git remote to push to after each sync, when type is git
+        #[arg(long = "git-push-remote")]
+        git_push_remote: Option<String>,
+
         /// Url for the webdav backend, required when type is webdev
         #[arg(long = "webdav-url")]
         webdav_url: Option<String>,
 
-        /// Username for the webdav backend, required when type is webdev
+        /// Username for the webdav backend, required when type is webdev and using basic auth
         #[arg(long = "webdav-username")]
         webdav_username: Option<String>,
+
+        /// How to authenticate with the webdav backend
+        #[arg(long = "webdav-auth", default_value = "basic")]
+        webdav_auth: WebDavAuth,
+
+        /// PEM encoded certificate to trust for the webdav server, e.g. if it is self-signed
+        #[arg(long = "webdav-ca-cert")]
+        webdav_ca_cert: Option<PathBuf>,
+
+        /// Disable certificate verification for the webdav server
+        ///
+        /// THIS IS INSECURE, prefer --webdav-ca-cert if at all possible
+        #[arg(long = "webdav-danger-accept-invalid-certs", default_value = "false")]
+        webdav_danger_accept_invalid_certs: bool,
+
+        /// Hostname of the ftp server, required when type is ftp
+        #[arg(long = "ftp-host")]
+        ftp_host: Option<String>,
+
+        /// Port of the ftp server
+        #[arg(long = "ftp-port", default_value_t = DEFAULT_FTP_PORT)]
+        ftp_port: u16,
+
+        /// Username for the ftp server
+        #[arg(long = "ftp-username", default_value = "anonymous")]
+        ftp_username: String,
+
+        /// Use explicit TLS (FTPS) with the ftp server
+        #[arg(long = "ftp-tls", default_value = "false")]
+        ftp_tls: bool,
+    },
+    /// Change the settings of an existing backend
+    ///
+    /// Only the options given are changed, the stored password is kept unless
+    /// --change-password is passed
+    Edit {
+        /// Name of the backend to edit
+        #[arg()]
+        name: String,
+
+        /// New root for the backend, see `backends add --help`
+        #[arg(long = "root")]
+        root: Option<PathBuf>,
+
+        /// New rclone remote for the rclone backend
+        #[arg(long = "rclone-remote")]
+        rclone_remote: Option<String>,
+
+        /// New git remote to push to for the git backend, empty to stop pushing
+        #[arg(long = "git-push-remote")]
+        git_push_remote: Option<String>,
+
+        /// New url for the webdav backend
+        #[arg(long = "webdav-url")]
+        webdav_url: Option<String>,
+
+        /// New username for the webdav backend
+        #[arg(long = "webdav-username")]
+        webdav_username: Option<String>,
+
+        /// How to authenticate with the webdav backend
+        #[arg(long = "webdav-auth")]
+        webdav_auth: Option<WebDavAuth>,
+
+        /// PEM encoded certificate to trust for the webdav server
+        #[arg(long = "webdav-ca-cert")]
+        webdav_ca_cert: Option<PathBuf>,
+
+        /// Enable or disable certificate verification for the webdav server
+        ///
+        /// THIS IS INSECURE when set to true, prefer --webdav-ca-cert if at all possible
+        #[arg(long = "webdav-danger-accept-invalid-certs", action = ArgAction::Set)]
+        webdav_danger_accept_invalid_certs: Option<bool>,
+
+        /// New hostname for the ftp backend
+        #[arg(long = "ftp-host")]
+        ftp_host: Option<String>,
+
+        /// New port for the ftp backend
+        #[arg(long = "ftp-port")]
+        ftp_port: Option<u16>,
+
+        /// New username for the ftp backend
+        #[arg(long = "ftp-username")]
+        ftp_username: Option<String>,
+
+        /// Enable or disable explicit TLS (FTPS) for the ftp backend
+        #[arg(long = "ftp-tls", action = ArgAction::Set)]
+        ftp_tls: Option<bool>,
+
+        /// Prompt for a new webdav or ftp password, or webdav token
+        #[arg(long = "change-password", default_value = "false")]
+        change_password: bool,
+    },
+    /// Prompt for a new password or token for a backend, e.g. after changing it on the server
+    ///
+    /// The old one is removed from the system keyring if it was stored there
+    SetPassword {
+        /// Name of the backend
+        #[arg()]
+        name: String,
     },
     Remove {
         /// Name of the backend to remove
@@ -116,6 +433,12 @@ pub enum BackendsArgs {
     },
     /// List all configured backends
     List,
+    /// Check a backend works by writing, reading back, and deleting a small file
+    Test {
+        /// Name of the backend to test
+        #[arg()]
+        name: String,
+    },
     /// Set a backend as the default
     SetDefault {
         /// Name of the backend
@@ -134,8 +457,11 @@ pub struct LaunchArgs {
         default_value = "auto"
     )]
     pub platform: PlatformOpt,
-    /// Don't upload after closing, this is a debug flag and is hidden from the user
-    #[arg(long = "debug-no-upload", hide = true, default_value = "false")]
+    /// Don't upload your saves after the game closes, e.g. for a quick check you don't want in
+    /// the cloud
+    ///
+    /// Saves are still downloaded before the game launches, see `--upload-only` for the opposite
+    #[arg(long = "no-upload", alias = "debug-no-upload", default_value_t = false)]
     pub no_upload: bool,
 
     /// Do not download any files, only upload your local changes (on game close)
@@ -146,6 +472,27 @@ pub struct LaunchArgs {
     #[arg(long = "upload-only", default_value_t = false)]
     pub no_download: bool,
 
+    /// Confirm `--upload-only` up front instead of with a dialog, e.g. for scripts
+    ///
+    /// `--yes` also does this but skips every other confirmation too
+    #[arg(
+        long = "confirm-upload-only",
+        env = "CINC_CONFIRM_UPLOAD_ONLY",
+        value_parser = BoolishValueParser::new(),
+        default_value_t = false
+    )]
+    pub confirm_upload_only: bool,
+
+    /// Read the archive back after uploading and check it matches what was sent
+    ///
+    /// This costs an extra download of the archive on every upload
+    #[arg(long = "verify-upload", default_value_t = false)]
+    pub verify_upload: bool,
+
+    /// Upload even if the saves are over the `max_upload_size_mb` limit
+    #[arg(long = "allow-large-upload", alias = "force", default_value_t = false)]
+    pub allow_large_upload: bool,
+
     /// Specify the steam app id used to find the game in the manifest directly
     ///
     /// This is useful in case the actual app id on steam differs from the app id steam tells cinc,
@@ -154,23 +501,104 @@ pub struct LaunchArgs {
     #[arg(long = "steam-app-id")]
     pub manifest_app_id_override: Option<SteamId>,
 
+    /// Use this game from the manifest instead of working out which game is being launched
+    ///
+    /// This is the name as it appears in the manifest, see `cinc games search`. Use it when cinc
+    /// picks the wrong game or can't find it at all
+    #[arg(long = "game", conflicts_with = "manifest_app_id_override")]
+    pub manifest_game_override: Option<String>,
+
+    /// Print what each of the game's save paths resolves to for this launch, then exit
+    ///
+    /// Nothing is synced and the game isn't run. Use it to see why cinc isn't finding some saves
+    #[arg(long = "list-templates", default_value_t = false)]
+    pub list_templates: bool,
+
+    /// Don't sync at all and just run the game, e.g. when your internet is down
+    ///
+    /// The backend isn't contacted in any way, saves made now are uploaded the next time the game
+    /// is launched online and conflicts with the remote are checked for as usual then
+    #[arg(long = "offline", default_value_t = false)]
+    pub offline: bool,
+
     #[arg(help = "Command to run the game, e.g. for steam pass as %command%")]
     pub command: Vec<String>,
 }
 
+/// Format for the log file
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable multi-line logs
+    #[default]
+    Pretty,
+    /// One json object per line, for log collectors
+    Json,
+}
+
+/// Format for the output of commands which show information
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum OutputFormat {
+    /// For people
+    #[default]
+    Text,
+    /// For other programs
+    Json,
+}
+
+/// Shells we can generate completions for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum CompletionShell {
+    /// Bourne again shell
+    Bash,
+    /// Z shell
+    Zsh,
+    /// Friendly interactive shell
+    Fish,
+    /// Powershell
+    #[value(name = "powershell")]
+    PowerShell,
+}
+
+impl From<CompletionShell> for clap_complete::Shell {
+    fn from(value: CompletionShell) -> Self {
+        match value {
+            CompletionShell::Bash => Self::Bash,
+            CompletionShell::Zsh => Self::Zsh,
+            CompletionShell::Fish => Self::Fish,
+            CompletionShell::PowerShell => Self::PowerShell,
+        }
+    }
+}
+
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
 /// Force specific platform support, usually unnecessary as autodetect should find it
 pub enum PlatformOpt {
     /// Force steam mode
     Steam,
     /// Force umu mode
     Umu,
+    /// Force lutris mode
+    Lutris,
+    /// Force bottles mode, the game must be run with `bottles-cli run`
+    Bottles,
+    /// Game runs natively without wine, this is only autodetected on windows
+    Native,
     #[default]
     /// Attempt to autodetect launcher platform
     Auto,
 }
 const UMU_EXE_NAME: &str = "umu-run";
 const WINE_EXE_NAME: &str = "wine";
+const BOTTLES_CLI_NAME: &str = "bottles-cli";
+
+/// Whether `arg` runs bottles-cli, either directly or as `flatpak run --command=bottles-cli`
+pub fn is_bottles_cli(arg: &str) -> bool {
+    let exe = arg.strip_prefix("--command=").unwrap_or(arg);
+    Path::new(exe).file_name().and_then(|n| n.to_str()) == Some(BOTTLES_CLI_NAME)
+}
 
 impl LaunchArgs {
     /// Resolve the platform to one which is not auto
@@ -179,12 +607,23 @@ impl LaunchArgs {
             PlatformOpt::Auto => {
                 if self.command.iter().any(|s| s.starts_with("AppId=")) {
                     Some(PlatformOpt::Steam)
+                } else if std::env::var(LUTRIS_GAME_UUID).is_ok() {
+                    // lutris may itself use umu or wine so check it first
+                    Some(PlatformOpt::Lutris)
+                } else if self.command.iter().any(|c| is_bottles_cli(c)) {
+                    Some(PlatformOpt::Bottles)
                 } else if let Some(UMU_EXE_NAME | WINE_EXE_NAME) = self
                     .command
                     .first()
                     .and_then(|c| Path::new(c).file_name().and_then(|p| p.to_str()))
                 {
                     Some(PlatformOpt::Umu)
+                } else if matches!(self.launched_steam_app_id(), Ok(Some(_))) {
+                    // run by steam, just not with %command%
+                    Some(PlatformOpt::Steam)
+                } else if cfg!(windows) {
+                    // there's no wine on windows so anything not under steam is native
+                    Some(PlatformOpt::Native)
                 } else {
                     None
                 }
@@ -192,6 +631,40 @@ impl LaunchArgs {
             v => Some(v),
         }
     }
+
+    /// Steam app id the game can be looked up by, if the launch has one
+    /// There is none when the game is given by name with `--game`
+    pub fn steam_app_id(&self) -> Option<SteamId> {
+        if self.manifest_game_override.is_some() {
+            return None;
+        }
+        self.manifest_app_id_override.or_else(|| {
+            if self.resolve_platform()? != PlatformOpt::Steam {
+                return None;
+            }
+            self.launched_steam_app_id().ok().flatten()
+        })
+    }
+
+    /// App id steam is running the game as, from the `AppId=` argument `%command%` has or
+    /// failing that the variables steam and proton set
+    ///
+    /// Steam sets the variables to 0 for games that aren't on steam, which is treated as there
+    /// being no id
+    pub fn launched_steam_app_id(&self) -> Result<Option<SteamId>, SteamIdParseError> {
+        if let Some(id) = self.command.iter().find_map(|c| c.strip_prefix("AppId=")) {
+            return id.parse().map(Some);
+        }
+        for var in STEAM_APP_ID_VARS {
+            if let Ok(id) = std::env::var(var) {
+                let id: SteamId = id.parse()?;
+                if id.id() != 0 {
+                    return Ok(Some(id));
+                }
+            }
+        }
+        Ok(None)
+    }
 }
 
 #[derive(Args, Clone)]
@@ -205,7 +678,13 @@ pub struct FsBackendArgs {
 
 impl ValueEnum for BackendType {
     fn value_variants<'a>() -> &'a [Self] {
-        &[BackendType::Filesystem, BackendType::WebDav]
+        &[
+            BackendType::Filesystem,
+            BackendType::WebDav,
+            BackendType::Rclone,
+            BackendType::Git,
+            BackendType::Ftp,
+        ]
     }
 
     fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
@@ -216,6 +695,33 @@ impl ValueEnum for BackendType {
                     .help("filesystem backend which copies the files to local folder"),
             ),
             BackendType::WebDav => Some(PossibleValue::new("webdav").help("webdav backend")),
+            BackendType::Rclone => Some(
+                PossibleValue::new("rclone")
+                    .help("any remote configured in rclone, uses the rclone command"),
+            ),
+            BackendType::Git => {
+                Some(PossibleValue::new("git").help("local git repository, each sync is a commit"))
+            }
+            BackendType::Ftp => Some(
+                PossibleValue::new("ftp").help("ftp server, use --ftp-tls for ftps (explicit tls)"),
+            ),
+        }
+    }
+}
+
+impl ValueEnum for WebDavAuth {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[WebDavAuth::Basic, WebDavAuth::Bearer]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            WebDavAuth::Basic => {
+                Some(PossibleValue::new("basic").help("http basic auth with username and password"))
+            }
+            WebDavAuth::Bearer => Some(
+                PossibleValue::new("bearer").help("bearer token sent in the Authorization header"),
+            ),
         }
     }
 }
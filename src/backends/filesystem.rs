@@ -2,13 +2,14 @@ use std::path::{Path, PathBuf};
 use tokio::fs;
 use tracing::debug;
 
-use super::Result;
+use super::{ListEntry, Result};
 
 pub struct FilesystemStore {
     root: PathBuf,
 }
 impl FilesystemStore {
     pub fn new(root: PathBuf) -> Result<Self, std::io::Error> {
+        check_root(&root)?;
         if !std::fs::exists(&root)? {
             std::fs::create_dir_all(&root)?;
         }
@@ -24,6 +25,30 @@ impl FilesystemStore {
     }
 }
 
+/// Check `root` is a directory we can write to, or that it can be created as one
+///
+/// Nothing is created, if `root` doesn't exist its closest existing parent is checked instead
+pub fn check_root(root: &Path) -> Result<(), std::io::Error> {
+    let mut dir = root;
+    while !std::fs::exists(dir)? {
+        dir = match dir.parent() {
+            Some(p) if p.as_os_str().is_empty() => Path::new("."),
+            Some(p) => p,
+            None => return Ok(()),
+        };
+    }
+    if !dir.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotADirectory,
+            format!("{dir:?} is not a directory"),
+        ));
+    }
+    let probe = dir.join(format!(".cinc-write-check-{}", uuid::Uuid::new_v4()));
+    std::fs::File::create(&probe)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{dir:?} is not writable: {e}")))?;
+    std::fs::remove_file(probe)
+}
+
 impl FilesystemStore {
     pub async fn write_file(&self, at: &std::path::Path, bytes: &[u8]) -> Result<()> {
         let p = self.filename(at);
@@ -42,4 +67,93 @@ impl FilesystemStore {
     pub async fn exists(&self, f: &Path) -> Result<bool> {
         Ok(std::fs::exists(self.filename(f))?)
     }
+
+    pub async fn remove_file(&self, at: &Path) -> Result<()> {
+        Ok(fs::remove_file(self.filename(at)).await?)
+    }
+
+    pub async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        Ok(fs::rename(self.filename(from), self.filename(to)).await?)
+    }
+
+    pub async fn remove_dir(&self, at: &Path) -> Result<()> {
+        Ok(fs::remove_dir_all(self.filename(at)).await?)
+    }
+
+    pub async fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        list_dir(&self.filename(dir), dir).await
+    }
+}
+
+/// List `full`, giving paths under `dir` as that is how the backend refers to them
+pub(super) async fn list_dir(full: &Path, dir: &Path) -> Result<Vec<ListEntry>> {
+    let mut rd = match fs::read_dir(full).await {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    while let Some(e) = rd.next_entry().await? {
+        entries.push(ListEntry {
+            path: dir.join(e.file_name()),
+            is_dir: e.file_type().await?.is_dir(),
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use assert_fs::{
+        TempDir,
+        prelude::{FileTouch, PathChild, PathCreateDir},
+    };
+    use test_log::test;
+
+    use crate::backends::ListEntry;
+
+    use super::{FilesystemStore, check_root};
+
+    #[test(tokio::test)]
+    async fn list_gives_files_and_dirs() {
+        let root = TempDir::new().unwrap();
+        root.child("game").child("saves").create_dir_all().unwrap();
+        root.child("game").child("mod-meta.ron").touch().unwrap();
+        let store = FilesystemStore::new(root.path().to_owned()).unwrap();
+
+        let mut entries = store.list(Path::new("game")).await.unwrap();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            entries,
+            [
+                ListEntry {
+                    path: PathBuf::from("game/mod-meta.ron"),
+                    is_dir: false
+                },
+                ListEntry {
+                    path: PathBuf::from("game/saves"),
+                    is_dir: true
+                },
+            ]
+        );
+        assert!(store.list(Path::new("missing")).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn root_must_be_a_writable_dir() {
+        let root = TempDir::new().unwrap();
+        let file = root.child("file");
+        file.touch().unwrap();
+        assert!(FilesystemStore::new(file.to_path_buf()).is_err());
+        assert!(FilesystemStore::new(file.join("store")).is_err());
+
+        let store = root.child("a").child("store");
+        check_root(&store).unwrap();
+        assert!(!store.exists(), "checking created the root");
+        FilesystemStore::new(store.to_path_buf()).unwrap();
+        assert!(store.is_dir());
+        assert_eq!(std::fs::read_dir(&store).unwrap().count(), 0);
+    }
 }
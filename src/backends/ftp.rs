@@ -0,0 +1,447 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use suppaftp::{
+    FtpError, Status,
+    async_native_tls::TlsConnector,
+    list::{File, ListParser},
+    tokio::{AsyncNativeTlsConnector, AsyncNativeTlsFtpStream},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{MappedMutexGuard, Mutex, MutexGuard},
+};
+use tracing::debug;
+
+use super::{ListEntry, Result, resolve_secret};
+use crate::{config::FtpInfo, secrets::SecretsApi, ui};
+
+/// Size of the pieces transfers are done in, progress is updated after each one
+const TRANSFER_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Backend for plain FTP or FTPS (explicit TLS) servers
+///
+/// The connection is opened on first use and kept for the rest of the run
+pub struct FtpStore<'s> {
+    cfg: FtpInfo,
+    secrets: &'s SecretsApi<'s>,
+    timeout: Duration,
+    conn: Mutex<Option<Box<AsyncNativeTlsFtpStream>>>,
+}
+
+/// Whether the server replied that the file or directory doesn't exist
+fn is_unavailable(e: &FtpError) -> bool {
+    matches!(e, FtpError::UnexpectedResponse(r) if r.status == Status::FileUnavailable)
+}
+
+/// Whether the server doesn't understand a command, e.g. MLST on older servers
+fn is_unsupported(e: &FtpError) -> bool {
+    matches!(
+        e,
+        FtpError::UnexpectedResponse(r)
+            if matches!(r.status, Status::BadCommand | Status::NotImplemented | Status::CommandNotImplemented)
+    )
+}
+
+/// Parse a line of a MLSD or LIST listing, `.` and `..` are skipped
+fn parse_listing_line(line: &str, mlsd: bool) -> Option<File> {
+    let file = if mlsd {
+        ListParser::parse_mlsd(line).ok()?
+    } else {
+        ListParser::parse_posix(line)
+            .or_else(|_| ListParser::parse_dos(line))
+            .ok()?
+    };
+    (!matches!(file.name(), "." | "..")).then_some(file)
+}
+
+impl<'s> FtpStore<'s> {
+    pub fn new(cfg: FtpInfo, secrets: &'s SecretsApi, timeout: Duration) -> Self {
+        Self {
+            cfg,
+            secrets,
+            timeout,
+            conn: Mutex::new(None),
+        }
+    }
+
+    async fn connect(&self) -> Result<AsyncNativeTlsFtpStream> {
+        debug!(
+            "connecting to ftp server {}:{}",
+            self.cfg.host, self.cfg.port
+        );
+        let addr = tokio::net::lookup_host((self.cfg.host.as_str(), self.cfg.port))
+            .await?
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("could not resolve ftp host {}", self.cfg.host),
+                )
+            })?;
+        let mut ftp = AsyncNativeTlsFtpStream::connect_timeout(addr, self.timeout).await?;
+        if self.cfg.tls {
+            ftp = ftp
+                .into_secure(
+                    AsyncNativeTlsConnector::from(TlsConnector::new()),
+                    &self.cfg.host,
+                )
+                .await?;
+        }
+        let password = match &self.cfg.psk {
+            Some(s) => resolve_secret(s, self.secrets).await?,
+            None => String::new(),
+        };
+        ftp.login(self.cfg.username.as_str(), password.as_str())
+            .await?;
+        ftp.transfer_type(suppaftp::types::FileType::Binary).await?;
+        Ok(ftp)
+    }
+
+    /// The connection, opening it if this is the first use
+    async fn stream(&self) -> Result<MappedMutexGuard<'_, AsyncNativeTlsFtpStream>> {
+        let mut conn = self.conn.lock().await;
+        if conn.is_none() {
+            *conn = Some(Box::new(self.connect().await?));
+        }
+        Ok(MutexGuard::map(conn, |c| {
+            c.as_deref_mut().expect("just connected")
+        }))
+    }
+
+    fn path_for(&self, at: &Path) -> String {
+        self.cfg
+            .root
+            .join(at)
+            .to_str()
+            .expect("ftp path is not valid utf8")
+            .trim_end_matches('/')
+            .to_owned()
+    }
+
+    /// Create `dir` and any missing parents, directories which already exist are fine
+    async fn mkdir_all(&self, dir: &Path) -> Result<()> {
+        let dir = self.cfg.root.join(dir);
+        debug!("mkdir all for {dir:?}");
+        let mut ftp = self.stream().await?;
+        for p in dir.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            let p = p.to_str().expect("ftp path is not valid utf8");
+            if p.is_empty() || p == "/" {
+                continue;
+            }
+            match ftp.mkdir(p).await {
+                Ok(()) => {}
+                // servers use 550 for both already existing and actual failures, the write
+                // after this will fail if it was the latter
+                Err(e) if is_unavailable(&e) => debug!("not creating {p}: {e}"),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_lines(&self, dir: &str) -> Result<(Vec<String>, bool)> {
+        let mut ftp = self.stream().await?;
+        match ftp.mlsd(Some(dir)).await {
+            Ok(lines) => Ok((lines, true)),
+            Err(e) if is_unsupported(&e) => {
+                debug!("server doesn't support MLSD, falling back to LIST");
+                Ok((ftp.list(Some(dir)).await?, false))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl FtpStore<'_> {
+    pub async fn write_file(&self, at: &Path, bytes: &[u8]) -> Result<()> {
+        debug!("writing to {at:?}");
+        let parent = at.parent().expect("no parent path for file");
+        if !self.exists(parent).await? {
+            debug!("creating parent directories for {at:?}");
+            self.mkdir_all(parent).await?;
+        }
+        let path = self.path_for(at);
+        let mut ftp = self.stream().await?;
+        let progress = ui::byte_progress("uploading", Some(bytes.len() as u64));
+        let mut stream = ftp.put_with_stream(&path).await?;
+        for chunk in bytes.chunks(TRANSFER_CHUNK_SIZE) {
+            stream.write_all(chunk).await?;
+            progress.inc(chunk.len() as u64);
+        }
+        ftp.finalize_put_stream(stream).await?;
+        progress.finish_and_clear();
+        Ok(())
+    }
+
+    pub async fn read_file(&self, at: &Path) -> Result<Vec<u8>> {
+        debug!("read {at:?}");
+        let path = self.path_for(at);
+        let mut ftp = self.stream().await?;
+        let size = ftp.size(&path).await.ok();
+        let progress = ui::byte_progress("downloading", size.map(|s| s as u64));
+        let mut stream = ftp.retr_as_stream(&path).await?;
+        let mut data = Vec::with_capacity(size.unwrap_or(0));
+        let mut chunk = vec![0; TRANSFER_CHUNK_SIZE];
+        loop {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            progress.inc(n as u64);
+            data.extend_from_slice(&chunk[..n]);
+        }
+        ftp.finalize_retr_stream(stream).await?;
+        progress.finish_and_clear();
+        Ok(data)
+    }
+
+    /// Uses MLST so directories can be checked too, or SIZE for servers without it
+    pub async fn exists(&self, f: &Path) -> Result<bool> {
+        debug!("check exists for {f:?}");
+        let path = self.path_for(f);
+        if path.is_empty() || path == "/" {
+            return Ok(true);
+        }
+        let mut ftp = self.stream().await?;
+        match ftp.mlst(Some(&path)).await {
+            Ok(_) => return Ok(true),
+            Err(e) if is_unavailable(&e) => return Ok(false),
+            Err(e) if is_unsupported(&e) => {
+                debug!("server doesn't support MLST, falling back to SIZE");
+            }
+            Err(e) => return Err(e.into()),
+        }
+        match ftp.size(&path).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_unavailable(&e) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn remove_file(&self, at: &Path) -> Result<()> {
+        debug!("delete {at:?}");
+        let path = self.path_for(at);
+        self.stream().await?.rm(&path).await?;
+        Ok(())
+    }
+
+    /// FTP can only remove empty directories so everything in it is removed first
+    pub async fn remove_dir(&self, at: &Path) -> Result<()> {
+        debug!("delete dir {at:?}");
+        for entry in self.list(at).await? {
+            if entry.is_dir {
+                Box::pin(self.remove_dir(&entry.path)).await?;
+            } else {
+                self.remove_file(&entry.path).await?;
+            }
+        }
+        let path = self.path_for(at);
+        self.stream().await?.rmdir(&path).await?;
+        Ok(())
+    }
+
+    pub async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        debug!("move {from:?} to {to:?}");
+        let (from_path, to_path) = (self.path_for(from), self.path_for(to));
+        let res = self.stream().await?.rename(&from_path, &to_path).await;
+        match res {
+            Ok(()) => Ok(()),
+            // not every server will replace an existing file, but 550 is also what a missing
+            // source gets so `to` is only removed if there is something to replace it with
+            Err(e) if is_unavailable(&e) => {
+                if !self.exists(from).await? {
+                    return Err(e.into());
+                }
+                if self.exists(to).await? {
+                    debug!("rename failed ({e}), removing {to_path} and trying again");
+                    self.stream().await?.rm(&to_path).await?;
+                }
+                self.stream().await?.rename(&from_path, &to_path).await?;
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        debug!("list {dir:?}");
+        let path = self.path_for(dir);
+        let (lines, mlsd) = match self.list_lines(&path).await {
+            Ok(r) => r,
+            Err(super::BackendError::Ftp(e)) if is_unavailable(&e) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        Ok(lines
+            .iter()
+            .filter_map(|l| {
+                let f = parse_listing_line(l, mlsd);
+                if f.is_none() {
+                    debug!("skipping unparsable listing line {l:?}");
+                }
+                f
+            })
+            .map(|f| ListEntry {
+                path: dir.join(PathBuf::from(f.name())),
+                is_dir: f.is_directory(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        path::Path,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use test_log::test;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use crate::{
+        backends::ftp::{FtpStore, parse_listing_line},
+        config::FtpInfo,
+        secrets::SecretsApi,
+    };
+
+    /// Just enough of an FTP server for renames, it won't replace an existing file like some
+    /// real servers
+    async fn fake_server(files: Arc<Mutex<BTreeSet<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            let (read, mut write) = conn.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 ready\r\n").await.unwrap();
+            let mut rename_from = None;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let (cmd, arg) = line.split_once(' ').unwrap_or((&line, ""));
+                let arg = arg.to_owned();
+                let reply = {
+                    let mut files = files.lock().unwrap();
+                    match cmd {
+                        "USER" => "331 password please".to_owned(),
+                        "PASS" => "230 logged in".to_owned(),
+                        "TYPE" => "200 ok".to_owned(),
+                        "MLST" if files.contains(&arg) => {
+                            format!("250-listing\r\n type=file; {arg}\r\n250 end")
+                        }
+                        "RNFR" if files.contains(&arg) => {
+                            rename_from = Some(arg);
+                            "350 pending".to_owned()
+                        }
+                        "RNTO" if !files.contains(&arg) => {
+                            files.remove(&rename_from.take().unwrap());
+                            files.insert(arg);
+                            "250 renamed".to_owned()
+                        }
+                        "DELE" if files.remove(&arg) => "250 deleted".to_owned(),
+                        "MLST" | "RNFR" | "RNTO" | "DELE" => "550 unavailable".to_owned(),
+                        _ => "502 not implemented".to_owned(),
+                    }
+                };
+                write
+                    .write_all(format!("{reply}\r\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        port
+    }
+
+    fn test_store<'a>(s: &'a SecretsApi<'a>, port: u16) -> FtpStore<'a> {
+        FtpStore::new(
+            FtpInfo {
+                host: "127.0.0.1".to_owned(),
+                port,
+                username: "anonymous".to_owned(),
+                psk: None,
+                root: "/cinc".into(),
+                tls: false,
+            },
+            s,
+            Duration::from_secs(5),
+        )
+    }
+
+    #[test(tokio::test)]
+    async fn rename_replaces_existing_file() {
+        let files = Arc::new(Mutex::new(BTreeSet::from([
+            "/cinc/mod-meta.ron".to_owned(),
+            "/cinc/mod-meta.ron.tmp".to_owned(),
+        ])));
+        let port = fake_server(files.clone()).await;
+        let s = SecretsApi::new_unavailable();
+        let store = test_store(&s, port);
+        store
+            .rename(Path::new("mod-meta.ron.tmp"), Path::new("mod-meta.ron"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *files.lock().unwrap(),
+            BTreeSet::from(["/cinc/mod-meta.ron".to_owned()])
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn rename_with_missing_source_keeps_destination() {
+        let files = Arc::new(Mutex::new(BTreeSet::from(
+            ["/cinc/mod-meta.ron".to_owned()],
+        )));
+        let port = fake_server(files.clone()).await;
+        let s = SecretsApi::new_unavailable();
+        let store = test_store(&s, port);
+        store
+            .rename(Path::new("mod-meta.ron.tmp"), Path::new("mod-meta.ron"))
+            .await
+            .unwrap_err();
+        assert!(files.lock().unwrap().contains("/cinc/mod-meta.ron"));
+    }
+
+    #[test]
+    fn paths_are_under_root() {
+        let s = SecretsApi::new_unavailable();
+        let store = FtpStore::new(
+            FtpInfo {
+                host: "localhost".to_owned(),
+                port: 21,
+                username: "anonymous".to_owned(),
+                psk: None,
+                root: "/cinc/game".into(),
+                tls: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        );
+        assert_eq!(store.path_for(Path::new("a/b.ron")), "/cinc/game/a/b.ron");
+        assert_eq!(store.path_for(Path::new("")), "/cinc/game");
+    }
+
+    #[test]
+    fn listings_are_parsed() {
+        let f = parse_listing_line(
+            "type=file;size=12;modify=20240101000000; mod-meta.ron",
+            true,
+        )
+        .unwrap();
+        assert_eq!(f.name(), "mod-meta.ron");
+        assert!(!f.is_directory());
+        let d = parse_listing_line(
+            "drwxr-xr-x    2 1000     1000         4096 Jan 01 00:00 files",
+            false,
+        )
+        .unwrap();
+        assert_eq!(d.name(), "files");
+        assert!(d.is_directory());
+        assert!(parse_listing_line("type=cdir;modify=20240101000000; .", true).is_none());
+    }
+}
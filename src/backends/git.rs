@@ -0,0 +1,302 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use tokio::{fs, process::Command, sync::Mutex};
+use tracing::debug;
+
+use super::{
+    BackendError, LOCK_FILE, ListEntry, Result, SYNC_TIME_FILE, SyncMetadata, filesystem::list_dir,
+};
+
+const GIT_EXE_NAME: &str = "git";
+
+/// Backend that keeps the files in a git repository, committing each sync
+///
+/// Files are written to the working tree and staged, a commit is made when the sync metadata
+/// is written as that is always the last step of an upload
+pub struct GitStore {
+    program: PathBuf,
+    repo: PathBuf,
+    /// Directory inside the repo for this game
+    dir: PathBuf,
+    /// Remote to push to after each commit
+    push_remote: Option<String>,
+    /// Held while running git, concurrent commands fail on the repo's index.lock
+    running: Mutex<()>,
+}
+
+impl GitStore {
+    /// Creates the repository at `repo` if it doesn't exist
+    pub fn new(repo: PathBuf, dir: PathBuf, push_remote: Option<String>) -> Result<Self> {
+        Self::with_program(GIT_EXE_NAME.into(), repo, dir, push_remote)
+    }
+
+    fn with_program(
+        program: PathBuf,
+        repo: PathBuf,
+        dir: PathBuf,
+        push_remote: Option<String>,
+    ) -> Result<Self> {
+        if !std::fs::exists(&repo)? {
+            std::fs::create_dir_all(&repo)?;
+        }
+        if !std::fs::exists(repo.join(".git"))? {
+            debug!("creating git repository at {repo:?}");
+            let out = match std::process::Command::new(&program)
+                .arg("-C")
+                .arg(&repo)
+                .args(["init", "-q"])
+                .output()
+            {
+                Ok(out) => out,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(BackendError::GitNotFound);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            Self::check(&["init"], out)?;
+        }
+        Ok(Self {
+            program,
+            repo,
+            dir,
+            push_remote,
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Path of a file relative to the repo, for pathspecs
+    fn repo_path(&self, at: &Path) -> PathBuf {
+        self.dir.join(at)
+    }
+
+    fn filename(&self, at: &Path) -> PathBuf {
+        self.repo.join(self.repo_path(at))
+    }
+
+    fn check(args: &[&str], out: Output) -> Result<Output> {
+        if out.status.success() {
+            Ok(out)
+        } else {
+            Err(BackendError::Git {
+                args: args.join(" "),
+                stderr: String::from_utf8_lossy(&out.stderr).trim().to_owned(),
+            })
+        }
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<Output> {
+        let _running = self.running.lock().await;
+        debug!("running git {args:?}");
+        let out = Command::new(&self.program)
+            .arg("-C")
+            .arg(&self.repo)
+            // so commits don't depend on the user's git config
+            .args(["-c", "user.name=cinc", "-c", "user.email=cinc@localhost"])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        Self::check(args, out)
+    }
+
+    /// Stage a file, including it having been removed
+    ///
+    /// The lock file is never staged as it shouldn't end up in the history
+    async fn stage(&self, at: &Path) -> Result<()> {
+        if at == Path::new(LOCK_FILE) {
+            return Ok(());
+        }
+        let path = self.repo_path(at);
+        self.run(&["add", "-A", "--", path.to_str().expect("path is not utf8")])
+            .await?;
+        Ok(())
+    }
+
+    /// Commit everything staged using the sync metadata for the author and date
+    async fn commit(&self) -> Result<()> {
+        let metadata: SyncMetadata =
+            ron::de::from_bytes(&fs::read(self.filename(Path::new(SYNC_TIME_FILE))).await?)?;
+        let host = &metadata.last_write_hostname;
+        let author = format!("{host} <cinc@{host}>");
+        let date = metadata.last_write_timestamp.to_rfc3339();
+        let message = format!(
+            "sync from {host} with cinc {}",
+            metadata.last_write_cinc_version
+        );
+        let dir = self.dir.to_str().expect("path is not utf8");
+        self.run(&[
+            "commit",
+            "-q",
+            "--allow-empty",
+            "--author",
+            &author,
+            "--date",
+            &date,
+            "-m",
+            &message,
+            "--",
+            dir,
+        ])
+        .await?;
+        self.push().await
+    }
+
+    async fn push(&self) -> Result<()> {
+        if let Some(remote) = &self.push_remote {
+            debug!("pushing to {remote}");
+            self.run(&["push", "-q", remote, "HEAD"]).await?;
+        }
+        Ok(())
+    }
+}
+
+impl GitStore {
+    pub async fn write_file(&self, at: &Path, bytes: &[u8]) -> Result<()> {
+        let p = self.filename(at);
+        debug!("writing to {p:?}");
+        if let Some(parent) = p.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&p, bytes).await?;
+        self.stage(at).await?;
+        if at == Path::new(SYNC_TIME_FILE) {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read_file(&self, at: &Path) -> Result<Vec<u8>> {
+        Ok(fs::read(self.filename(at)).await?)
+    }
+
+    pub async fn exists(&self, f: &Path) -> Result<bool> {
+        Ok(std::fs::exists(self.filename(f))?)
+    }
+
+    pub async fn remove_file(&self, at: &Path) -> Result<()> {
+        fs::remove_file(self.filename(at)).await?;
+        self.stage(at).await
+    }
+
+    /// Removing a directory is committed straight away as it isn't part of a sync
+    pub async fn remove_dir(&self, at: &Path) -> Result<()> {
+        fs::remove_dir_all(self.filename(at)).await?;
+        self.stage(at).await?;
+        let path = self.repo_path(at);
+        let path = path.to_str().expect("path is not utf8");
+        self.run(&["commit", "-q", "-m", &format!("remove {path}"), "--", path])
+            .await?;
+        self.push().await
+    }
+
+    pub async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(self.filename(from), self.filename(to)).await?;
+        self.stage(from).await?;
+        self.stage(to).await?;
+        if to == Path::new(SYNC_TIME_FILE) {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        let mut entries = list_dir(&self.filename(dir), dir).await?;
+        // only possible when this store is for the whole repo
+        entries.retain(|e| self.repo_path(&e.path) != Path::new(".git"));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, process::Command};
+
+    use assert_fs::TempDir;
+    use test_log::test;
+
+    use crate::backends::{
+        BackendError, FileMetaTable, StorageBackend, SyncMetadata, git::GitStore,
+    };
+
+    #[test]
+    fn missing_git_is_a_clear_error() {
+        let root = TempDir::new().unwrap();
+        let r = GitStore::with_program(
+            "./this/does/not/exist/git".into(),
+            root.path().join("repo"),
+            "game".into(),
+            None,
+        );
+        assert!(matches!(r, Err(BackendError::GitNotFound)));
+    }
+
+    #[test(tokio::test)]
+    async fn each_sync_is_a_commit() {
+        let root = TempDir::new().unwrap();
+        let repo = root.path().join("repo");
+        let backend =
+            StorageBackend::new(GitStore::new(repo.clone(), "game".into(), None).unwrap());
+        let metadata = SyncMetadata::from_sys_info(
+            FileMetaTable {
+                entries: Vec::new(),
+                oldest_modified_time: chrono::Utc::now(),
+            },
+            String::new(),
+        );
+
+        for contents in ["first", "second"] {
+            backend
+                .write_file(Path::new("archive.tar.xz"), contents.as_bytes())
+                .await
+                .unwrap();
+            backend.write_sync_time(&metadata).await.unwrap();
+        }
+
+        let log = Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["log", "--format=%an", "--", "game"])
+            .output()
+            .unwrap();
+        let authors = String::from_utf8(log.stdout).unwrap();
+        assert_eq!(
+            authors.lines().collect::<Vec<_>>(),
+            [metadata.last_write_hostname.as_str(); 2]
+        );
+        assert_eq!(
+            backend
+                .read_file(Path::new("archive.tar.xz"))
+                .await
+                .unwrap(),
+            b"second"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn concurrent_writes_dont_collide() {
+        let root = TempDir::new().unwrap();
+        let backend = StorageBackend::new(
+            GitStore::new(root.path().join("repo"), "game".into(), None).unwrap(),
+        );
+
+        let names = (0..8).map(|i| format!("file{i}")).collect::<Vec<_>>();
+        futures_util::future::try_join_all(
+            names
+                .iter()
+                .map(|name| backend.write_file(Path::new(name), name.as_bytes())),
+        )
+        .await
+        .unwrap();
+        for name in &names {
+            assert_eq!(
+                backend.read_file(Path::new(name)).await.unwrap(),
+                name.as_bytes()
+            );
+        }
+    }
+}
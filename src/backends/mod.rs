@@ -1,20 +1,31 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
 
 use chrono::{DateTime, Utc};
 use filesystem::FilesystemStore;
+use ftp::FtpStore;
+use git::GitStore;
+use rclone::RcloneStore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use typesum::sumtype;
 use webdav::WebDavStore;
 
 use crate::{
-    config::{BackendInfo, BackendTy, WebDavInfo},
+    config::{BackendInfo, BackendTy, FtpInfo, Secret, WebDavInfo},
     curr_crate_ver,
     manifest::{TemplateError, TemplateInfo, TemplatePath},
     secrets::SecretsApi,
 };
 
 pub mod filesystem;
+pub mod ftp;
+pub mod git;
+pub mod rclone;
 pub mod webdav;
 
 #[derive(Debug, Error)]
@@ -40,13 +51,68 @@ pub enum BackendError {
     #[error(transparent)]
     SecretService(#[from] secret_service::Error),
 
+    #[error(transparent)]
+    Xml(#[from] quick_xml::Error),
+
+    #[error(transparent)]
+    Ftp(#[from] suppaftp::FtpError),
+
+    #[error("invalid directory listing from webdav server: {0}")]
+    InvalidWebDavListing(String),
+
     #[error("could not find secret '{0}' in system store")]
     CouldNotLocateSecret(String),
+
+    #[error("could not find rclone, it must be installed and on the PATH to use an rclone backend")]
+    RcloneNotFound,
+
+    #[error("rclone {args} failed: {stderr}")]
+    Rclone { args: String, stderr: String },
+
+    #[error("could not find git, it must be installed and on the PATH to use a git backend")]
+    GitNotFound,
+
+    #[error("git {args} failed: {stderr}")]
+    Git { args: String, stderr: String },
 }
 
 type Result<T, E = BackendError> = std::result::Result<T, E>;
 
+/// Get the value of a secret from the config, looking it up in the system keyring if it is kept there
+async fn resolve_secret(secret: &Secret, secrets: &SecretsApi<'_>) -> Result<String> {
+    match secret {
+        Secret::SystemSecret(name) => {
+            assert!(
+                secrets.available(),
+                "system secrets must be available to use them in a config"
+            );
+            let s = secrets.get_item(name).await?;
+            let sv = s.ok_or_else(|| BackendError::CouldNotLocateSecret(name.to_owned()))?;
+            Ok(String::from_utf8(sv).expect("failed to convert from secret to utf8"))
+        }
+        Secret::Plain(p) => Ok(p.to_owned()),
+    }
+}
+
 pub const SYNC_TIME_FILE: &str = "mod-meta.ron";
+const SYNC_TIME_TMP_FILE: &str = "mod-meta.ron.tmp";
+/// Metadata of the config archive, kept apart from [`SYNC_TIME_FILE`] so it is never downloaded
+/// by a sync
+pub const CONFIG_META_FILE: &str = "config-meta.ron";
+const CONFIG_META_TMP_FILE: &str = "config-meta.ron.tmp";
+/// Directory files are stored under for incremental syncs
+pub const INCREMENTAL_DIR: &str = "files";
+pub const LOCK_FILE: &str = "sync.lock";
+/// Placeholder in a backend root for where the game's directory goes
+pub const GAME_PLACEHOLDER: &str = "{game}";
+
+/// An entry in a directory listing from a backend
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListEntry {
+    /// Path relative to the backend root, can be passed straight to the other backend methods
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct SyncMetadata {
@@ -55,6 +121,30 @@ pub struct SyncMetadata {
     pub file_table: FileMetaTable,
     #[serde(default = "default_last_write_cinc_version")]
     pub last_write_cinc_version: semver::Version,
+    /// sha256 of the archive, written by versions after 0.2.3
+    #[serde(default)]
+    pub archive_hash: Option<String>,
+    /// Files are stored individually under [`INCREMENTAL_DIR`] rather than in one archive
+    ///
+    /// Only written by 0.3 and later, 0.2 doesn't know about it so it is kept out by the version
+    /// check rather than finding no archive
+    #[serde(default)]
+    pub incremental: bool,
+    /// The most recent writes, newest first and including this one. At most
+    /// [`MAX_LAST_WRITERS`] are kept
+    #[serde(default)]
+    pub last_writers: Vec<WriterInfo>,
+}
+
+/// How many previous writes are kept in [`SyncMetadata::last_writers`]
+pub const MAX_LAST_WRITERS: usize = 5;
+
+/// Who wrote to a remote and when
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct WriterInfo {
+    pub hostname: String,
+    pub timestamp: chrono::DateTime<Utc>,
+    pub cinc_version: semver::Version,
 }
 
 impl SyncMetadata {
@@ -73,7 +163,7 @@ impl SyncMetadata {
     /// OR a strictly younger breaking change, e.g. 0.3.0 is allowed to write when previousely
     /// 0.2.2 wrote but NOT the other way around as we want to enforce an upgrade here
     pub fn is_version_write_compatabible(&self) -> bool {
-        check_version_compat_write(&self.last_write_cinc_version, &curr_crate_ver())
+        check_version_compat_write(&curr_crate_ver(), &self.last_write_cinc_version)
     }
 }
 
@@ -89,10 +179,18 @@ fn default_last_write_cinc_version() -> semver::Version {
     semver::Version::new(0, 2, 1)
 }
 
+/// Hash of an archive or file as stored in the metadata
+pub fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMetaEntry {
     pub template: TemplatePath,
     pub remote_path: PathBuf,
+    /// sha256 of the file, written by versions after 0.2.3
+    #[serde(default)]
+    pub hash: Option<String>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMetaTable {
@@ -111,18 +209,91 @@ impl FileMetaTable {
     }
 }
 
-impl SyncMetadata {
-    pub fn from_sys_info(file_table: FileMetaTable) -> Self {
-        let last_write_timestamp = chrono::Local::now().to_utc();
-        let last_write_hostname = gethostname::gethostname()
+/// Written to the remote while a device has the game running
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct SyncLock {
+    pub hostname: String,
+    pub timestamp: chrono::DateTime<Utc>,
+}
+
+impl SyncLock {
+    pub fn now() -> Self {
+        Self {
+            hostname: device_name(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Whether this device took the lock
+    pub fn is_ours(&self) -> bool {
+        self.hostname == device_name()
+    }
+}
+
+/// Set from the config's `device_name`, the hostname is used if it isn't
+static DEVICE_NAME: OnceLock<String> = OnceLock::new();
+
+/// Record writes and locks from this device as `name` rather than its hostname. Only the first
+/// call has any effect
+pub fn set_device_name(name: String) {
+    let _ = DEVICE_NAME.set(name);
+}
+
+/// What this device is recorded as in the metadata and locks
+fn device_name() -> String {
+    DEVICE_NAME.get().cloned().unwrap_or_else(|| {
+        gethostname::gethostname()
             .to_str()
             .expect("failed to convert hostname to string")
-            .to_owned();
+            .to_owned()
+    })
+}
+
+impl SyncMetadata {
+    pub fn from_sys_info(file_table: FileMetaTable, archive_hash: String) -> Self {
+        Self::now(file_table, Some(archive_hash), false)
+    }
+
+    /// Metadata for files stored individually rather than in an archive
+    pub fn incremental_from_sys_info(file_table: FileMetaTable) -> Self {
+        Self::now(file_table, None, true)
+    }
+
+    /// Keep the writers from the metadata this replaces
+    pub fn with_history(mut self, previous: Option<&SyncMetadata>) -> Self {
+        if let Some(previous) = previous {
+            if previous.last_writers.is_empty() {
+                // written before the history was kept
+                self.last_writers.push(WriterInfo {
+                    hostname: previous.last_write_hostname.clone(),
+                    timestamp: previous.last_write_timestamp,
+                    cinc_version: previous.last_write_cinc_version.clone(),
+                });
+            } else {
+                self.last_writers
+                    .extend(previous.last_writers.iter().cloned());
+            }
+        }
+        self.last_writers.truncate(MAX_LAST_WRITERS);
+        self
+    }
+
+    fn now(file_table: FileMetaTable, archive_hash: Option<String>, incremental: bool) -> Self {
+        let last_write_timestamp = chrono::Local::now().to_utc();
+        let last_write_hostname = device_name();
+        let writer = WriterInfo {
+            hostname: last_write_hostname.clone(),
+            timestamp: last_write_timestamp,
+            cinc_version: curr_crate_ver(),
+        };
         Self {
             last_write_timestamp,
             last_write_hostname,
             file_table,
             last_write_cinc_version: curr_crate_ver(),
+            archive_hash,
+            incremental,
+            last_writers: vec![writer],
         }
     }
 }
@@ -130,6 +301,9 @@ impl SyncMetadata {
 pub enum StorageBackendTy<'s> {
     WebDav(WebDavStore<'s>),
     Fs(FilesystemStore),
+    Rclone(RcloneStore),
+    Git(GitStore),
+    Ftp(FtpStore<'s>),
 }
 pub struct StorageBackend<'s> {
     backend: StorageBackendTy<'s>,
@@ -141,6 +315,9 @@ macro_rules! forward {
             match &self.backend {
                 StorageBackendTy::WebDav(b) => b.$name($($argname),*).await,
                 StorageBackendTy::Fs(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Rclone(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Git(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Ftp(b) => b.$name($($argname),*).await,
             }
         }
     };
@@ -150,6 +327,9 @@ macro_rules! forward {
             match &mut self.backend {
                 StorageBackendTy::WebDav(b) => b.$name($($argname),*).await,
                 StorageBackendTy::Fs(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Rclone(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Git(b) => b.$name($($argname),*).await,
+                StorageBackendTy::Ftp(b) => b.$name($($argname),*).await,
             }
         }
     }
@@ -164,6 +344,31 @@ impl<'s> StorageBackend<'s> {
     forward!(fn write_file(at: &Path, bytes: &[u8]) -> ());
     forward!(fn read_file(at: &Path) -> Vec<u8>);
     forward!(fn exists(at: &Path) -> bool);
+    forward!(fn rename(from: &Path, to: &Path) -> ());
+    forward!(fn remove_file(at: &Path) -> ());
+    // Removes a directory and everything in it
+    forward!(fn remove_dir(at: &Path) -> ());
+    // Lists the immediate children of a directory, a directory that doesn't exist is empty
+    forward!(fn list(dir: &Path) -> Vec<ListEntry>);
+
+    /// [`Self::write_file`] giving up the data, which lets backends that stream it avoid copying it
+    pub async fn write_file_owned(&self, at: &Path, bytes: Vec<u8>) -> Result<()> {
+        match &self.backend {
+            StorageBackendTy::WebDav(b) => b.write_owned(at, bytes).await,
+            _ => self.write_file(at, &bytes).await,
+        }
+    }
+
+    /// Whether files can be written individually, if not then only archives are used
+    pub fn supports_per_file_writes(&self) -> bool {
+        match &self.backend {
+            StorageBackendTy::WebDav(_)
+            | StorageBackendTy::Fs(_)
+            | StorageBackendTy::Rclone(_)
+            | StorageBackendTy::Git(_)
+            | StorageBackendTy::Ftp(_) => true,
+        }
+    }
 
     pub async fn read_file_str(&self, at: &Path) -> Result<String> {
         Ok(String::from_utf8(self.read_file(at).await?)?)
@@ -177,29 +382,165 @@ impl<'s> StorageBackend<'s> {
         Ok(Some(ron::de::from_bytes(&f)?))
     }
 
+    /// Write the metadata, this is atomic so readers will either see the old or new metadata
     pub async fn write_sync_time(&self, metadata: &SyncMetadata) -> Result<()> {
         let data = ron::ser::to_string(metadata)?;
-        self.write_file(Path::new(SYNC_TIME_FILE), data.as_bytes())
-            .await
+        let tmp = Path::new(SYNC_TIME_TMP_FILE);
+        self.write_file(tmp, data.as_bytes()).await?;
+        self.rename(tmp, Path::new(SYNC_TIME_FILE)).await
+    }
+
+    pub async fn read_config_meta(&self) -> Result<Option<SyncMetadata>> {
+        let meta_file = Path::new(CONFIG_META_FILE);
+        if !self.exists(meta_file).await? {
+            return Ok(None);
+        }
+        let f = self.read_file(meta_file).await?;
+        Ok(Some(ron::de::from_bytes(&f)?))
+    }
+
+    /// Write the config archive's metadata, atomically like [`Self::write_sync_time`]
+    pub async fn write_config_meta(&self, metadata: &SyncMetadata) -> Result<()> {
+        let data = ron::ser::to_string(metadata)?;
+        let tmp = Path::new(CONFIG_META_TMP_FILE);
+        self.write_file(tmp, data.as_bytes()).await?;
+        self.rename(tmp, Path::new(CONFIG_META_FILE)).await
+    }
+
+    pub async fn read_lock(&self) -> Result<Option<SyncLock>> {
+        let lock_file = Path::new(LOCK_FILE);
+        if !self.exists(lock_file).await? {
+            return Ok(None);
+        }
+        let f = self.read_file(lock_file).await?;
+        Ok(Some(ron::de::from_bytes(&f)?))
+    }
+
+    pub async fn write_lock(&self, lock: &SyncLock) -> Result<()> {
+        let data = ron::ser::to_string(lock)?;
+        self.write_file(Path::new(LOCK_FILE), data.as_bytes()).await
+    }
+
+    pub async fn remove_lock(&self) -> Result<()> {
+        self.remove_file(Path::new(LOCK_FILE)).await
+    }
+}
+
+/// Where a game's files go under a backend root, at [`GAME_PLACEHOLDER`] if the root has it
+/// otherwise in a directory directly under the root
+///
+/// An empty `game_name` gives the directory the games' directories are in
+fn game_root(root: &Path, game_name: &str) -> PathBuf {
+    let Some(r) = root.to_str().filter(|r| r.contains(GAME_PLACEHOLDER)) else {
+        return root.join(game_name);
+    };
+    if !game_name.is_empty() {
+        return r.replace(GAME_PLACEHOLDER, game_name).into();
+    }
+    let (before, _) = r.split_once(GAME_PLACEHOLDER).expect("checked above");
+    if before.is_empty() || before.ends_with('/') {
+        before.into()
+    } else {
+        // the placeholder is part of a name, e.g. `saves-{game}`
+        Path::new(before)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_owned()
     }
 }
 
+/// The game a directory directly under the root backend is for, the inverse of [`game_root`]
+///
+/// `None` if the directory name does not match the root's [`GAME_PLACEHOLDER`] pattern, e.g.
+/// `other` for a root of `saves-{game}`
+fn game_of_root_dir<'a>(root: &Path, dir_name: &'a str) -> Option<&'a str> {
+    let Some(r) = root.to_str().filter(|r| r.contains(GAME_PLACEHOLDER)) else {
+        return Some(dir_name);
+    };
+    let (before, after) = r.split_once(GAME_PLACEHOLDER).expect("checked above");
+    let prefix = before.rsplit('/').next().unwrap_or_default();
+    let suffix = after.split('/').next().unwrap_or_default();
+    dir_name
+        .strip_prefix(prefix)?
+        .strip_suffix(suffix)
+        .filter(|g| !g.is_empty())
+}
+
 impl BackendInfo {
+    /// Game directories under the root backend for games not in `keep`
+    ///
+    /// Directories which don't match the root's [`GAME_PLACEHOLDER`] pattern are not a game's and
+    /// are never included
+    pub async fn orphaned_games(
+        &self,
+        root_backend: &StorageBackend<'_>,
+        keep: &[&str],
+    ) -> Result<Vec<PathBuf>> {
+        let root = match &self.info {
+            BackendTy::Filesystem { root } | BackendTy::Rclone { root, .. } => root.as_path(),
+            BackendTy::WebDav(info) => info.root.as_path(),
+            BackendTy::Ftp(info) => info.root.as_path(),
+            BackendTy::Git { .. } => Path::new(""),
+        };
+        let mut orphans = root_backend
+            .list(Path::new(""))
+            .await?
+            .into_iter()
+            .filter(|e| e.is_dir)
+            .filter(|e| {
+                e.path
+                    .to_str()
+                    .and_then(|d| game_of_root_dir(root, d))
+                    .is_some_and(|g| !keep.contains(&g))
+            })
+            .map(|e| e.path)
+            .collect::<Vec<_>>();
+        orphans.sort();
+        Ok(orphans)
+    }
+
+    /// Backend for the whole store rather than a single game, e.g. to list every game in it
+    pub fn to_root_backend<'a>(
+        &self,
+        secrets: &'a SecretsApi,
+        timeout: Duration,
+    ) -> Result<StorageBackend<'a>> {
+        self.to_backend("", secrets, timeout)
+    }
+
     pub fn to_backend<'a>(
         &self,
         game_name: &str,
         secrets: &'a SecretsApi,
+        timeout: Duration,
     ) -> Result<StorageBackend<'a>> {
         Ok(match &self.info {
             BackendTy::Filesystem { root } => {
-                StorageBackend::new(FilesystemStore::new(root.join(game_name))?)
+                StorageBackend::new(FilesystemStore::new(game_root(root, game_name))?)
             }
             BackendTy::WebDav(web_dav_info) => StorageBackend::new(WebDavStore::new(
                 WebDavInfo {
-                    root: web_dav_info.root.join(game_name),
+                    root: game_root(&web_dav_info.root, game_name),
                     ..web_dav_info.to_owned()
                 },
                 secrets,
+                timeout,
+            )?),
+            BackendTy::Rclone { remote, root } => {
+                StorageBackend::new(RcloneStore::new(remote, game_root(root, game_name))?)
+            }
+            BackendTy::Git { repo, push_remote } => StorageBackend::new(GitStore::new(
+                repo.to_owned(),
+                game_name.into(),
+                push_remote.to_owned(),
+            )?),
+            BackendTy::Ftp(ftp_info) => StorageBackend::new(FtpStore::new(
+                FtpInfo {
+                    root: game_root(&ftp_info.root, game_name),
+                    ..ftp_info.to_owned()
+                },
+                secrets,
+                timeout,
             )),
         })
     }
@@ -207,9 +548,119 @@ impl BackendInfo {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use assert_fs::{
+        TempDir,
+        prelude::{PathChild, PathCreateDir},
+    };
     use semver::Version;
+    use test_log::test;
+
+    use crate::{
+        backends::{
+            FileMetaTable, MAX_LAST_WRITERS, SyncMetadata, check_version_compat_read,
+            check_version_compat_write, game_root,
+        },
+        config::{BackendInfo, BackendTy},
+        curr_crate_ver,
+        secrets::SecretsApi,
+    };
 
-    use crate::backends::{check_version_compat_read, check_version_compat_write};
+    fn empty_metadata() -> SyncMetadata {
+        SyncMetadata::from_sys_info(
+            FileMetaTable {
+                entries: Vec::new(),
+                oldest_modified_time: chrono::Utc::now(),
+            },
+            String::new(),
+        )
+    }
+
+    #[test]
+    fn game_placeholder_positions_game_dir() {
+        let cases = [
+            ("/cinc", "Game", "/cinc/Game", "/cinc"),
+            ("/cinc/{game}/saves", "Game", "/cinc/Game/saves", "/cinc/"),
+            ("saves-{game}", "Game", "saves-Game", ""),
+            ("/cinc/saves-{game}", "Game", "/cinc/saves-Game", "/cinc"),
+        ];
+        for (root, game, expected, expected_root) in cases {
+            assert_eq!(game_root(Path::new(root), game), PathBuf::from(expected));
+            assert_eq!(game_root(Path::new(root), ""), PathBuf::from(expected_root));
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn prune_only_matches_the_game_pattern() {
+        let dir = TempDir::new().unwrap();
+        for d in ["saves-foo", "saves-bar", "other"] {
+            dir.child(d).create_dir_all().unwrap();
+        }
+        let info = BackendInfo {
+            name: "fs".to_owned(),
+            info: BackendTy::Filesystem {
+                root: dir.join("saves-{game}"),
+            },
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let b = info
+            .to_root_backend(&secrets, Duration::from_secs(1))
+            .unwrap();
+        let orphans = info.orphaned_games(&b, &["foo"]).await.unwrap();
+        assert_eq!(orphans, vec![PathBuf::from("saves-bar")]);
+    }
+
+    #[test]
+    fn incremental_metadata_is_not_read_by_0_2() {
+        let metadata = SyncMetadata::incremental_from_sys_info(FileMetaTable {
+            entries: Vec::new(),
+            oldest_modified_time: chrono::Utc::now(),
+        });
+        assert!(!check_version_compat_read(
+            &Version::new(0, 2, 3),
+            &metadata.last_write_cinc_version
+        ));
+        assert!(!check_version_compat_write(
+            &Version::new(0, 2, 3),
+            &metadata.last_write_cinc_version
+        ));
+    }
+
+    #[test]
+    fn last_writers_are_kept_and_capped() {
+        let mut metadata = empty_metadata();
+        for _ in 0..MAX_LAST_WRITERS + 2 {
+            metadata = empty_metadata().with_history(Some(&metadata));
+        }
+        assert_eq!(metadata.last_writers.len(), MAX_LAST_WRITERS);
+        assert_eq!(
+            metadata.last_writers[0].timestamp,
+            metadata.last_write_timestamp
+        );
+    }
+
+    #[test]
+    fn last_writers_include_metadata_from_before_history() {
+        let mut old = empty_metadata();
+        old.last_writers.clear();
+        let metadata = empty_metadata().with_history(Some(&old));
+        assert_eq!(metadata.last_writers.len(), 2);
+        assert_eq!(metadata.last_writers[1].timestamp, old.last_write_timestamp);
+    }
+
+    #[test]
+    fn newer_remote_blocks_writes() {
+        let mut metadata = empty_metadata();
+        assert!(metadata.is_version_write_compatabible());
+        let curr = curr_crate_ver();
+        metadata.last_write_cinc_version = Version::new(curr.major + 1, 0, 0);
+        assert!(!metadata.is_version_write_compatabible());
+        assert!(!metadata.is_version_read_compatabible());
+    }
 
     #[test]
     fn version_compat_read_leading_zero() {
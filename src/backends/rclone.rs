@@ -0,0 +1,222 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+};
+
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::debug;
+
+use super::{BackendError, ListEntry, Result};
+
+const RCLONE_EXE_NAME: &str = "rclone";
+/// Exit codes rclone uses when the directory or file doesn't exist
+const RCLONE_NOT_FOUND_CODES: [i32; 2] = [3, 4];
+
+/// The parts of an `rclone lsjson` entry we use
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LsJsonEntry {
+    name: String,
+    is_dir: bool,
+}
+
+/// Backend that stores files on any remote rclone has been configured for
+pub struct RcloneStore {
+    program: PathBuf,
+    remote: String,
+    root: PathBuf,
+}
+
+impl RcloneStore {
+    /// `remote` is the name of a remote in the rclone config, with or without the trailing `:`
+    pub fn new(remote: &str, root: PathBuf) -> Result<Self> {
+        Self::with_program(RCLONE_EXE_NAME.into(), remote, root)
+    }
+
+    fn with_program(program: PathBuf, remote: &str, root: PathBuf) -> Result<Self> {
+        match std::process::Command::new(&program)
+            .arg("version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(BackendError::RcloneNotFound);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Self {
+            program,
+            remote: remote.trim_end_matches(':').to_owned(),
+            root,
+        })
+    }
+
+    /// The `remote:path` rclone uses for a file
+    fn remote_path(&self, at: &Path) -> String {
+        let path = self.root.join(at);
+        format!(
+            "{}:{}",
+            self.remote,
+            path.to_str().expect("rclone path is not valid utf8")
+        )
+    }
+
+    fn cmd(&self, args: &[&str]) -> Command {
+        debug!("running rclone {args:?}");
+        let mut c = Command::new(&self.program);
+        c.args(args).stdin(Stdio::null()).kill_on_drop(true);
+        c
+    }
+
+    fn check(args: &[&str], out: Output) -> Result<Output> {
+        if out.status.success() {
+            Ok(out)
+        } else {
+            Err(BackendError::Rclone {
+                args: args.join(" "),
+                stderr: String::from_utf8_lossy(&out.stderr).trim().to_owned(),
+            })
+        }
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<Output> {
+        let out = self.cmd(args).output().await?;
+        Self::check(args, out)
+    }
+}
+
+impl RcloneStore {
+    pub async fn write_file(&self, at: &Path, bytes: &[u8]) -> Result<()> {
+        let dest = self.remote_path(at);
+        let args = ["rcat", dest.as_str()];
+        let mut child = self
+            .cmd(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("rclone stdin was not piped");
+        stdin.write_all(bytes).await?;
+        // rclone only finishes the upload once stdin is closed
+        drop(stdin);
+        Self::check(&args, child.wait_with_output().await?)?;
+        Ok(())
+    }
+
+    pub async fn read_file(&self, at: &Path) -> Result<Vec<u8>> {
+        let src = self.remote_path(at);
+        Ok(self.run(&["cat", &src]).await?.stdout)
+    }
+
+    pub async fn exists(&self, f: &Path) -> Result<bool> {
+        let path = self.remote_path(f);
+        let args = ["lsjson", "--stat", path.as_str()];
+        let out = self.cmd(&args).output().await?;
+        if out
+            .status
+            .code()
+            .is_some_and(|c| RCLONE_NOT_FOUND_CODES.contains(&c))
+        {
+            return Ok(false);
+        }
+        Self::check(&args, out)?;
+        Ok(true)
+    }
+
+    pub async fn remove_file(&self, at: &Path) -> Result<()> {
+        let path = self.remote_path(at);
+        self.run(&["deletefile", &path]).await?;
+        Ok(())
+    }
+
+    pub async fn remove_dir(&self, at: &Path) -> Result<()> {
+        let path = self.remote_path(at);
+        self.run(&["purge", &path]).await?;
+        Ok(())
+    }
+
+    pub async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (self.remote_path(from), self.remote_path(to));
+        self.run(&["moveto", &from, &to]).await?;
+        Ok(())
+    }
+
+    pub async fn list(&self, dir: &Path) -> Result<Vec<ListEntry>> {
+        let path = self.remote_path(dir);
+        let args = ["lsjson", path.as_str()];
+        let out = self.cmd(&args).output().await?;
+        if out
+            .status
+            .code()
+            .is_some_and(|c| RCLONE_NOT_FOUND_CODES.contains(&c))
+        {
+            return Ok(Vec::new());
+        }
+        let out = Self::check(&args, out)?;
+        Ok(parse_lsjson(&out.stdout)?
+            .into_iter()
+            .map(|e| ListEntry {
+                path: dir.join(e.name),
+                is_dir: e.is_dir,
+            })
+            .collect())
+    }
+}
+
+fn parse_lsjson(out: &[u8]) -> Result<Vec<LsJsonEntry>> {
+    serde_json::from_slice(out).map_err(|e| BackendError::Rclone {
+        args: "lsjson".to_owned(),
+        stderr: format!("invalid output: {e}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::backends::BackendError;
+
+    use super::{RcloneStore, parse_lsjson};
+
+    #[test]
+    fn missing_rclone_is_a_clear_error() {
+        let r = RcloneStore::with_program(
+            "./this/does/not/exist/rclone".into(),
+            "remote",
+            "cinc".into(),
+        );
+        assert!(matches!(r, Err(BackendError::RcloneNotFound)));
+    }
+
+    #[test]
+    fn remote_path_joins_remote_and_root() {
+        let store = RcloneStore {
+            program: "rclone".into(),
+            remote: "drive".to_owned(),
+            root: "cinc/game".into(),
+        };
+        assert_eq!(
+            store.remote_path(Path::new("archive.tar.xz")),
+            "drive:cinc/game/archive.tar.xz"
+        );
+    }
+
+    #[test]
+    fn lsjson_output_is_parsed() {
+        let out = br#"[
+{"Path":"saves","Name":"saves","Size":-1,"MimeType":"inode/directory","ModTime":"2024-01-01T00:00:00Z","IsDir":true},
+{"Path":"mod-meta.ron","Name":"mod-meta.ron","Size":120,"MimeType":"text/plain","ModTime":"2024-01-01T00:00:00Z","IsDir":false}
+]"#;
+        let entries = parse_lsjson(out).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.name.as_str(), e.is_dir))
+                .collect::<Vec<_>>(),
+            [("saves", true), ("mod-meta.ron", false)]
+        );
+    }
+}
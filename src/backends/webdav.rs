@@ -1,17 +1,91 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use super::Result;
+use super::{BackendError, ListEntry, Result, resolve_secret};
 
-use crate::{config::WebDavInfo, paths::PathExt, secrets::SecretsApi};
+use crate::{
+    config::{WebDavAuth, WebDavInfo},
+    paths::PathExt,
+    secrets::SecretsApi,
+    ui,
+};
+use futures_util::stream;
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use quick_xml::{Reader, events::Event};
 use reqwest::{
-    Method, StatusCode, {Client, RequestBuilder},
+    Body, Certificate, Method, StatusCode, Url,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    {Client, RequestBuilder},
 };
-use tracing::debug;
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+/// Size of the pieces uploads are sent in, progress is updated after each one
+const TRANSFER_CHUNK_SIZE: usize = 64 * 1024;
+/// Times an upload is tried before giving up
+const UPLOAD_ATTEMPTS: usize = 3;
+/// Uploads smaller than this are restarted rather than resumed when they fail
+const RESUMABLE_MIN_SIZE: usize = 8 * 1024 * 1024;
+/// Feature in the `DAV` header of servers which accept [`PARTIAL_UPDATE_TYPE`] PATCHes
+const PARTIAL_UPDATE_FEATURE: &str = "sabredav-partialupdate";
+const PARTIAL_UPDATE_TYPE: &str = "application/x-sabredav-partialupdate";
+/// Only ask for whether each entry is a collection when listing
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<propfind xmlns="DAV:"><prop><resourcetype/></prop></propfind>"#;
 
 pub struct WebDavStore<'s> {
     client: Client,
     cfg: WebDavInfo,
     secrets: &'s SecretsApi<'s>,
+    /// Limit on the whole of a request, except for transfers of file contents
+    timeout: Duration,
+    partial_updates: OnceCell<bool>,
+}
+
+/// Whether a failed request is worth trying again, i.e. it didn't get an answer or the server
+/// had a temporary problem
+fn is_transient(e: &BackendError) -> bool {
+    let BackendError::Reqwuest(e) = e else {
+        return false;
+    };
+    match e.status() {
+        Some(s) => s.is_server_error() && s != StatusCode::NOT_IMPLEMENTED,
+        None => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+    }
+}
+
+/// Append `path` to the server url, absolute paths are still inside the url's path
+///
+/// Empty and `.` segments are dropped from both so the result has no `./` or `//` in it, which
+/// some servers 404 on. A trailing slash on `path` (or an empty `path`) is kept as it is
+/// significant for collections
+fn join_url(base: &str, path: &Path) -> String {
+    let (scheme, rest) = match base.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, base),
+    };
+    let base_segments = rest.split('/');
+    let path_segments = path.components().filter_map(|c| match c {
+        Component::Normal(s) => Some(s.to_str().expect("non utf8 path")),
+        Component::ParentDir => Some(".."),
+        Component::Prefix(_) | Component::RootDir | Component::CurDir => None,
+    });
+    let mut url = base_segments
+        .chain(path_segments)
+        .filter(|s| !s.is_empty() && *s != ".")
+        .join("/");
+    if let Some(scheme) = scheme {
+        url = format!("{scheme}://{url}");
+    }
+    let raw = path.as_os_str().to_string_lossy();
+    if raw.is_empty() || raw.ends_with('/') {
+        url.push('/');
+    }
+    url
 }
 
 fn calc_mkdir_all_paths(dir: &Path) -> Vec<PathBuf> {
@@ -29,57 +103,126 @@ fn calc_mkdir_all_paths(dir: &Path) -> Vec<PathBuf> {
     r
 }
 
+/// Decode the `%XX` escapes in a href
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Get the href of each response in a PROPFIND multistatus and whether it is a collection
+fn parse_multistatus(xml: &str) -> Result<Vec<(String, bool)>> {
+    let mut reader = Reader::from_str(xml);
+    let mut entries = Vec::new();
+    let mut href = None;
+    let mut in_href = false;
+    let mut is_dir = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"response" => {
+                    href = None;
+                    is_dir = false;
+                }
+                b"href" => in_href = true,
+                b"collection" => is_dir = true,
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"collection" => is_dir = true,
+            Event::Text(t) if in_href => href = Some(t.unescape()?.trim().to_owned()),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"href" => in_href = false,
+                b"response" => {
+                    let href = href.take().ok_or_else(|| {
+                        BackendError::InvalidWebDavListing("response without a href".to_owned())
+                    })?;
+                    entries.push((href, is_dir));
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
 impl<'s> WebDavStore<'s> {
-    pub fn new(cfg: WebDavInfo, secrets: &'s SecretsApi) -> Self {
-        Self {
-            client: Client::new(),
+    pub fn new(cfg: WebDavInfo, secrets: &'s SecretsApi, timeout: Duration) -> Result<Self> {
+        // a total timeout would cut off large transfers, so only stalls are limited here
+        let mut client = Client::builder()
+            .connect_timeout(timeout)
+            .read_timeout(timeout);
+        if let Some(ca) = &cfg.ca_cert_path {
+            debug!("adding root certificate from {ca:?}");
+            let pem = std::fs::read(ca)?;
+            client = client.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        if cfg.danger_accept_invalid_certs {
+            warn!(
+                "certificate verification is disabled for webdav server {}, this is insecure",
+                cfg.url
+            );
+            client = client.danger_accept_invalid_certs(true);
+        }
+        let client = client.build()?;
+        Ok(Self {
+            client,
             cfg,
             secrets,
-        }
+            timeout,
+            partial_updates: OnceCell::new(),
+        })
     }
 
     async fn mk_req_abs(&self, method: Method, url: &str) -> Result<RequestBuilder> {
+        Ok(self.authed_req(method, url).await?.timeout(self.timeout))
+    }
+
+    /// Request for the contents of a file, these can take much longer than the timeout so it
+    /// only applies to connecting and to the transfer stalling
+    async fn mk_transfer_req(&self, method: Method, path: &Path) -> Result<RequestBuilder> {
+        let url = self.url_for(path);
+        self.authed_req(method, &url).await
+    }
+
+    async fn authed_req(&self, method: Method, url: &str) -> Result<RequestBuilder> {
         debug!("dispatching {method:?} request to {url}");
-        let psk = self.cfg.psk.as_ref().map(|s| async move {
-            match s {
-                crate::config::Secret::SystemSecret(name) => {
-                    assert!(
-                        self.secrets.available(),
-                        "system secrets must be available to use them in a config"
-                    );
-                    let s = self.secrets.get_item(name).await?;
-                    let sv = s.ok_or_else(|| {
-                        super::BackendError::CouldNotLocateSecret(name.to_owned())
-                    })?;
-                    let s = String::from_utf8(sv).expect("failed to convert from secret to utf8");
-                    Ok::<_, super::BackendError>(s)
-                }
-                crate::config::Secret::Plain(p) => Ok(p.to_owned()),
-            }
-        });
-        let psk = if let Some(p) = psk {
-            Some(p.await?)
-        } else {
-            None
+        let psk = match &self.cfg.psk {
+            Some(s) => Some(resolve_secret(s, self.secrets).await?),
+            None => None,
         };
-        Ok(self
-            .client
-            .request(method, url)
-            .basic_auth(&self.cfg.username, psk.as_deref()))
+        let req = self.client.request(method, url);
+        Ok(match (self.cfg.auth, psk) {
+            (WebDavAuth::Basic, psk) => req.basic_auth(&self.cfg.username, psk),
+            (WebDavAuth::Bearer, Some(token)) => req.bearer_auth(token),
+            (WebDavAuth::Bearer, None) => req,
+        })
+    }
+
+    fn url_for(&self, path: &Path) -> String {
+        join_url(&self.cfg.url, &self.cfg.root.join(path))
     }
 
     async fn mk_req(&self, method: Method, path: &Path) -> Result<RequestBuilder> {
-        let url = Path::new(&self.cfg.url)
-            .join_good(
-                self.cfg
-                    .root
-                    .join(path)
-                    .to_str()
-                    .expect("failed to concat path"),
-            )
-            .to_str()
-            .unwrap()
-            .to_owned();
+        let url = self.url_for(path);
         self.mk_req_abs(method, &url).await
     }
 
@@ -88,11 +231,7 @@ impl<'s> WebDavStore<'s> {
     /// Requires that dir is already parented to root
     async fn mkdir_abs(&self, dir: &Path) -> Result<()> {
         debug!("mkdir for {dir:?}");
-        let url = Path::new(&self.cfg.url)
-            .join_good(dir)
-            .to_str()
-            .unwrap()
-            .to_owned();
+        let url = join_url(&self.cfg.url, dir);
         let resp = self
             .mk_req_abs(
                 Method::from_bytes(b"MKCOL").expect("failed to make mkcol method"),
@@ -126,6 +265,14 @@ impl<'s> WebDavStore<'s> {
 
 impl WebDavStore<'_> {
     pub async fn write_file(&self, at: &Path, bytes: &[u8]) -> super::Result<()> {
+        self.write_owned(at, bytes.to_vec()).await
+    }
+
+    /// Uploads which fail part way through are retried, on servers which support partial
+    /// updates large ones carry on from what the server already has
+    ///
+    /// Takes the data so large archives are sent without a copy of them being made
+    pub async fn write_owned(&self, at: &Path, bytes: Vec<u8>) -> super::Result<()> {
         debug!("writing to {at:?}");
         if !self
             .exists(at.parent().expect("no parent path for file"))
@@ -134,10 +281,83 @@ impl WebDavStore<'_> {
             debug!("creating parent directories for {at:?}");
             self.mkdir_all(at.parent().unwrap()).await?;
         }
-        let resp = self
-            .mk_req(Method::PUT, at)
-            .await?
-            .body(bytes.to_owned())
+        // only resume into files this upload created, an existing file of the same name
+        // starts with the old contents rather than what was sent
+        let resumable = bytes.len() >= RESUMABLE_MIN_SIZE
+            && self.supports_partial_updates().await
+            && self.remote_len(at).await?.is_none();
+        let data = Arc::new(bytes);
+        let progress = ui::byte_progress("uploading", Some(data.len() as u64));
+        let mut offset = 0;
+        let mut attempt = 1;
+        loop {
+            match self.send_from(at, &data, offset, &progress).await {
+                Ok(()) => break,
+                Err(e) if attempt < UPLOAD_ATTEMPTS && is_transient(&e) => {
+                    attempt += 1;
+                    offset = if resumable {
+                        self.remote_len(at)
+                            .await
+                            .ok()
+                            .flatten()
+                            .filter(|&l| l < data.len())
+                            .unwrap_or(0)
+                    } else {
+                        0
+                    };
+                    warn!("upload of {at:?} failed ({e}), retrying from byte {offset}");
+                    progress.set_position(offset as u64);
+                }
+                Err(e) => {
+                    progress.finish_and_clear();
+                    return Err(e);
+                }
+            }
+        }
+        progress.finish_and_clear();
+        Ok(())
+    }
+
+    /// Send `data` from `offset` on, a PUT of the whole file if it is 0 and a partial update
+    /// otherwise
+    async fn send_from(
+        &self,
+        at: &Path,
+        data: &Arc<Vec<u8>>,
+        offset: usize,
+        progress: &ProgressBar,
+    ) -> Result<()> {
+        let body = {
+            let progress = progress.clone();
+            let data = data.clone();
+            // chunks are copied out as they are sent so the whole file isn't held twice
+            Body::wrap_stream(stream::iter(
+                (offset..data.len())
+                    .step_by(TRANSFER_CHUNK_SIZE)
+                    .map(move |start| {
+                        let chunk =
+                            data[start..data.len().min(start + TRANSFER_CHUNK_SIZE)].to_vec();
+                        progress.inc(chunk.len() as u64);
+                        Ok::<_, std::io::Error>(chunk)
+                    }),
+            ))
+        };
+        let req = if offset == 0 {
+            self.mk_transfer_req(Method::PUT, at).await?
+        } else {
+            debug!("resuming upload of {at:?} from byte {offset}");
+            self.mk_transfer_req(Method::PATCH, at)
+                .await?
+                .header(CONTENT_TYPE, PARTIAL_UPDATE_TYPE)
+                .header(
+                    "X-Update-Range",
+                    format!("bytes={offset}-{}", data.len() - 1),
+                )
+        };
+        let resp = req
+            // streamed bodies are chunked otherwise, which not all servers accept
+            .header(CONTENT_LENGTH, data.len() - offset)
+            .body(body)
             .send()
             .await?;
         if resp.status() == StatusCode::CONFLICT {
@@ -148,16 +368,136 @@ impl WebDavStore<'_> {
         Ok(())
     }
 
+    /// Whether the server advertises sabredav's partial updates, only asked once
+    async fn supports_partial_updates(&self) -> bool {
+        *self
+            .partial_updates
+            .get_or_init(|| async {
+                let resp = match self.mk_req(Method::OPTIONS, Path::new("")).await {
+                    Ok(r) => r.send().await,
+                    Err(e) => {
+                        debug!("not checking for partial update support: {e}");
+                        return false;
+                    }
+                };
+                let supported = resp.is_ok_and(|r| {
+                    r.headers().get_all("DAV").iter().any(|v| {
+                        v.to_str()
+                            .unwrap_or_default()
+                            .split(',')
+                            .any(|f| f.trim() == PARTIAL_UPDATE_FEATURE)
+                    })
+                });
+                debug!("webdav server supports partial updates: {supported}");
+                supported
+            })
+            .await
+    }
+
+    /// Size of a file on the server, `None` if it doesn't exist
+    async fn remote_len(&self, at: &Path) -> Result<Option<usize>> {
+        let resp = self.mk_req(Method::HEAD, at).await?.send().await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        // content_length() is the size of the (empty) body for a HEAD
+        Ok(resp
+            .error_for_status()?
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|l| l.to_str().ok()?.parse().ok()))
+    }
+
     pub async fn read_file(&self, at: &Path) -> super::Result<Vec<u8>> {
         debug!("read {at:?}");
-        let data = self
-            .mk_req(Method::GET, at)
+        let mut data = self
+            .mk_transfer_req(Method::GET, at)
             .await?
             .send()
             .await?
             .error_for_status()?;
-        let d = data.bytes().await?;
-        Ok(d.to_vec())
+        let progress = ui::byte_progress("downloading", data.content_length());
+        let mut d = Vec::with_capacity(data.content_length().unwrap_or(0) as usize);
+        while let Some(chunk) = data.chunk().await? {
+            progress.inc(chunk.len() as u64);
+            d.extend_from_slice(&chunk);
+        }
+        progress.finish_and_clear();
+        Ok(d)
+    }
+
+    pub async fn remove_file(&self, at: &Path) -> super::Result<()> {
+        debug!("delete {at:?}");
+        self.mk_req(Method::DELETE, at)
+            .await?
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// DELETE on a collection removes everything in it
+    pub async fn remove_dir(&self, at: &Path) -> super::Result<()> {
+        self.remove_file(at).await
+    }
+
+    pub async fn rename(&self, from: &Path, to: &Path) -> super::Result<()> {
+        debug!("move {from:?} to {to:?}");
+        self.mk_req(
+            Method::from_bytes(b"MOVE").expect("failed to make move method"),
+            from,
+        )
+        .await?
+        .header("Destination", self.url_for(to))
+        .header("Overwrite", "T")
+        .send()
+        .await?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn list(&self, dir: &Path) -> super::Result<Vec<ListEntry>> {
+        debug!("list {dir:?}");
+        let url = self.url_for(dir);
+        let resp = self
+            .mk_req_abs(
+                Method::from_bytes(b"PROPFIND").expect("failed to make propfind method"),
+                &url,
+            )
+            .await?
+            .header("Depth", "1")
+            .header(CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let body = resp.error_for_status()?.text().await?;
+
+        let invalid = |e: &dyn std::fmt::Display| BackendError::InvalidWebDavListing(e.to_string());
+        let base = Url::parse(&url).map_err(|e| invalid(&e))?;
+        let base_path = percent_decode(base.path());
+        let base_path = base_path.trim_end_matches('/');
+        let mut entries = Vec::new();
+        for (href, is_dir) in parse_multistatus(&body)? {
+            // hrefs may be absolute urls or just the path
+            let path = percent_decode(base.join(&href).map_err(|e| invalid(&e))?.path());
+            let path = path.trim_end_matches('/');
+            if path == base_path {
+                // the directory itself
+                continue;
+            }
+            let name = path
+                .strip_prefix(base_path)
+                .and_then(|p| p.strip_prefix('/'))
+                .ok_or_else(|| invalid(&format!("{href} is not inside {url}")))?;
+            entries.push(ListEntry {
+                path: dir.join(name),
+                is_dir,
+            });
+        }
+        Ok(entries)
     }
 
     pub async fn exists(&self, f: &Path) -> super::Result<bool> {
@@ -174,12 +514,21 @@ impl WebDavStore<'_> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::{Path, PathBuf};
-
-    use crate::{backends::webdav::WebDavStore, config::WebDavInfo, secrets::SecretsApi};
+    use std::{
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use crate::{
+        backends::{ListEntry, webdav::WebDavStore},
+        config::{Secret, WebDavAuth, WebDavInfo},
+        secrets::SecretsApi,
+    };
     use test_log::test;
 
-    use super::calc_mkdir_all_paths;
+    use super::{
+        PARTIAL_UPDATE_TYPE, RESUMABLE_MIN_SIZE, calc_mkdir_all_paths, join_url, percent_decode,
+    };
 
     #[test]
     fn calc_mkdir_all_paths_gives_individual_segments() {
@@ -211,9 +560,14 @@ mod tests {
                 username: "".to_owned(),
                 psk: None,
                 root: prefix.into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
             },
             &s,
-        );
+            Duration::from_secs(5),
+        )
+        .unwrap();
 
         store.mkdir_all(Path::new("hmm")).await.unwrap();
 
@@ -249,9 +603,14 @@ mod tests {
                 username: "".to_owned(),
                 psk: None,
                 root: "cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
             },
             &s,
-        );
+            Duration::from_secs(5),
+        )
+        .unwrap();
 
         store.mkdir_abs(Path::new("cinc/hmm")).await.unwrap();
 
@@ -276,12 +635,364 @@ mod tests {
                 username: "".to_owned(),
                 psk: None,
                 root: "".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
             },
             &s,
-        );
+            Duration::from_secs(5),
+        )
+        .unwrap();
 
         store.mkdir_all(Path::new("hmm")).await.unwrap();
 
         hmm.assert_async().await;
     }
+
+    #[test(tokio::test)]
+    async fn bearer_auth_sends_token() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let hmm = server
+            .mock("GET", "/hmm")
+            .match_header("authorization", "Bearer bingle")
+            .with_status(200)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url,
+                username: "".to_owned(),
+                psk: Some(Secret::Plain("bingle".to_owned())),
+                root: "".into(),
+                auth: WebDavAuth::Bearer,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert!(store.exists(Path::new("hmm")).await.unwrap());
+
+        hmm.assert_async().await;
+    }
+
+    #[test]
+    fn missing_ca_cert_is_an_error() {
+        let s = SecretsApi::new_unavailable();
+        let r = WebDavStore::new(
+            WebDavInfo {
+                url: "https://localhost".to_owned(),
+                username: "".to_owned(),
+                psk: None,
+                root: "".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: Some("./this/does/not/exist.pem".into()),
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        );
+        assert!(r.is_err());
+    }
+
+    #[test(tokio::test)]
+    async fn rename_sends_move_with_destination() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let mv = server
+            .mock("MOVE", "/cinc/a")
+            .match_header("destination", format!("{url}/cinc/b").as_str())
+            .match_header("overwrite", "T")
+            .with_status(201)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url,
+                username: "".to_owned(),
+                psk: None,
+                root: "cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        store.rename(Path::new("a"), Path::new("b")).await.unwrap();
+
+        mv.assert_async().await;
+    }
+
+    #[test]
+    fn join_url_has_no_empty_or_dot_segments() {
+        assert_eq!(
+            join_url("https://example.com/files/", Path::new("/cinc/a")),
+            "https://example.com/files/cinc/a"
+        );
+        assert_eq!(
+            join_url("https://example.com//files", Path::new("./cinc//a")),
+            "https://example.com/files/cinc/a"
+        );
+        assert_eq!(
+            join_url("https://example.com/files", Path::new("cinc/")),
+            "https://example.com/files/cinc/"
+        );
+        assert_eq!(
+            join_url("https://example.com", Path::new("")),
+            "https://example.com/"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn url_path_is_kept_with_absolute_root() {
+        let mut server = mockito::Server::new_async().await;
+        let base = server.url();
+
+        let mkcol = server
+            .mock("MKCOL", "/files/cinc")
+            .with_status(201)
+            .create_async()
+            .await;
+        let mv = server
+            .mock("MOVE", "/files/cinc/a")
+            .match_header("destination", format!("{base}/files/cinc/b").as_str())
+            .with_status(201)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url: format!("{base}/files/"),
+                username: "".to_owned(),
+                psk: None,
+                root: "/cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        store.mkdir_all(Path::new("")).await.unwrap();
+        store.rename(Path::new("a"), Path::new("b")).await.unwrap();
+
+        mkcol.assert_async().await;
+        mv.assert_async().await;
+    }
+
+    #[test(tokio::test)]
+    async fn write_then_read_sends_whole_body() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        let body = vec![7u8; 200 * 1024];
+
+        let parent = server
+            .mock("GET", "/cinc/")
+            .with_status(200)
+            .create_async()
+            .await;
+        let put = server
+            .mock("PUT", "/cinc/a")
+            .match_header("content-length", body.len().to_string().as_str())
+            .match_body(body.clone())
+            .with_status(201)
+            .create_async()
+            .await;
+        let get = server
+            .mock("GET", "/cinc/a")
+            .with_status(200)
+            .with_body(&body)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url,
+                username: "".to_owned(),
+                psk: None,
+                root: "cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        store.write_file(Path::new("a"), &body).await.unwrap();
+        assert_eq!(store.read_file(Path::new("a")).await.unwrap(), body);
+
+        parent.assert_async().await;
+        put.assert_async().await;
+        get.assert_async().await;
+    }
+
+    #[test]
+    fn percent_decode_handles_escapes() {
+        assert_eq!(percent_decode("a%20b%F0%9F%92%BE"), "a b💾");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test(tokio::test)]
+    async fn list_parses_propfind_response() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+
+        let propfind = server
+            .mock("PROPFIND", "/cinc/game")
+            .match_header("depth", "1")
+            .with_status(207)
+            .with_body(format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/cinc/game/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>{url}/cinc/game/mod-meta.ron</d:href>
+    <d:propstat><d:prop><d:resourcetype/></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/cinc/game/my%20saves/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+</d:multistatus>"#
+            ))
+            .create_async()
+            .await;
+        let missing = server
+            .mock("PROPFIND", "/cinc/missing")
+            .with_status(404)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url,
+                username: "".to_owned(),
+                psk: None,
+                root: "cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert_eq!(
+            store.list(Path::new("game")).await.unwrap(),
+            [
+                ListEntry {
+                    path: PathBuf::from("game/mod-meta.ron"),
+                    is_dir: false
+                },
+                ListEntry {
+                    path: PathBuf::from("game/my saves"),
+                    is_dir: true
+                },
+            ]
+        );
+        assert!(store.list(Path::new("missing")).await.unwrap().is_empty());
+
+        propfind.assert_async().await;
+        missing.assert_async().await;
+    }
+
+    #[test(tokio::test)]
+    async fn failed_uploads_resume_from_what_the_server_has() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url();
+        let len = RESUMABLE_MIN_SIZE + 10;
+        let received = 1000;
+
+        let parent = server
+            .mock("GET", "/cinc/game")
+            .with_status(200)
+            .create_async()
+            .await;
+        let options = server
+            .mock("OPTIONS", "/cinc/")
+            .with_status(200)
+            .with_header("DAV", "1, 2, 3, sabredav-partialupdate")
+            .create_async()
+            .await;
+        let not_uploaded = server
+            .mock("HEAD", "/cinc/game/big.bin")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let partial = server
+            .mock("HEAD", "/cinc/game/big.bin")
+            .with_status(200)
+            .with_header("content-length", &received.to_string())
+            .create_async()
+            .await;
+        let put = server
+            .mock("PUT", "/cinc/game/big.bin")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let patch = server
+            .mock("PATCH", "/cinc/game/big.bin")
+            .match_header("content-type", PARTIAL_UPDATE_TYPE)
+            .match_header(
+                "x-update-range",
+                format!("bytes={received}-{}", len - 1).as_str(),
+            )
+            .match_header("content-length", (len - received).to_string().as_str())
+            .with_status(204)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+
+        let store = WebDavStore::new(
+            WebDavInfo {
+                url,
+                username: "".to_owned(),
+                psk: None,
+                root: "cinc".into(),
+                auth: WebDavAuth::Basic,
+                ca_cert_path: None,
+                danger_accept_invalid_certs: false,
+            },
+            &s,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        store
+            .write_file(Path::new("game/big.bin"), &vec![7; len])
+            .await
+            .unwrap();
+
+        parent.assert_async().await;
+        options.assert_async().await;
+        not_uploaded.assert_async().await;
+        partial.assert_async().await;
+        put.assert_async().await;
+        patch.assert_async().await;
+    }
 }
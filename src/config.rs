@@ -1,9 +1,13 @@
-use std::{fmt::Display, path::PathBuf, str::FromStr};
+use std::{collections::HashMap, fmt::Display, path::PathBuf, str::FromStr, time::Duration};
 
+use chrono::{
+    FixedOffset,
+    format::{Item, StrftimeItems},
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{paths::data_dir, secrets::SecretsApi};
+use crate::{backends::filesystem, paths::data_dir, secrets::SecretsApi};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ConfigValidationError {
@@ -19,41 +23,313 @@ pub enum ConfigValidationError {
 
     #[error("failed to contact secrets service {0:?}")]
     FailedToGetSecrets(secret_service::Error),
+
+    #[error("network timeout must be greater than zero")]
+    ZeroNetworkTimeout,
+
+    #[error("backend '{0}' uses bearer auth but has no token")]
+    MissingBearerToken(String),
+
+    #[error("ca certificate '{0:?}' for backend '{1}' does not exist")]
+    MissingCaCert(PathBuf, String),
+
+    #[error("hook '{0}' has an empty command")]
+    EmptyHookCommand(&'static str),
+
+    #[error("dialog time format '{0}' is invalid")]
+    InvalidTimeFormat(String),
+
+    #[error("dialog time zone '{0}' is invalid, it should be UTC or an offset like +01:00")]
+    InvalidTimeZone(String),
+
+    #[error("device name is empty, remove it to use the hostname")]
+    EmptyDeviceName,
+
+    #[error("root {0:?} of backend '{1}' can't be used: {2}")]
+    BackendRootUnusable(PathBuf, String, std::io::Error),
+}
+
+/// The config has errors, see [`Config::validate`]
+#[derive(Error, Debug)]
+#[error("errors in config\n{}", .0.iter().map(|e| format!("- {e}")).collect::<Vec<_>>().join("\n"))]
+pub struct InvalidConfigError(pub Vec<ConfigValidationError>);
+
+/// Version of the config layout, bump this and add a step to [`Config::migrate`] for
+/// changes old configs can't be read with
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ConfigMigrationError {
+    #[error("config is version {0} but this cinc only understands up to {CONFIG_VERSION}")]
+    TooNew(u32),
+    #[error("config version is not a valid number")]
+    InvalidVersion,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
-    #[serde(alias = "backend")]
+    /// Layout version of the config, configs from before this was added are 0
+    #[serde(default)]
+    pub version: u32,
+
     pub backends: Vec<BackendInfo>,
     /// Default backend to use
     pub default_backend: String,
 
     pub manifest_url: Option<String>,
+
+    /// Timeout in seconds for connecting to and completing network requests
+    ///
+    /// Uploads and downloads of saves to webdav only time out if they stall for this long, so
+    /// large saves on a slow connection still complete
+    #[serde(default = "default_network_timeout_secs")]
+    pub network_timeout_secs: u64,
+
+    /// Extra template variables for use in save paths, e.g. `myCloud = "/mnt/cloud"` allows
+    /// `<myCloud>`. Builtin variables take precedence over these
+    #[serde(default)]
+    pub template_vars: HashMap<String, PathBuf>,
+
+    /// Match save paths against the filesystem ignoring case, useful when wine creates
+    /// directories with inconsistent capitalisation
+    #[serde(default)]
+    pub case_insensitive_paths: bool,
+
+    /// Follow symlinks inside save directories. Symlinks pointing outside of the save
+    /// directory are always skipped
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
+    /// Only upload the files which have changed rather than an archive of every file.
+    /// Games with lots of save files still use an archive
+    #[serde(default)]
+    pub incremental_uploads: bool,
+
+    /// How many requests to make to a backend at once when transferring the files of an
+    /// incremental upload, lower it if your server rate limits you
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
+    /// Refuse to upload if the save files add up to more than this many megabytes, 0 for no
+    /// limit. This protects against bad manifest entries, it can be overridden with --force
+    #[serde(default = "default_max_upload_size_mb")]
+    pub max_upload_size_mb: u64,
+
+    /// Local saves have to be at least this many seconds newer than the cloud ones to be
+    /// treated as a conflict, this stops clocks being slightly out between machines causing
+    /// spurious conflicts
+    #[serde(default = "default_conflict_skew_secs")]
+    pub conflict_skew_secs: u64,
+
+    /// How many backups of each game's local saves to keep, they are made before a download
+    /// overwrites them. 0 turns the backups off
+    #[serde(default = "default_max_download_backups")]
+    pub max_download_backups: usize,
+
+    /// Threads to use when compressing saves for upload, 0 (the default) uses one per cpu
+    #[serde(default)]
+    pub compression_threads: u32,
+
+    /// Leave out save files which can't be read when uploading instead of failing the whole
+    /// upload, e.g. ones a game keeps locked. Skipped files are missing from the cloud copy so
+    /// other machines won't get them, a warning lists them after each upload
+    #[serde(default)]
+    pub skip_unreadable_files: bool,
+
+    /// Also upload the files the manifest tags as config (resolution, keybinds etc.) to a separate
+    /// archive. It is never downloaded by a sync, restore it with `cinc restore --config`
+    #[serde(default)]
+    pub backup_config_files: bool,
+
+    /// Keep a lock file on the backend while a game is running, so launching it on another
+    /// device at the same time asks before syncing
+    #[serde(default)]
+    pub sync_lock: bool,
+
+    /// Locks older than this many seconds are assumed to be left over from a device that
+    /// never finished syncing and are ignored
+    #[serde(default = "default_sync_lock_stale_secs")]
+    pub sync_lock_stale_secs: u64,
+
+    /// Name other devices see this one as, e.g. in the sync conflict dialog, instead of its
+    /// hostname. Set it to keep the hostname off backends shared with other people
+    #[serde(default)]
+    pub device_name: Option<String>,
+
+    /// Allow webdav passwords to be written to the config in plaintext when the system keyring
+    /// is unavailable or not wanted. Off by default so configs kept in dotfiles repos don't leak
+    /// credentials
+    #[serde(default)]
+    pub allow_plaintext_secrets: bool,
+
+    /// Format for the times in the sync conflict dialog, see chrono's strftime docs
+    #[serde(default = "default_dialog_time_format")]
+    pub dialog_time_format: String,
+
+    /// Timezone for the times in the sync conflict dialog, either `UTC` or an offset like
+    /// `+01:00`. The system timezone is used if this isn't set
+    #[serde(default)]
+    pub dialog_time_zone: Option<String>,
+
+    /// Close the error and version mismatch dialogs after this many seconds, 0 leaves them open
+    /// until they are closed
+    #[serde(default)]
+    pub dialog_timeout_secs: u64,
+
+    /// Names of games, as in the manifest, which are launched without syncing
+    #[serde(default)]
+    pub skip_games: Vec<String>,
+
+    /// Show desktop notifications when saves are synced or there is a conflict
+    #[serde(default)]
+    pub notifications: bool,
+
+    /// Commands to run at points during a launch
+    #[serde(default)]
+    pub hooks: HooksConfig,
+}
+
+/// Commands run around a launch, each is the program followed by its arguments
+///
+/// They are run with `CINC_GAME_NAME`, `CINC_BACKEND` and `CINC_HOOK` set
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HooksConfig {
+    /// Run before the game is launched, after downloading. Failure aborts the launch
+    #[serde(default)]
+    pub pre_launch: Option<Vec<String>>,
+    /// Run after the saves have been downloaded
+    #[serde(default)]
+    pub post_download: Option<Vec<String>>,
+    /// Run after the saves have been uploaded
+    #[serde(default)]
+    pub post_upload: Option<Vec<String>>,
 }
+
+impl HooksConfig {
+    fn all(&self) -> impl Iterator<Item = (&'static str, &Vec<String>)> {
+        [
+            ("pre_launch", &self.pre_launch),
+            ("post_download", &self.post_download),
+            ("post_upload", &self.post_upload),
+        ]
+        .into_iter()
+        .filter_map(|(n, h)| h.as_ref().map(|h| (n, h)))
+    }
+}
+
+fn default_network_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_upload_size_mb() -> u64 {
+    1024
+}
+
+fn default_conflict_skew_secs() -> u64 {
+    5
+}
+
+fn default_max_concurrent_requests() -> usize {
+    4
+}
+
+fn default_max_download_backups() -> usize {
+    5
+}
+
+fn default_sync_lock_stale_secs() -> u64 {
+    12 * 60 * 60
+}
+
+fn default_dialog_time_format() -> String {
+    "%Y-%m-%d %H:%M %Z".to_owned()
+}
+
+/// Parse a timezone given in the config, `UTC` or a fixed offset
+pub fn parse_time_zone(s: &str) -> Option<FixedOffset> {
+    if s.eq_ignore_ascii_case("utc") {
+        FixedOffset::east_opt(0)
+    } else {
+        s.parse().ok()
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             backends: vec![BackendInfo {
                 name: "local-store".to_owned(),
                 info: Default::default(),
             }],
             manifest_url: None,
             default_backend: "local-store".to_owned(),
+            network_timeout_secs: default_network_timeout_secs(),
+            template_vars: HashMap::new(),
+            case_insensitive_paths: false,
+            follow_symlinks: false,
+            hooks: HooksConfig::default(),
+            notifications: false,
+            skip_games: Vec::new(),
+            incremental_uploads: false,
+            max_concurrent_requests: default_max_concurrent_requests(),
+            max_upload_size_mb: default_max_upload_size_mb(),
+            conflict_skew_secs: default_conflict_skew_secs(),
+            max_download_backups: default_max_download_backups(),
+            compression_threads: 0,
+            skip_unreadable_files: false,
+            backup_config_files: false,
+            sync_lock: false,
+            sync_lock_stale_secs: default_sync_lock_stale_secs(),
+            device_name: None,
+            allow_plaintext_secrets: false,
+            dialog_time_format: default_dialog_time_format(),
+            dialog_time_zone: None,
+            dialog_timeout_secs: 0,
         }
     }
 }
 impl Config {
+    /// Upgrade a config from an older layout in place so it can be deserialised
+    ///
+    /// Returns the version it was upgraded from, or None if it was already current
+    pub fn migrate(cfg: &mut toml::Table) -> Result<Option<u32>, ConfigMigrationError> {
+        let version = match cfg.get("version") {
+            Some(v) => v
+                .as_integer()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or(ConfigMigrationError::InvalidVersion)?,
+            None => 0,
+        };
+        if version > CONFIG_VERSION {
+            return Err(ConfigMigrationError::TooNew(version));
+        }
+        if version == CONFIG_VERSION {
+            return Ok(None);
+        }
+        if version < 1 {
+            // 0.1 configs used [[backend]]
+            if let Some(backends) = cfg.remove("backend") {
+                cfg.insert("backends".to_owned(), backends);
+            }
+        }
+        cfg.insert(
+            "version".to_owned(),
+            toml::Value::Integer(CONFIG_VERSION.into()),
+        );
+        Ok(Some(version))
+    }
+
+    pub fn network_timeout(&self) -> Duration {
+        Duration::from_secs(self.network_timeout_secs)
+    }
+    pub fn dialog_timeout(&self) -> Option<Duration> {
+        (self.dialog_timeout_secs != 0).then(|| Duration::from_secs(self.dialog_timeout_secs))
+    }
     pub fn used_keyring_ids(&self) -> impl Iterator<Item = &str> {
         self.backends
             .iter()
-            .filter_map(|b| {
-                if let BackendTy::WebDav(i) = &b.info {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .filter_map(|i| i.psk.as_ref())
+            .filter_map(|b| b.info.psk())
             .filter_map(|p| {
                 if let Secret::SystemSecret(s) = p {
                     Some(s.as_str())
@@ -77,12 +353,53 @@ impl Config {
         {
             errs.push(ConfigValidationError::MalformedManifestUrl);
         }
+        if self.network_timeout_secs == 0 {
+            errs.push(ConfigValidationError::ZeroNetworkTimeout);
+        }
+        if StrftimeItems::new(&self.dialog_time_format).any(|i| matches!(i, Item::Error)) {
+            errs.push(ConfigValidationError::InvalidTimeFormat(
+                self.dialog_time_format.clone(),
+            ));
+        }
+        if let Some(tz) = &self.dialog_time_zone {
+            if parse_time_zone(tz).is_none() {
+                errs.push(ConfigValidationError::InvalidTimeZone(tz.clone()));
+            }
+        }
+        if self
+            .device_name
+            .as_ref()
+            .is_some_and(|n| n.trim().is_empty())
+        {
+            errs.push(ConfigValidationError::EmptyDeviceName);
+        }
+        for (name, cmd) in self.hooks.all() {
+            if cmd.is_empty() {
+                errs.push(ConfigValidationError::EmptyHookCommand(name));
+            }
+        }
         for b in &self.backends {
             if let BackendTy::WebDav(WebDavInfo {
-                psk: Some(Secret::SystemSecret(key)),
+                auth: WebDavAuth::Bearer,
+                psk: None,
                 ..
             }) = &b.info
             {
+                errs.push(ConfigValidationError::MissingBearerToken(b.name.clone()));
+            }
+            if let BackendTy::WebDav(WebDavInfo {
+                ca_cert_path: Some(ca),
+                ..
+            }) = &b.info
+            {
+                if !ca.exists() {
+                    errs.push(ConfigValidationError::MissingCaCert(
+                        ca.clone(),
+                        b.name.clone(),
+                    ));
+                }
+            }
+            if let Some(Secret::SystemSecret(key)) = b.info.psk() {
                 if !secrets.available() {
                     errs.push(ConfigValidationError::SecretsUnavailable(b.name.clone()));
                 } else {
@@ -99,6 +416,22 @@ impl Config {
         }
         errs
     }
+
+    /// Filesystem backends whose root can't be used, e.g. the drive isn't mounted
+    ///
+    /// This isn't part of [`Self::validate`] so a backend which isn't being used doesn't stop
+    /// every command, one which is errors when it is opened. Each root is written to, to check it
+    pub fn unusable_backend_roots(&self) -> Vec<ConfigValidationError> {
+        self.backends
+            .iter()
+            .filter_map(|b| match &b.info {
+                BackendTy::Filesystem { root } => filesystem::check_root(root).err().map(|e| {
+                    ConfigValidationError::BackendRootUnusable(root.clone(), b.name.clone(), e)
+                }),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 pub const DEFAULT_MANIFEST_URL: &str =
@@ -107,8 +440,42 @@ pub const DEFAULT_MANIFEST_URL: &str =
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum BackendTy {
-    Filesystem { root: PathBuf },
+    Filesystem {
+        root: PathBuf,
+    },
     WebDav(WebDavInfo),
+    /// Any remote configured in rclone, `root` is relative to the remote
+    Rclone {
+        remote: String,
+        root: PathBuf,
+    },
+    /// Local git repository, each sync is committed and optionally pushed to `push_remote`
+    Git {
+        repo: PathBuf,
+        #[serde(default)]
+        push_remote: Option<String>,
+    },
+    Ftp(FtpInfo),
+}
+
+impl BackendTy {
+    /// The password or token for the backend, if it has one
+    pub fn psk(&self) -> Option<&Secret> {
+        match self {
+            BackendTy::WebDav(i) => i.psk.as_ref(),
+            BackendTy::Ftp(i) => i.psk.as_ref(),
+            BackendTy::Filesystem { .. } | BackendTy::Rclone { .. } | BackendTy::Git { .. } => None,
+        }
+    }
+
+    /// The password or token for the backend, None if it is a kind which doesn't use one
+    pub fn psk_mut(&mut self) -> Option<&mut Option<Secret>> {
+        match self {
+            BackendTy::WebDav(i) => Some(&mut i.psk),
+            BackendTy::Ftp(i) => Some(&mut i.psk),
+            BackendTy::Filesystem { .. } | BackendTy::Rclone { .. } | BackendTy::Git { .. } => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -124,12 +491,36 @@ impl BackendInfo {
     pub fn pretty_print(&self) -> String {
         match &self.info {
             BackendTy::Filesystem { root } => format!("filesystem at '{root:?}'"),
-            BackendTy::WebDav(web_dav_info) => format!(
-                "webdav at '{url}/{root:?}' with username {username}",
-                root = web_dav_info.root,
-                username = web_dav_info.username,
-                url = web_dav_info.url
+            BackendTy::Rclone { remote, root } => format!("rclone remote '{remote}' at {root:?}"),
+            BackendTy::Git {
+                repo,
+                push_remote: Some(remote),
+            } => format!("git repository at {repo:?} pushing to '{remote}'"),
+            BackendTy::Git {
+                repo,
+                push_remote: None,
+            } => format!("git repository at {repo:?}"),
+            BackendTy::Ftp(ftp_info) => format!(
+                "{scheme} at '{host}:{port}/{root:?}' with username {username}",
+                scheme = if ftp_info.tls { "ftps" } else { "ftp" },
+                host = ftp_info.host,
+                port = ftp_info.port,
+                root = ftp_info.root,
+                username = ftp_info.username,
             ),
+            BackendTy::WebDav(web_dav_info) => match web_dav_info.auth {
+                WebDavAuth::Basic => format!(
+                    "webdav at '{url}/{root:?}' with username {username}",
+                    root = web_dav_info.root,
+                    username = web_dav_info.username,
+                    url = web_dav_info.url
+                ),
+                WebDavAuth::Bearer => format!(
+                    "webdav at '{url}/{root:?}' with bearer token",
+                    root = web_dav_info.root,
+                    url = web_dav_info.url
+                ),
+            },
         }
     }
 }
@@ -188,8 +579,47 @@ impl FromStr for Secret {
 pub struct WebDavInfo {
     pub url: String,
     pub username: String,
+    /// Password for basic auth or the token for bearer auth
     pub psk: Option<Secret>,
     pub root: PathBuf,
+    #[serde(default)]
+    pub auth: WebDavAuth,
+    /// Extra PEM encoded root certificate to trust, e.g. for a self-signed server
+    pub ca_cert_path: Option<PathBuf>,
+    /// Disable all certificate verification. This is insecure and should only be used
+    /// if adding the certificate with `ca_cert_path` is not possible
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+/// How to authenticate with a WebDav server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WebDavAuth {
+    /// HTTP basic auth using the username and psk
+    #[default]
+    Basic,
+    /// `Authorization: Bearer <psk>`, the username is ignored
+    Bearer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FtpInfo {
+    pub host: String,
+    #[serde(default = "default_ftp_port")]
+    pub port: u16,
+    pub username: String,
+    pub psk: Option<Secret>,
+    pub root: PathBuf,
+    /// Upgrade the connection with `AUTH TLS` (explicit FTPS) before logging in
+    #[serde(default)]
+    pub tls: bool,
+}
+
+pub const DEFAULT_FTP_PORT: u16 = 21;
+
+fn default_ftp_port() -> u16 {
+    DEFAULT_FTP_PORT
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -253,11 +683,26 @@ impl SteamId64 {
 pub enum BackendType {
     Filesystem,
     WebDav,
+    Rclone,
+    Git,
+    Ftp,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::Secret;
+    use assert_fs::{
+        TempDir,
+        prelude::{FileTouch, PathChild},
+    };
+    use chrono::FixedOffset;
+
+    use crate::{
+        config::{
+            BackendTy, CONFIG_VERSION, ConfigMigrationError, ConfigValidationError, Secret,
+            WebDavAuth, parse_time_zone,
+        },
+        secrets::SecretsApi,
+    };
 
     use super::Config;
 
@@ -301,5 +746,146 @@ root = "/cinc"
         let r: Config = toml::from_str(example_cfg).unwrap();
         assert_eq!(r.default_backend, "cloud".to_owned());
         assert_eq!(r.backends.len(), 2);
+        assert_eq!(r.network_timeout_secs, 30);
+    }
+
+    #[test]
+    fn webdav_auth_defaults_to_basic() {
+        let example_cfg = r#"
+default_backend = "cloud"
+
+[[backends]]
+name = "cloud"
+type = "web_dav"
+url = "https://webdav.example.com/files/"
+username = "example@example.com"
+root = "/cinc"
+
+[[backends]]
+name = "gateway"
+type = "web_dav"
+url = "https://webdav.example.com/files/"
+username = ""
+psk = { ty = "plain", value = "token" }
+root = "/cinc"
+auth = "bearer"
+        "#;
+        let r: Config = toml::from_str(example_cfg).unwrap();
+        let auths = r
+            .backends
+            .iter()
+            .map(|b| match &b.info {
+                BackendTy::WebDav(i) => i.auth,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(auths, vec![WebDavAuth::Basic, WebDavAuth::Bearer]);
+    }
+
+    #[test]
+    fn config_with_template_vars_roundtrips() {
+        let mut cfg = Config::default();
+        cfg.template_vars
+            .insert("myCloud".to_owned(), "/mnt/cloud".into());
+        let s = toml::to_string_pretty(&cfg).unwrap();
+        let r: Config = toml::from_str(&s).unwrap();
+        assert_eq!(r.template_vars, cfg.template_vars);
+    }
+
+    #[test]
+    fn time_zones_parse() {
+        assert_eq!(parse_time_zone("UTC"), FixedOffset::east_opt(0));
+        assert_eq!(parse_time_zone("+01:00"), FixedOffset::east_opt(3600));
+        assert_eq!(parse_time_zone("Europe/London"), None);
+    }
+
+    #[tokio::test]
+    async fn invalid_dialog_time_settings_are_errors() {
+        let cfg = Config {
+            dialog_time_format: "%Y %Q".to_owned(),
+            dialog_time_zone: Some("nowhere".to_owned()),
+            ..Default::default()
+        };
+        let errs = cfg.validate(&SecretsApi::new_unavailable()).await;
+        assert!(matches!(
+            errs.as_slice(),
+            [
+                ConfigValidationError::InvalidTimeFormat(_),
+                ConfigValidationError::InvalidTimeZone(_)
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn blank_device_name_is_an_error() {
+        let secrets = SecretsApi::new_unavailable();
+        let cfg = Config {
+            device_name: Some(" ".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            cfg.validate(&secrets).await.as_slice(),
+            [ConfigValidationError::EmptyDeviceName]
+        ));
+
+        let cfg = Config {
+            device_name: Some("laptop".to_owned()),
+            ..Default::default()
+        };
+        assert!(cfg.validate(&secrets).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn filesystem_root_must_be_usable() {
+        let root = TempDir::new().unwrap();
+        let file = root.child("file");
+        file.touch().unwrap();
+        let mut cfg = Config::default();
+        cfg.backends[0].info = BackendTy::Filesystem {
+            root: file.to_path_buf(),
+        };
+        assert!(
+            cfg.validate(&SecretsApi::new_unavailable())
+                .await
+                .is_empty()
+        );
+        assert!(matches!(
+            cfg.unusable_backend_roots().as_slice(),
+            [ConfigValidationError::BackendRootUnusable(..)]
+        ));
+    }
+
+    #[test]
+    fn old_config_is_migrated() {
+        let mut cfg: toml::Table = toml::from_str(
+            r#"
+default_backend = "local-store"
+
+[[backend]]
+name = "local-store"
+type = "filesystem"
+root = "./cinc-data/data/local-store"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(Config::migrate(&mut cfg).unwrap(), Some(0));
+        let r: Config = cfg.try_into().unwrap();
+        assert_eq!(r.version, CONFIG_VERSION);
+        assert_eq!(r.backends.len(), 1);
+    }
+
+    #[test]
+    fn current_config_is_not_migrated() {
+        let mut cfg = toml::Table::try_from(Config::default()).unwrap();
+        assert_eq!(Config::migrate(&mut cfg).unwrap(), None);
+    }
+
+    #[test]
+    fn config_from_newer_version_is_an_error() {
+        let mut cfg: toml::Table = toml::from_str("version = 1000").unwrap();
+        assert!(matches!(
+            Config::migrate(&mut cfg),
+            Err(ConfigMigrationError::TooNew(1000))
+        ));
     }
 }
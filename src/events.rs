@@ -0,0 +1,57 @@
+use std::sync::mpsc::Sender;
+
+use crate::ui::{SyncChoices, SyncIssueInfo};
+
+/// Which way a sync is moving the saves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyncDirection {
+    Download,
+    Upload,
+}
+
+/// Something that happened during a sync, see [`SyncObserver`]
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    /// Saves of `game` started being transferred
+    SyncStarted {
+        game: String,
+        direction: SyncDirection,
+    },
+    /// The local and remote saves conflict, the user will be asked what to do unless
+    /// [`SyncObserver::resolve_conflict`] decides
+    ConflictDetected(SyncIssueInfo),
+    /// This many more bytes were downloaded
+    Downloading(u64),
+    /// This many more bytes were uploaded
+    Uploading(u64),
+    /// All the saves of `game` were transferred
+    Finished {
+        game: String,
+        direction: SyncDirection,
+    },
+}
+
+/// Told about the progress of syncs, for showing it somewhere other than the log e.g. when cinc is
+/// embedded in a launcher
+///
+/// `()` ignores everything and is used when no observer is given
+pub trait SyncObserver: Send + Sync {
+    fn event(&self, event: SyncEvent);
+
+    /// Choose what to do about a conflict instead of showing the built in dialog, `None` shows
+    /// the dialog
+    fn resolve_conflict(&self, _issue: &SyncIssueInfo) -> Option<SyncChoices> {
+        None
+    }
+}
+
+impl SyncObserver for () {
+    fn event(&self, _event: SyncEvent) {}
+}
+
+/// Sends the events down the channel, they are dropped if the receiver has gone
+impl SyncObserver for Sender<SyncEvent> {
+    fn event(&self, event: SyncEvent) {
+        let _ = self.send(event);
+    }
+}
@@ -0,0 +1,96 @@
+use std::error::Error;
+
+use crate::{
+    backends::BackendError,
+    config::{ConfigMigrationError, InvalidConfigError},
+    platform::{IncomaptibleCincVersionError, SyncConflictAbortedError},
+};
+
+/// Shown at the end of `--help`, keep in sync with [`ExitCode`]
+pub const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  any other error
+  2  the config could not be read or is invalid
+  3  a network request failed
+  4  aborted by the user due to a sync conflict
+  5  the saves on the server are from an incompatible version of cinc";
+
+/// What cinc exits with on an error, so scripts can tell failures apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum ExitCode {
+    Other = 1,
+    Config = 2,
+    Network = 3,
+    ConflictAborted = 4,
+    VersionIncompatible = 5,
+}
+
+impl ExitCode {
+    /// The code for an error, the first cause in its chain we know about decides it
+    pub fn for_error(e: &anyhow::Error) -> Self {
+        e.chain().find_map(Self::for_cause).unwrap_or(Self::Other)
+    }
+
+    fn for_cause(e: &(dyn Error + 'static)) -> Option<Self> {
+        if e.is::<IncomaptibleCincVersionError>() {
+            Some(Self::VersionIncompatible)
+        } else if e.is::<SyncConflictAbortedError>() {
+            Some(Self::ConflictAborted)
+        } else if e.is::<InvalidConfigError>()
+            || e.is::<ConfigMigrationError>()
+            || e.is::<toml::de::Error>()
+        {
+            Some(Self::Config)
+        } else if e.is::<reqwest::Error>()
+            || matches!(e.downcast_ref(), Some(BackendError::Reqwuest(_)))
+        {
+            Some(Self::Network)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use crate::{
+        config::{ConfigMigrationError, ConfigValidationError, InvalidConfigError},
+        platform::{IncomaptibleCincVersionError, SyncConflictAbortedError},
+    };
+
+    use super::ExitCode;
+
+    #[test]
+    fn errors_map_to_codes() {
+        let cases = [
+            (anyhow!("hmm"), ExitCode::Other),
+            (
+                InvalidConfigError(vec![ConfigValidationError::ZeroNetworkTimeout]).into(),
+                ExitCode::Config,
+            ),
+            (SyncConflictAbortedError.into(), ExitCode::ConflictAborted),
+            (
+                IncomaptibleCincVersionError {
+                    server_version: semver::Version::new(9, 0, 0),
+                    read: true,
+                }
+                .into(),
+                ExitCode::VersionIncompatible,
+            ),
+        ];
+        for (e, code) in cases {
+            assert_eq!(ExitCode::for_error(&e), code, "{e:?}");
+        }
+    }
+
+    #[test]
+    fn context_does_not_hide_the_cause() {
+        let e = Err::<(), _>(ConfigMigrationError::TooNew(99))
+            .context("while migrating config")
+            .unwrap_err();
+        assert_eq!(ExitCode::for_error(&e), ExitCode::Config);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use tracing::debug;
+
+const HEROIC_FLATPAK_ID: &str = "com.heroicgameslauncher.hgl";
+
+/// Config directories heroic may be using, native first then flatpak
+fn heroic_config_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(cfg) = dirs::config_dir() {
+        dirs.push(cfg.join("heroic"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(
+            home.join(".var")
+                .join("app")
+                .join(HEROIC_FLATPAK_ID)
+                .join("config")
+                .join("heroic"),
+        );
+    }
+    dirs
+}
+
+#[derive(Deserialize)]
+struct GogInstalled {
+    installed: Vec<GogInstalledGame>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogInstalledGame {
+    app_name: String,
+    #[serde(rename = "install_path")]
+    install_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct LegendaryInstalledGame {
+    install_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct NileInstalledGame {
+    id: String,
+    path: PathBuf,
+}
+
+fn gog_install_path(json: &str, app_name: &str) -> serde_json::Result<Option<PathBuf>> {
+    let installed: GogInstalled = serde_json::from_str(json)?;
+    Ok(installed
+        .installed
+        .into_iter()
+        .find(|g| g.app_name == app_name)
+        .map(|g| g.install_path))
+}
+
+fn legendary_install_path(json: &str, app_name: &str) -> serde_json::Result<Option<PathBuf>> {
+    let mut installed: HashMap<String, LegendaryInstalledGame> = serde_json::from_str(json)?;
+    Ok(installed.remove(app_name).map(|g| g.install_path))
+}
+
+fn nile_install_path(json: &str, app_name: &str) -> serde_json::Result<Option<PathBuf>> {
+    let installed: Vec<NileInstalledGame> = serde_json::from_str(json)?;
+    Ok(installed
+        .into_iter()
+        .find(|g| g.id == app_name)
+        .map(|g| g.path))
+}
+
+/// Files listing installed games for a heroic source, relative to heroic's config dir
+fn installed_files(source: &str) -> &'static [&'static str] {
+    match source {
+        "gog" => &["gog_store/installed.json"],
+        "epic" => &["legendaryConfig/legendary/installed.json"],
+        "amazon" => &["nile_config/nile/installed.json"],
+        _ => &[],
+    }
+}
+
+/// Look up where heroic installed a game from its `HEROIC_APP_SOURCE` and `HEROIC_APP_NAME`
+///
+/// Returns none if the install can't be found or heroic's config can't be read
+pub fn find_install_path(source: &str, app_name: &str) -> Option<PathBuf> {
+    for dir in heroic_config_dirs() {
+        for file in installed_files(source) {
+            let path = dir.join(file);
+            if let Some(p) = read_install_path(&path, source, app_name) {
+                return Some(p);
+            }
+        }
+    }
+    debug!("could not find {source} game {app_name} in heroic's config");
+    None
+}
+
+fn read_install_path(path: &Path, source: &str, app_name: &str) -> Option<PathBuf> {
+    let json = std::fs::read_to_string(path).ok()?;
+    let r = match source {
+        "gog" => gog_install_path(&json, app_name),
+        "amazon" => nile_install_path(&json, app_name),
+        _ => legendary_install_path(&json, app_name),
+    };
+    match r {
+        Ok(p) => p,
+        Err(e) => {
+            debug!("failed to parse heroic config {path:?}: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{gog_install_path, legendary_install_path, nile_install_path};
+
+    #[test]
+    fn gog_installed_is_parsed() {
+        let json = r#"{"installed": [
+            {"appName": "1", "install_path": "/games/one", "platform": "windows"},
+            {"appName": "2", "install_path": "/games/two", "platform": "windows"}
+        ]}"#;
+        assert_eq!(
+            gog_install_path(json, "2").unwrap(),
+            Some(PathBuf::from("/games/two"))
+        );
+        assert_eq!(gog_install_path(json, "3").unwrap(), None);
+    }
+
+    #[test]
+    fn legendary_installed_is_parsed() {
+        let json = r#"{"Bingle": {"app_name": "Bingle", "install_path": "/games/bingle"}}"#;
+        assert_eq!(
+            legendary_install_path(json, "Bingle").unwrap(),
+            Some(PathBuf::from("/games/bingle"))
+        );
+    }
+
+    #[test]
+    fn nile_installed_is_parsed() {
+        let json = r#"[{"id": "amzn1.adg.product.1", "version": "1", "path": "/games/amazon"}]"#;
+        assert_eq!(
+            nile_install_path(json, "amzn1.adg.product.1").unwrap(),
+            Some(PathBuf::from("/games/amazon"))
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::process::Command;
+
+use anyhow::{Result, bail};
+use tracing::{debug, warn};
+
+/// What a hook is being run for, passed to it through env vars
+pub struct HookEnv<'a> {
+    pub game_name: &'a str,
+    pub backend: &'a str,
+}
+
+/// Run a hook command and wait for it to finish, erroring if it exits unsuccessfully
+pub fn run_hook(name: &str, cmd: &[String], env: &HookEnv<'_>) -> Result<()> {
+    let Some((program, args)) = cmd.split_first() else {
+        bail!("hook '{name}' has an empty command");
+    };
+    debug!("running {name} hook: {cmd:?}");
+    let status = Command::new(program)
+        .args(args)
+        .env("CINC_HOOK", name)
+        .env("CINC_GAME_NAME", env.game_name)
+        .env("CINC_BACKEND", env.backend)
+        .status()?;
+    if !status.success() {
+        bail!("hook '{name}' failed with {status}");
+    }
+    Ok(())
+}
+
+/// Run a hook which isn't allowed to stop cinc, failures are only logged
+pub fn run_hook_or_warn(name: &str, cmd: &[String], env: &HookEnv<'_>) {
+    if let Err(e) = run_hook(name, cmd, env) {
+        warn!("{e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HookEnv, run_hook};
+
+    const ENV: HookEnv<'static> = HookEnv {
+        game_name: "game",
+        backend: "backend",
+    };
+
+    #[cfg(unix)]
+    #[test]
+    fn hook_exit_status_is_checked() {
+        assert!(run_hook("test", &["true".to_owned()], &ENV).is_ok());
+        assert!(run_hook("test", &["false".to_owned()], &ENV).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hook_gets_env_vars() {
+        let cmd = [
+            "sh",
+            "-c",
+            r#"[ "$CINC_GAME_NAME" = game ] && [ "$CINC_HOOK" = test ]"#,
+        ]
+        .map(str::to_owned);
+        assert!(run_hook("test", &cmd, &ENV).is_ok());
+    }
+
+    #[test]
+    fn empty_hook_is_an_error() {
+        assert!(run_hook("test", &[], &ENV).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+/// When each game was last launched on this machine, this is only kept locally in
+/// [`last_played_file`](crate::paths::last_played_file)
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct LastPlayed(BTreeMap<String, DateTime<Utc>>);
+
+impl LastPlayed {
+    /// Read the times from `path`, nothing has been played if it doesn't exist
+    pub fn read(path: &Path) -> Result<Self> {
+        if !std::fs::exists(path)? {
+            return Ok(Self::default());
+        }
+        Ok(ron::de::from_bytes(&fs::read(path)?)?)
+    }
+
+    /// Write the times to `path`, replacing what is there in one go so a reader never sees half
+    /// of it
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("ron.tmp");
+        fs::write(&tmp, ron::ser::to_string(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Record `game` being played at `when` in the file at `path`
+    pub fn record_at(path: &Path, game: &str, when: DateTime<Utc>) -> Result<()> {
+        let mut played = Self::read(path)?;
+        played.0.insert(game.to_owned(), when);
+        played.write(path)
+    }
+
+    pub fn get(&self, game: &str) -> Option<DateTime<Utc>> {
+        self.0.get(game).copied()
+    }
+
+    /// Sort `games` most recently played first, those never played are last in the order they
+    /// were given
+    pub fn sort_by_recent<'a>(&self, games: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+        let mut games = games.into_iter().collect::<Vec<_>>();
+        games.sort_by_key(|g| std::cmp::Reverse(self.get(g)));
+        games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::{TempDir, prelude::PathChild};
+    use chrono::{TimeDelta, Utc};
+
+    use super::LastPlayed;
+
+    #[test]
+    fn recent_games_are_first() {
+        let root = TempDir::new().unwrap();
+        let path = root.child("data").child("last-played.ron");
+        assert_eq!(LastPlayed::read(&path).unwrap(), LastPlayed::default());
+
+        let now = Utc::now();
+        LastPlayed::record_at(&path, "old", now - TimeDelta::days(30)).unwrap();
+        LastPlayed::record_at(&path, "new", now).unwrap();
+        let played = LastPlayed::read(&path).unwrap();
+        assert_eq!(played.get("new"), Some(now));
+        assert_eq!(
+            played.sort_by_recent(["a", "old", "b", "new"]),
+            ["new", "old", "a", "b"]
+        );
+    }
+}
@@ -1,13 +1,23 @@
 pub mod args;
 pub mod backends;
 pub mod config;
+pub mod events;
+pub mod exit_code;
+pub mod heroic;
+pub mod hooks;
+pub mod last_played;
+pub mod ludusavi;
 pub mod manifest;
+pub mod manifest_cache;
 pub mod paths;
 pub mod platform;
+pub mod registry;
 pub mod secrets;
 pub mod sync;
 pub mod ui;
 
+pub use platform::{run_sync, run_sync_with_observer};
+
 #[macro_export]
 macro_rules! time {
     ($name:literal : { $($code:tt)* }) => {
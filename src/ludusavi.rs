@@ -0,0 +1,206 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use fs_err as fs;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// ludusavi keeps one of these in each game's backup dir describing the backups in it
+const MAPPING_FILE: &str = "mapping.yaml";
+
+#[derive(Debug, Error)]
+pub enum LudusaviError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse ludusavi mapping {0:?}")]
+    Mapping(PathBuf, #[source] serde_yaml::Error),
+    #[error("ludusavi backup of '{0}' has no backups in it")]
+    NoBackups(String),
+    #[error("ludusavi backup '{1}' of '{0}' is a zip, only folder backups can be imported")]
+    ZipBackup(String, String),
+    #[error("ludusavi backup of '{0}' has no drive folder for {1:?}")]
+    UnknownDrive(String, String),
+}
+
+#[derive(Deserialize, Debug)]
+struct Mapping {
+    name: String,
+    /// Folder in the backup to the drive it is for, e.g. `drive-C: "C:"` or `drive-0: ""`
+    #[serde(default)]
+    drives: HashMap<String, String>,
+    #[serde(default)]
+    backups: Vec<FullBackup>,
+}
+
+#[derive(Deserialize, Debug)]
+struct FullBackup {
+    /// Folder of the backup in the game dir, `.` for backups from older versions which are
+    /// directly in it
+    name: String,
+    when: DateTime<Utc>,
+    /// Path the file was backed up from, the values are the hash and size which aren't needed
+    #[serde(default)]
+    files: BTreeMap<String, serde_yaml::Value>,
+    /// Differential backups on top of this one
+    #[serde(default)]
+    children: Vec<serde_yaml::Value>,
+}
+
+/// A game's latest backup from ludusavi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LudusaviGame {
+    /// Name of the game in the manifest
+    pub name: String,
+    pub when: DateTime<Utc>,
+    pub files: Vec<BackedUpFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackedUpFile {
+    /// Where the file was backed up from
+    pub original: PathBuf,
+    /// Where it is in the backup
+    pub stored: PathBuf,
+}
+
+/// Read every game backed up in a ludusavi backup dir
+pub fn read_backups(dir: &Path) -> Result<Vec<LudusaviGame>, LudusaviError> {
+    let mut games = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let game_dir = entry?.path();
+        if !game_dir.join(MAPPING_FILE).is_file() {
+            debug!("skipping {game_dir:?} as it has no {MAPPING_FILE}");
+            continue;
+        }
+        games.push(read_game(&game_dir)?);
+    }
+    games.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(games)
+}
+
+/// Read the latest full backup of a game, differential backups on top of it are ignored
+pub fn read_game(game_dir: &Path) -> Result<LudusaviGame, LudusaviError> {
+    let mapping_path = game_dir.join(MAPPING_FILE);
+    let mapping: Mapping = serde_yaml::from_str(&fs::read_to_string(&mapping_path)?)
+        .map_err(|e| LudusaviError::Mapping(mapping_path, e))?;
+    let Some(backup) = mapping.backups.iter().max_by_key(|b| b.when) else {
+        return Err(LudusaviError::NoBackups(mapping.name));
+    };
+    if backup.name.ends_with(".zip") {
+        return Err(LudusaviError::ZipBackup(mapping.name, backup.name.clone()));
+    }
+    if !backup.children.is_empty() {
+        warn!(
+            "ignoring {} differential backup(s) of '{}', only the full backup is imported",
+            backup.children.len(),
+            mapping.name
+        );
+    }
+    let files = backup
+        .files
+        .keys()
+        .map(|original| {
+            let stored = stored_path(&mapping, original).ok_or_else(|| {
+                LudusaviError::UnknownDrive(mapping.name.clone(), original.clone())
+            })?;
+            Ok(BackedUpFile {
+                original: original.into(),
+                stored: game_dir.join(&backup.name).join(stored),
+            })
+        })
+        .collect::<Result<_, LudusaviError>>()?;
+    Ok(LudusaviGame {
+        name: mapping.name,
+        when: backup.when,
+        files,
+    })
+}
+
+/// Where ludusavi puts a file in a backup, e.g. `C:/Users/a` is at `drive-C/Users/a`
+fn stored_path(mapping: &Mapping, original: &str) -> Option<PathBuf> {
+    let (drive, rest) = match original.split_once(':') {
+        Some((letter, rest)) if letter.len() == 1 => (&original[..2], rest),
+        _ => ("", original),
+    };
+    let (folder, _) = mapping.drives.iter().find(|(_, d)| *d == drive)?;
+    Some(Path::new(folder).join(rest.trim_start_matches(['/', '\\'])))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use assert_fs::{TempDir, prelude::*};
+
+    use super::{BackedUpFile, LudusaviError, read_backups};
+
+    #[test]
+    fn backups_are_read() {
+        let dir = TempDir::new().unwrap();
+        let game = dir.child("Some Game");
+        game.child("mapping.yaml")
+            .write_str(
+                r#"
+name: Some Game
+drives:
+  drive-0: ""
+  drive-C: "C:"
+backups:
+  - name: backup-20240101T000000Z
+    when: "2024-01-01T00:00:00Z"
+    files:
+      /home/me/.local/share/game/save.dat:
+        hash: abc
+        size: 1
+  - name: backup-20240201T000000Z
+    when: "2024-02-01T00:00:00Z"
+    files:
+      /home/me/.local/share/game/save.dat:
+        hash: abc
+        size: 1
+      C:/Users/me/Documents/Game/slot1:
+        hash: def
+        size: 1
+"#,
+            )
+            .unwrap();
+        dir.child("not a game").create_dir_all().unwrap();
+
+        let games = read_backups(dir.path()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].name, "Some Game");
+        let backup = game.child("backup-20240201T000000Z");
+        assert_eq!(
+            games[0].files,
+            [
+                BackedUpFile {
+                    original: PathBuf::from("/home/me/.local/share/game/save.dat"),
+                    stored: backup.join("drive-0/home/me/.local/share/game/save.dat"),
+                },
+                BackedUpFile {
+                    original: PathBuf::from("C:/Users/me/Documents/Game/slot1"),
+                    stored: backup.join("drive-C/Users/me/Documents/Game/slot1"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn zip_backups_are_refused() {
+        let dir = TempDir::new().unwrap();
+        dir.child("Game")
+            .child("mapping.yaml")
+            .write_str(
+                "name: Game\nbackups:\n  - name: backup-1.zip\n    when: \"2024-01-01T00:00:00Z\"\n",
+            )
+            .unwrap();
+        assert!(matches!(
+            read_backups(dir.path()),
+            Err(LudusaviError::ZipBackup(..))
+        ));
+    }
+}
@@ -7,84 +7,199 @@ use crossterm::{
 };
 use fs_err as fs;
 use std::{
-    fs::{File, OpenOptions},
-    io::{BufReader, BufWriter},
+    fs::OpenOptions,
     process::exit,
-    time::SystemTime,
+    sync::OnceLock,
+    time::{Duration, SystemTime},
 };
 use std::{
-    io::{self, Write},
+    io::{self, IsTerminal, Write},
     path::{Path, PathBuf},
 };
 use uuid::Uuid;
 
-use anyhow::{Context, Result, bail};
-use chrono::Local;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{Local, Utc};
 use cinc::{
-    args::{CliArgs, LaunchArgs},
-    config::{BackendInfo, BackendTy, Config, DEFAULT_MANIFEST_URL, Secret, WebDavInfo},
+    args::{
+        CliArgs, GamesArgs, LaunchArgs, LogFormat, Operation, OutputFormat, PlatformOpt,
+        RestoreArgs,
+    },
+    backends,
+    config::{
+        BackendInfo, BackendTy, Config, DEFAULT_MANIFEST_URL, FtpInfo, InvalidConfigError, Secret,
+        WebDavAuth, WebDavInfo,
+    },
     curr_crate_ver,
-    manifest::GameManifests,
-    paths::{cache_dir, config_dir, log_dir},
+    exit_code::ExitCode,
+    last_played::LastPlayed,
+    ludusavi,
+    manifest::{FileTag, GameManifest, GameManifests, TemplateInfo},
+    manifest_cache::{self, ManifestVersion, StaleManifestCacheError},
+    paths::{
+        LOG_FILE_PREFIX, cache_dir, config_dir, last_played_file, log_dir, prune_log_files,
+        resolve_case_insensitive, set_data_root,
+    },
     platform::{IncomaptibleCincVersionError, LaunchInfo},
+    run_sync,
     secrets::SecretsApi,
-    ui::{self, SyncIssueInfo},
+    sync::{
+        PlannedTransfer, SyncMgr, SyncOptions, Verification, detect_local_template_info,
+        extract_remote,
+    },
+    ui::{self, SyncIssueInfo, TimeDisplay},
 };
-use clap::Parser;
+use clap::{ColorChoice, CommandFactory, Parser};
 use itertools::Itertools;
-use tracing::{debug, info, warn};
+use reqwest::{
+    StatusCode,
+    header::{ETAG, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use tracing::{debug, info, level_filters::LevelFilter, warn};
 use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
-async fn grab_manifest(url: &str) -> Result<String> {
-    Ok(reqwest::get(url).await?.text().await?)
+/// Fetch the manifest, None if it is the same as `cached`
+async fn grab_manifest(
+    url: &str,
+    timeout: Duration,
+    cached: Option<&ManifestVersion>,
+) -> Result<Option<(String, ManifestVersion)>> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()?;
+    let mut req = client.get(url);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = &cached.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, modified);
+        }
+    }
+    let resp = req.send().await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let resp = resp.error_for_status()?;
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+            .map(str::to_owned)
+    };
+    let version = ManifestVersion {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    Ok(Some((resp.text().await?, version)))
 }
 
-fn init_file_logging() -> Result<()> {
+/// Whether to colour output, see <https://no-color.org>
+fn should_colorize(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stdout().is_terminal()
+        }
+    }
+}
+
+/// How many per-run log files to keep around
+const MAX_LOG_FILES: usize = 10;
+
+/// Log to a file in [`log_dir`] and optionally to stderr
+///
+/// `level` overrides the default level for both
+fn init_logging(format: LogFormat, level: Option<LevelFilter>, stderr: bool) -> Result<()> {
     let dir = &log_dir();
     if !std::fs::exists(dir)? {
         fs::create_dir_all(dir)?;
     }
+    // leave space for this run's log
+    prune_log_files(dir, MAX_LOG_FILES - 1)?;
     let log_file = OpenOptions::new()
-        .create(true)
+        .create_new(true)
         .write(true)
-        .truncate(true)
-        .open(dir.join("general.log"))?;
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .pretty()
-        .with_writer(log_file);
+        .open(dir.join(format!(
+            "{LOG_FILE_PREFIX}{}-{}.log",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id()
+        )))?;
+    let fmt_layer = tracing_subscriber::fmt::layer().with_writer(log_file);
+    let fmt_layer = match format {
+        LogFormat::Pretty => fmt_layer.pretty().boxed(),
+        LogFormat::Json => fmt_layer.json().boxed(),
+    };
+    // the file always gets at least debug logs unless the user asked for less
+    let file_level = level.unwrap_or(LevelFilter::DEBUG);
+    let stderr_layer = stderr.then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_ansi(colored::control::SHOULD_COLORIZE.should_colorize())
+            .without_time()
+            .with_target(false)
+            .with_filter(
+                tracing_subscriber::filter::Targets::new()
+                    .with_target("cinc", level.unwrap_or(LevelFilter::INFO)),
+            )
+    });
     tracing_subscriber::registry()
-        .with(
-            fmt_layer.with_filter(tracing_subscriber::filter::Targets::new().with_target(
-                "cinc",
-                tracing_subscriber::filter::LevelFilter::from_level(tracing::Level::DEBUG),
-            )),
-        )
+        .with(fmt_layer.with_filter(
+            tracing_subscriber::filter::Targets::new().with_target("cinc", file_level),
+        ))
+        .with(stderr_layer)
         .init();
     Ok(())
 }
 
-async fn update_manifest(url: &str) -> Result<GameManifests> {
+/// Fetch the manifest and cache it, with `dry_run` it is only fetched and parsed
+async fn update_manifest(url: &str, timeout: Duration, dry_run: bool) -> Result<GameManifests> {
+    let path = &cache_dir().join("manifest.bin");
+    let cached = if std::fs::exists(path)? {
+        manifest_cache::read_version(path)
+            .inspect_err(|e| debug!("not using the cached manifest's version: {e:?}"))
+            .ok()
+    } else {
+        None
+    };
+    info!("grabbing manifest...");
+    let (txt, version) = match grab_manifest(url, timeout, cached.as_ref()).await? {
+        Some(fetched) => fetched,
+        None => match manifest_cache::read_all(path) {
+            Ok(manifest) => {
+                info!("manifest hasn't changed since it was cached");
+                return Ok(manifest);
+            }
+            Err(e) => {
+                warn!("failed to read the manifest cache, grabbing all of it: {e:?}");
+                grab_manifest(url, timeout, None)
+                    .await?
+                    .context("server said the manifest hasn't changed without being asked")?
+            }
+        },
+    };
+    info!("parsing manifest...");
+    let manifest: GameManifests = serde_yaml::from_str(&txt).context("while parsing manifest")?;
+    if dry_run {
+        info!(
+            "manifest has {} games, not writing manifest cache due to dry-run flag",
+            manifest.len()
+        );
+        return Ok(manifest);
+    }
     let cache = &cache_dir();
     if !std::fs::exists(cache)? {
         info!("creating cache dir...");
         std::fs::create_dir_all(cache)?;
     }
-    let path = &cache.join("manifest.bin");
-
-    info!("grabbing manifest...");
-    let txt = grab_manifest(url).await?;
-    info!("parsing manifest...");
-    let manifest: GameManifests = serde_yaml::from_str(&txt).context("while parsing manifest")?;
     info!("write manifest...");
-    bincode::serde::encode_into_std_write(
-        &manifest,
-        &mut BufWriter::new(File::create(path)?),
-        bincode::config::standard(),
-    )?;
+    manifest_cache::write(path, &manifest, &version)?;
     Ok(manifest)
 }
 
-async fn get_game_manifests(url: &str) -> Result<GameManifests> {
+async fn get_game_manifests(url: &str, timeout: Duration) -> Result<GameManifests> {
     let cache = &cache_dir();
     if !std::fs::exists(cache)? {
         info!("creating cache dir...");
@@ -92,23 +207,44 @@ async fn get_game_manifests(url: &str) -> Result<GameManifests> {
     }
     let path = &cache.join("manifest.bin");
     if !std::fs::exists(path)? {
-        update_manifest(url).await
+        update_manifest(url, timeout, false).await
     } else {
         info!("reading cached manifest...");
-        match bincode::serde::decode_from_std_read(
-            &mut BufReader::new(File::open(path)?),
-            bincode::config::standard(),
-        ) {
+        match manifest_cache::read_all(path) {
             Ok(v) => Ok(v),
-            Err(_) => {
-                warn!(
-                    "failed to decode manifest, assuming it is an old version and grabbing from the server again"
-                );
+            Err(e) if e.downcast_ref::<StaleManifestCacheError>().is_some() => {
+                info!("{e}, grabbing it from the server again");
+                update_manifest(url, timeout, false).await
+            }
+            Err(e) => {
+                warn!("failed to decode manifest cache, grabbing it from the server again: {e:?}");
                 std::fs::remove_file(path)?;
-                update_manifest(url).await
+                update_manifest(url, timeout, false).await
+            }
+        }
+    }
+}
+
+/// Get the manifests needed to launch a game
+///
+/// When the game has a steam id only it is decoded from the cache, which avoids decoding the
+/// whole manifest
+async fn get_launch_manifests(
+    url: &str,
+    timeout: Duration,
+    largs: &LaunchArgs,
+) -> Result<GameManifests> {
+    let path = &cache_dir().join("manifest.bin");
+    if let Some(app_id) = largs.steam_app_id() {
+        if std::fs::exists(path)? {
+            match manifest_cache::read_by_steam_id(path, app_id) {
+                Ok(Some(game)) => return Ok([game].into_iter().collect()),
+                Ok(None) => debug!("{app_id} is not in the manifest cache, reading all of it"),
+                Err(e) => debug!("failed to read {app_id} from the manifest cache: {e:?}"),
             }
         }
     }
+    get_game_manifests(url, timeout).await
 }
 
 const CFG_FILE_NAME: &str = "general.toml";
@@ -129,7 +265,16 @@ fn read_config(cfg_file: &Path) -> Result<Config> {
         Ok(cfg)
     } else {
         let cfg_str = fs::read_to_string(cfg_file).context("while reading config")?;
-        let cfg = toml::from_str(&cfg_str).context("while deserialising config")?;
+        let mut cfg_table: toml::Table =
+            toml::from_str(&cfg_str).context("while deserialising config")?;
+        let migrated_from = Config::migrate(&mut cfg_table).context("while migrating config")?;
+        let cfg: Config = cfg_table.try_into().context("while deserialising config")?;
+        if let Some(old_version) = migrated_from {
+            let backup = cfg_file.with_extension(format!("toml.v{old_version}.bak"));
+            info!("upgraded config from version {old_version}, the old config is at {backup:?}");
+            fs::copy(cfg_file, &backup)?;
+            fs::write(cfg_file, toml::to_string_pretty(&cfg)?)?;
+        }
         Ok(cfg)
     }
 }
@@ -191,41 +336,296 @@ fn user_input_yesno(prompt: &str, default: bool) -> Result<bool> {
     Ok(matches!(to.to_lowercase().as_str(), "y" | "yes") || (to.is_empty() && default))
 }
 
+const FTP_SECRET_PROMPT: &str = "enter ftp password, leave blank for no password: ";
+
+/// What to ask the user for when adding a webdav backend
+fn webdav_secret_prompt(auth: WebDavAuth) -> &'static str {
+    match auth {
+        WebDavAuth::Basic => "enter webdav password, leave blank for no password: ",
+        WebDavAuth::Bearer => "enter webdav bearer token: ",
+    }
+}
+
+/// Ask the user for a backend password/token and store it, in the system keyring unless
+/// plaintext is allowed and they don't want to use it
+async fn prompt_backend_secret(
+    secrets: &SecretsApi<'_>,
+    backend: &str,
+    prompt: &str,
+    allow_plaintext: bool,
+    dry_run: bool,
+) -> Result<Option<Secret>> {
+    let psk = user_psk_input(prompt)?;
+    if psk.is_empty() {
+        return Ok(None);
+    }
+    if !secrets.available() && !allow_plaintext {
+        bail!(
+            "the system keyring is unavailable so the password can't be stored securely, \
+            start a secret service (e.g. gnome-keyring or kwallet) or set \
+            `allow_plaintext_secrets = true` in the config to store it in plaintext"
+        );
+    }
+    let use_secrets = secrets.available()
+        && (!allow_plaintext
+            || user_input_yesno(
+                "use system secrets API to store this password? (recommended) [Y/n]: ",
+                true,
+            )?);
+    Ok(Some(if use_secrets {
+        let secret_name = Uuid::new_v4().to_string();
+        if !dry_run {
+            secrets.add_item(&secret_name, backend, &psk).await?;
+        }
+        Secret::SystemSecret(secret_name)
+    } else {
+        Secret::Plain(psk)
+    }))
+}
+
+/// Ask for a new password/token for a backend and replace the one in its config, the old one is
+/// left in the keyring for garbage collection
+async fn change_backend_secret(
+    secrets: &SecretsApi<'_>,
+    backend: &mut BackendInfo,
+    allow_plaintext: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let prompt = match &backend.info {
+        BackendTy::WebDav(info) => webdav_secret_prompt(info.auth),
+        BackendTy::Ftp(_) => FTP_SECRET_PROMPT,
+        BackendTy::Filesystem { .. } | BackendTy::Rclone { .. } | BackendTy::Git { .. } => {
+            bail!("backend '{}' doesn't use a password", backend.name)
+        }
+    };
+    let psk =
+        prompt_backend_secret(secrets, &backend.name, prompt, allow_plaintext, dry_run).await?;
+    *backend
+        .info
+        .psk_mut()
+        .expect("only backends with passwords get here") = psk;
+    Ok(())
+}
+
 macro_rules! print_success {
     ($($arg:tt)*) => {
         println!("{}", format!($($arg)*).green())
     }
 }
 
+/// Remove everything in the cache dir except the logs
+fn clear_cache(dry_run: bool) -> Result<()> {
+    let cache = cache_dir();
+    let logs = log_dir();
+    let mut removed = 0;
+    if std::fs::exists(&cache)? {
+        for entry in fs::read_dir(&cache)? {
+            let path = entry?.path();
+            if path == logs {
+                continue;
+            }
+            if dry_run {
+                println!("dry-run: would remove {}", path.display());
+                continue;
+            }
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            println!("removed {}", path.display());
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        print_success!("cleared {removed} cached file(s)");
+    } else if !dry_run {
+        println!("the cache is already empty");
+    }
+    Ok(())
+}
+
+/// Show what a sync would have done for a dry-run
+fn print_plan(what: &str, plan: &[PlannedTransfer], fmt: impl Fn(&PlannedTransfer) -> String) {
+    if plan.is_empty() {
+        println!("dry-run: would {what} nothing");
+        return;
+    }
+    println!("dry-run: would {what} {} file(s)", plan.len());
+    for t in plan {
+        println!("- {}", fmt(t));
+    }
+}
+
+/// A save path of a game and what it resolves to on this machine
+#[derive(serde::Serialize)]
+struct ResolvedTemplate<'a> {
+    template: &'a Path,
+    /// None if it couldn't be resolved, then `error` says why
+    path: Option<PathBuf>,
+    exists: bool,
+    error: Option<String>,
+}
+
+/// Resolve each save path of `game`
+fn resolve_templates<'a>(
+    game: &'a GameManifest,
+    info: &TemplateInfo,
+    opts: &SyncOptions,
+) -> Vec<ResolvedTemplate<'a>> {
+    game.files
+        .iter()
+        .filter(|(_, f)| f.tags.contains(&FileTag::Save))
+        .map(|(t, _)| t)
+        .sorted()
+        .map(|template| match template.apply_substs(info) {
+            Ok(p) => {
+                let p = if opts.case_insensitive_paths {
+                    resolve_case_insensitive(Path::new(&p))
+                } else {
+                    PathBuf::from(p)
+                };
+                ResolvedTemplate {
+                    template: template.as_raw_path(),
+                    exists: p.exists(),
+                    path: Some(p),
+                    error: None,
+                }
+            }
+            Err(e) => ResolvedTemplate {
+                template: template.as_raw_path(),
+                path: None,
+                exists: false,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// Print what each save path of `game` resolves to and whether it exists
+fn print_templates(game: &GameManifest, info: &TemplateInfo, opts: &SyncOptions) {
+    for t in resolve_templates(game, info, opts) {
+        let raw = t.template.display();
+        match (&t.path, &t.error) {
+            (Some(p), _) => {
+                let status = if t.exists {
+                    "(exists)".green()
+                } else {
+                    "(missing)".yellow()
+                };
+                println!("- {raw} -> {} {status}", p.display());
+            }
+            (None, e) => println!("- {raw} -> {}", e.as_deref().unwrap_or_default().red()),
+        }
+    }
+}
+
+/// `games info` for `--output json`
+#[derive(serde::Serialize)]
+struct GameInfo<'a> {
+    name: &'a str,
+    manifest: &'a GameManifest,
+    /// None if it couldn't be worked out, there are no save files then
+    platform: Option<PlatformOpt>,
+    save_files: Vec<ResolvedTemplate<'a>>,
+}
+
+/// An entry of `backends list` for `--output json`
+#[derive(serde::Serialize)]
+struct BackendListEntry {
+    #[serde(flatten)]
+    backend: BackendInfo,
+    default: bool,
+}
+
+/// `verify` for `--output json`
+#[derive(serde::Serialize)]
+struct VerifyOutput<'a> {
+    game: &'a str,
+    backend: &'a str,
+    consistent: bool,
+    #[serde(flatten)]
+    verification: &'a Verification,
+}
+
+#[derive(serde::Serialize)]
+struct GameSearchResult<'a> {
+    name: &'a str,
+    steam_id: Option<u32>,
+    gog_id: Option<u32>,
+}
+
+impl std::fmt::Display for GameSearchResult<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name.bold())?;
+        if let Some(id) = self.steam_id {
+            write!(f, " (steam: {id})")?;
+        }
+        if let Some(id) = self.gog_id {
+            write!(f, " (gog: {id})")?;
+        }
+        Ok(())
+    }
+}
+
+/// From the config, it is global as the error dialogs are shown after `run` has returned
+static DIALOG_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
 async fn run() -> anyhow::Result<()> {
     let start_time = SystemTime::now();
     let args = CliArgs::try_parse()?;
+    if let Some(Operation::Completions { shell }) = &args.op {
+        // doesn't need any of the config or logging so do it before they can fail
+        clap_complete::generate(
+            clap_complete::Shell::from(*shell),
+            &mut CliArgs::command(),
+            "cinc",
+            &mut io::stdout(),
+        );
+        return Ok(());
+    }
 
-    init_file_logging().expect("failed to init file logging");
+    if let Some(root) = &args.data_root {
+        set_data_root(root.clone());
+    }
+    colored::control::set_override(should_colorize(args.color));
+    // launch output is usually hidden by the launcher so there isn't any point logging to stderr
+    let log_stderr = !matches!(args.op, Some(Operation::Launch(_)));
+    init_logging(args.log_format, args.log_level(), log_stderr).expect("failed to init logging");
+
+    if let Some(Operation::ClearCache) = &args.op {
+        // works even if the config or keyring is what's broken
+        return clear_cache(args.dry_run);
+    }
 
     let secrets = SecretsApi::new().await?;
     let cfg_file = args.config_path.map(Ok).unwrap_or_else(get_cfg_path)?;
     let cfg = read_config(&cfg_file)?;
+    if let Some(timeout) = cfg.dialog_timeout() {
+        let _ = DIALOG_TIMEOUT.set(timeout);
+    }
     let cfg_errs = cfg.validate(&secrets).await;
     if !cfg_errs.is_empty() {
-        bail!(
-            "errors in config\n{}",
-            cfg_errs
-                .iter()
-                .map(|e| format!("- {e}"))
-                .collect_vec()
-                .join("\n")
-        );
+        Err(InvalidConfigError(cfg_errs))?;
+    }
+    // probing writes to the disk
+    if !args.dry_run {
+        for e in cfg.unusable_backend_roots() {
+            warn!("{e}");
+        }
+    }
+    if let Some(name) = &cfg.device_name {
+        backends::set_device_name(name.clone());
     }
 
     let manifest_url = cfg.manifest_url.as_deref().unwrap_or(DEFAULT_MANIFEST_URL);
     if args.update {
-        update_manifest(manifest_url).await?;
+        update_manifest(manifest_url, cfg.network_timeout(), args.dry_run).await?;
     }
     debug!("secrets available: {}", secrets.available());
     let Some(op) = &args.op else {
         if !args.update {
-            let Err(e) = CliArgs::try_parse_from(["cinc", "--help"].into_iter()) else {
+            let Err(e) = CliArgs::try_parse_from(["cinc", "--help"]) else {
                 unreachable!()
             }; // hacky way of showing help
             println!("{e}");
@@ -239,6 +639,7 @@ async fn run() -> anyhow::Result<()> {
         cinc::args::Operation::Launch(
             largs @ LaunchArgs {
                 no_download,
+                confirm_upload_only,
                 command,
                 ..
             },
@@ -247,56 +648,156 @@ async fn run() -> anyhow::Result<()> {
             if cfg.backends.is_empty() {
                 bail!("invalid config: at least one backend must be specified");
             }
-            if *no_download && !ui::show_no_download_confirmation()? {
+            let run_game = || -> Result<()> {
+                let launch_time = SystemTime::now();
+                debug!(
+                    "we had an overhead of {}ms",
+                    launch_time.duration_since(start_time)?.as_millis()
+                );
+                std::process::Command::new(&command[0])
+                    .args(command.iter().skip(1))
+                    .spawn()
+                    .context("failed to launch the game")?
+                    .wait()?;
+                Ok(())
+            };
+            if largs.offline {
+                info!("offline, running the game without syncing");
+                return run_game();
+            }
+            if *no_download
+                && !args.yes
+                && !confirm_upload_only
+                && !ui::show_no_download_confirmation()?
+            {
                 tracing::info!("aborting due to user deciding not to continue");
                 return Ok(());
             }
             let manifest_start = SystemTime::now();
-            let manifests = get_game_manifests(manifest_url).await?;
+            let manifests =
+                get_launch_manifests(manifest_url, cfg.network_timeout(), largs).await?;
             let manifest_end = SystemTime::now();
             debug!(
                 "parsing the manifest took {}ms",
                 manifest_end.duration_since(manifest_start)?.as_millis()
             );
-            let platform = LaunchInfo::new(&cfg, &manifests, &secrets, largs)?;
-
+            if largs.list_templates {
+                let platform = LaunchInfo::new(&cfg, &manifests, &secrets, largs, args.yes)?;
+                let info = platform.template_info()?;
+                println!("save files of {}:", platform.game_name());
+                print_templates(platform.manifest(), &info, platform.sync_options());
+                return Ok(());
+            }
             if !args.dry_run {
-                platform.sync_down().await?;
-            } else {
-                info!("not downloading files due to dry-run");
+                run_sync(
+                    &cfg,
+                    &manifests,
+                    &secrets,
+                    largs,
+                    args.yes,
+                    async |launch| {
+                        if let Err(e) = LastPlayed::record_at(
+                            &last_played_file(),
+                            launch.game_name(),
+                            Utc::now(),
+                        ) {
+                            warn!(
+                                "failed to record when {} was played: {e:?}",
+                                launch.game_name()
+                            );
+                        }
+                        run_game()
+                    },
+                )
+                .await?;
+                return Ok(());
             }
 
-            let launch_time = SystemTime::now();
-            debug!(
-                "we had an overhead of {}ms",
-                launch_time.duration_since(start_time)?.as_millis()
-            );
-
-            let mut c = std::process::Command::new(&command[0])
-                .args(command.iter().skip(1))
-                .spawn()
-                .unwrap();
-            c.wait().unwrap();
-
-            if args.dry_run || !largs.no_upload {
-                platform.sync_up().await?;
+            let platform = LaunchInfo::new(&cfg, &manifests, &secrets, largs, args.yes)?;
+            info!("not running hooks due to dry-run");
+            let plan = platform.plan_sync_down().await?;
+            let time_display = TimeDisplay::from_config(&cfg);
+            if let Some(lock) = &plan.locked_by {
+                println!(
+                    "dry-run: {} has had '{}' locked since {}, {}",
+                    lock.hostname,
+                    platform.backend_name(),
+                    time_display.show(lock.timestamp),
+                    if args.yes {
+                        "it would be taken over due to --yes"
+                    } else {
+                        "you would be asked whether to take it over"
+                    }
+                );
+            }
+            if let Some(issue) = &plan.conflict {
+                println!(
+                    "dry-run: {} the local saves from {} are newer than those on '{}' from {} by {}, {}",
+                    "conflict:".yellow(),
+                    time_display.show(issue.local_time),
+                    issue.remote_name,
+                    time_display.show(issue.remote_time),
+                    issue.remote_last_writer,
+                    if args.yes {
+                        "the local ones would be kept due to --yes"
+                    } else {
+                        "you would be asked whether to download"
+                    }
+                );
+            }
+            print_plan("download", &plan.transfers, |t| {
+                format!(
+                    "{} -> {}{}",
+                    t.remote_path.display(),
+                    t.local_path.display(),
+                    if t.local_path.exists() {
+                        " (overwrite)"
+                    } else {
+                        ""
+                    }
+                )
+            });
+            run_game()?;
+            if largs.no_upload {
+                info!("not uploading as --no-upload was passed");
             } else {
-                debug!("not uploading due to --debug-no-upload or dry-run flag");
+                print_plan("upload", &platform.plan_sync_up()?, |t| {
+                    format!("{} -> {}", t.local_path.display(), t.remote_path.display())
+                });
             }
         }
+        cinc::args::Operation::Resolve(largs) => {
+            let manifests =
+                get_launch_manifests(manifest_url, cfg.network_timeout(), largs).await?;
+            let platform = LaunchInfo::new(&cfg, &manifests, &secrets, largs, args.yes)?;
+            println!("platform: {:?}", platform.platform());
+            println!("game: {}", platform.game_name());
+            println!("backend: {}", platform.backend_name());
+            let info = platform
+                .template_info()
+                .context("failed to work out the template variables")?;
+            println!("template variables: {info:#?}");
+            println!("save files:");
+            print_templates(platform.manifest(), &info, platform.sync_options());
+        }
         cinc::args::Operation::DebugSyncDialog {
             remote_name,
             last_writer,
         } => {
             let now = Local::now().to_utc();
-            let r = ui::spawn_sync_confirm(SyncIssueInfo {
-                remote_name: remote_name.to_owned(),
-                local_time: now,
-                remote_time: now,
-                remote_last_writer: last_writer.to_owned(),
-            })?;
+            let r = ui::spawn_sync_confirm(
+                SyncIssueInfo {
+                    remote_name: remote_name.to_owned(),
+                    local_time: now,
+                    remote_time: now,
+                    remote_last_writer: last_writer.to_owned(),
+                    recent_writers: Vec::new(),
+                },
+                &TimeDisplay::from_config(&cfg),
+            )?;
             println!("{r:?}");
         }
+        Operation::Completions { .. } => unreachable!("completions are handled before config"),
         cinc::args::Operation::DebugPskInput => {
             let psk = user_psk_input("example: ")?;
             println!("\n{psk}");
@@ -306,8 +807,17 @@ async fn run() -> anyhow::Result<()> {
                 name,
                 ty,
                 root,
+                rclone_remote,
+                git_push_remote,
                 webdav_url,
                 webdav_username,
+                webdav_auth,
+                webdav_ca_cert,
+                webdav_danger_accept_invalid_certs,
+                ftp_host,
+                ftp_port,
+                ftp_username,
+                ftp_tls,
                 set_default,
             } => {
                 let mut cfg = cfg;
@@ -316,34 +826,66 @@ async fn run() -> anyhow::Result<()> {
                 }
                 let backend_ty = match ty {
                     cinc::config::BackendType::Filesystem => BackendTy::Filesystem {
-                        root: root.to_owned(),
+                        root: root.to_owned().unwrap_or_else(|| "/".into()),
+                    },
+                    cinc::config::BackendType::Rclone => BackendTy::Rclone {
+                        remote: rclone_remote
+                            .to_owned()
+                            .ok_or_else(|| anyhow!("--rclone-remote is required for rclone"))?,
+                        root: root.to_owned().unwrap_or_else(|| "/".into()),
+                    },
+                    // the default of / would put the repository at the filesystem root
+                    cinc::config::BackendType::Git => BackendTy::Git {
+                        repo: root.to_owned().ok_or_else(|| {
+                            anyhow!("--root is required for git, it is the path of the repository")
+                        })?,
+                        push_remote: git_push_remote.to_owned(),
                     },
                     cinc::config::BackendType::WebDav => {
-                        let webdav_psk =
-                            user_psk_input("enter webdav password, leave blank for no password: ")?;
-                        let webdav_psk = if webdav_psk.is_empty() {
-                            None
-                        } else {
-                            let use_secrets = secrets.available()
-                                && user_input_yesno(
-                                    "use system secrets API to store this password? (recommended) [Y/n]: ",
-                                    true,
-                                )?;
-                            Some(if use_secrets {
-                                let secret_name = Uuid::new_v4().to_string();
-                                if !args.dry_run {
-                                    secrets.add_item(&secret_name, &webdav_psk).await?;
-                                }
-                                Secret::SystemSecret(secret_name)
-                            } else {
-                                Secret::Plain(webdav_psk)
-                            })
-                        };
+                        let webdav_psk = prompt_backend_secret(
+                            &secrets,
+                            name,
+                            webdav_secret_prompt(*webdav_auth),
+                            cfg.allow_plaintext_secrets,
+                            args.dry_run,
+                        )
+                        .await?;
                         BackendTy::WebDav(WebDavInfo {
                             url: webdav_url.to_owned().expect("missing webdav url"),
-                            username: webdav_username.to_owned().expect("missing webdav username"),
+                            username: match webdav_auth {
+                                WebDavAuth::Basic => {
+                                    webdav_username.to_owned().expect("missing webdav username")
+                                }
+                                WebDavAuth::Bearer => {
+                                    webdav_username.to_owned().unwrap_or_default()
+                                }
+                            },
                             psk: webdav_psk,
-                            root: root.to_owned(),
+                            root: root.to_owned().unwrap_or_else(|| "/".into()),
+                            auth: *webdav_auth,
+                            ca_cert_path: webdav_ca_cert.to_owned(),
+                            danger_accept_invalid_certs: *webdav_danger_accept_invalid_certs,
+                        })
+                    }
+                    cinc::config::BackendType::Ftp => {
+                        let host = ftp_host
+                            .to_owned()
+                            .ok_or_else(|| anyhow!("--ftp-host is required for ftp"))?;
+                        let psk = prompt_backend_secret(
+                            &secrets,
+                            name,
+                            FTP_SECRET_PROMPT,
+                            cfg.allow_plaintext_secrets,
+                            args.dry_run,
+                        )
+                        .await?;
+                        BackendTy::Ftp(FtpInfo {
+                            host,
+                            port: *ftp_port,
+                            username: ftp_username.to_owned(),
+                            psk,
+                            root: root.to_owned().unwrap_or_else(|| "/".into()),
+                            tls: *ftp_tls,
                         })
                     }
                 };
@@ -358,6 +900,144 @@ async fn run() -> anyhow::Result<()> {
                 write_cfg(&cfg, &cfg_file, args.dry_run)?;
                 print_success!("successfully added backend '{name}'");
             }
+            cinc::args::BackendsArgs::Edit {
+                name,
+                root,
+                rclone_remote,
+                git_push_remote,
+                webdav_url,
+                webdav_username,
+                webdav_auth,
+                webdav_ca_cert,
+                webdav_danger_accept_invalid_certs,
+                ftp_host,
+                ftp_port,
+                ftp_username,
+                ftp_tls,
+                change_password,
+            } => {
+                let mut cfg = cfg;
+                let allow_plaintext = cfg.allow_plaintext_secrets;
+                let Some(backend) = cfg.backends.iter_mut().find(|b| &b.name == name) else {
+                    bail!("cannot edit backend '{name}' as it does not exist");
+                };
+                let mut secret_changed = false;
+                let webdav_opts = webdav_url.is_some()
+                    || webdav_username.is_some()
+                    || webdav_auth.is_some()
+                    || webdav_ca_cert.is_some()
+                    || webdav_danger_accept_invalid_certs.is_some();
+                let ftp_opts = ftp_host.is_some()
+                    || ftp_port.is_some()
+                    || ftp_username.is_some()
+                    || ftp_tls.is_some();
+                let given_opts = [
+                    ("webdav", webdav_opts),
+                    ("ftp", ftp_opts),
+                    ("rclone", rclone_remote.is_some()),
+                    ("git", git_push_remote.is_some()),
+                ];
+                let backend_kind = match &backend.info {
+                    BackendTy::Filesystem { .. } => "filesystem",
+                    BackendTy::WebDav(_) => "webdav",
+                    BackendTy::Rclone { .. } => "rclone",
+                    BackendTy::Git { .. } => "git",
+                    BackendTy::Ftp(_) => "ftp",
+                };
+                if let Some((kind, _)) = given_opts
+                    .iter()
+                    .find(|(kind, given)| *given && *kind != backend_kind)
+                {
+                    bail!("cannot set {kind} options on {backend_kind} backend '{name}'");
+                }
+                match &mut backend.info {
+                    BackendTy::Filesystem { root: fs_root } => {
+                        if let Some(root) = root {
+                            *fs_root = root.to_owned();
+                        }
+                    }
+                    BackendTy::Rclone {
+                        remote,
+                        root: rclone_root,
+                    } => {
+                        if let Some(r) = rclone_remote {
+                            *remote = r.to_owned();
+                        }
+                        if let Some(root) = root {
+                            *rclone_root = root.to_owned();
+                        }
+                    }
+                    BackendTy::Git { repo, push_remote } => {
+                        if let Some(root) = root {
+                            *repo = root.to_owned();
+                        }
+                        if let Some(r) = git_push_remote {
+                            *push_remote = (!r.is_empty()).then(|| r.to_owned());
+                        }
+                    }
+                    BackendTy::WebDav(info) => {
+                        if let Some(root) = root {
+                            info.root = root.to_owned();
+                        }
+                        if let Some(url) = webdav_url {
+                            info.url = url.to_owned();
+                        }
+                        if let Some(username) = webdav_username {
+                            info.username = username.to_owned();
+                        }
+                        if let Some(auth) = webdav_auth {
+                            info.auth = *auth;
+                        }
+                        if let Some(ca) = webdav_ca_cert {
+                            info.ca_cert_path = Some(ca.to_owned());
+                        }
+                        if let Some(danger) = webdav_danger_accept_invalid_certs {
+                            info.danger_accept_invalid_certs = *danger;
+                        }
+                    }
+                    BackendTy::Ftp(info) => {
+                        if let Some(root) = root {
+                            info.root = root.to_owned();
+                        }
+                        if let Some(host) = ftp_host {
+                            info.host = host.to_owned();
+                        }
+                        if let Some(port) = ftp_port {
+                            info.port = *port;
+                        }
+                        if let Some(username) = ftp_username {
+                            info.username = username.to_owned();
+                        }
+                        if let Some(tls) = ftp_tls {
+                            info.tls = *tls;
+                        }
+                    }
+                }
+                if *change_password {
+                    change_backend_secret(&secrets, backend, allow_plaintext, args.dry_run).await?;
+                    secret_changed = true;
+                }
+                if secret_changed && !args.dry_run && secrets.available() {
+                    let used = cfg.used_keyring_ids().collect_vec();
+                    secrets.garbage_collect(&used).await?;
+                }
+                write_cfg(&cfg, &cfg_file, args.dry_run)?;
+                print_success!("successfully edited backend '{name}'");
+            }
+            cinc::args::BackendsArgs::SetPassword { name } => {
+                let mut cfg = cfg;
+                let allow_plaintext = cfg.allow_plaintext_secrets;
+                let Some(backend) = cfg.backends.iter_mut().find(|b| &b.name == name) else {
+                    bail!("cannot set the password of backend '{name}' as it does not exist");
+                };
+                change_backend_secret(&secrets, backend, allow_plaintext, args.dry_run).await?;
+                if !args.dry_run && secrets.available() {
+                    let used = cfg.used_keyring_ids().collect_vec();
+                    secrets.garbage_collect(&used).await?;
+                }
+                write_cfg(&cfg, &cfg_file, args.dry_run)?;
+                print_success!("successfully set the password of backend '{name}'");
+            }
             cinc::args::BackendsArgs::Remove { name } => {
                 let mut cfg = cfg;
                 if &cfg.default_backend == name {
@@ -381,6 +1061,24 @@ async fn run() -> anyhow::Result<()> {
                 write_cfg(&cfg, &cfg_file, args.dry_run)?;
                 print_success!("successfully removed backend '{name}'");
             }
+            cinc::args::BackendsArgs::List if args.output == OutputFormat::Json => {
+                let backends = cfg
+                    .backends
+                    .iter()
+                    .map(|b| {
+                        let mut backend = b.clone();
+                        // the keyring entry is harmless but a plaintext one isn't
+                        if let Some(psk) = backend.info.psk_mut() {
+                            *psk = None;
+                        }
+                        BackendListEntry {
+                            default: b.name == cfg.default_backend,
+                            backend,
+                        }
+                    })
+                    .collect_vec();
+                println!("{}", serde_json::to_string(&backends)?);
+            }
             cinc::args::BackendsArgs::List => {
                 for b in cfg.backends.iter() {
                     println!(
@@ -394,6 +1092,39 @@ async fn run() -> anyhow::Result<()> {
                     );
                 }
             }
+            cinc::args::BackendsArgs::Test { name } => {
+                let Some(info) = cfg.backends.iter().find(|b| &b.name == name) else {
+                    bail!("cannot test backend '{name}' as it does not exist");
+                };
+                let start = SystemTime::now();
+                let b = info.to_backend("", &secrets, cfg.network_timeout())?;
+                if args.dry_run {
+                    b.exists(Path::new("")).await?;
+                    info!("not writing test file due to dry-run");
+                } else {
+                    let test_file = PathBuf::from(format!(".cinc-test-{}", Uuid::new_v4()));
+                    let contents = b"cinc backend test";
+                    b.write_file(&test_file, contents)
+                        .await
+                        .context("while writing test file")?;
+                    let read = b
+                        .read_file(&test_file)
+                        .await
+                        .context("while reading test file")?;
+                    b.remove_file(&test_file)
+                        .await
+                        .context("while removing test file")?;
+                    if read != contents {
+                        bail!(
+                            "file read back from backend '{name}' did not match what was written"
+                        );
+                    }
+                }
+                print_success!(
+                    "backend '{name}' is working (took {}ms)",
+                    SystemTime::now().duration_since(start)?.as_millis()
+                );
+            }
             cinc::args::BackendsArgs::SetDefault { name } => {
                 let mut cfg = cfg;
                 if !cfg.backends.iter().any(|b| &b.name == name) {
@@ -405,6 +1136,305 @@ async fn run() -> anyhow::Result<()> {
                 print_success!("successfully set backend '{name}' as the default backend");
             }
         },
+        cinc::args::Operation::Games(games_args) => {
+            let manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+            match games_args {
+                GamesArgs::List {
+                    with_saves,
+                    sort_by_recent,
+                } => {
+                    let mut names = manifests
+                        .iter()
+                        .filter(|(_, m)| !with_saves || m.has_saves())
+                        .map(|(name, _)| name.as_str())
+                        .sorted()
+                        .collect_vec();
+                    let played = if *sort_by_recent {
+                        let played = LastPlayed::read(&last_played_file())?;
+                        names = played.sort_by_recent(names);
+                        Some(played)
+                    } else {
+                        None
+                    };
+                    if args.output == OutputFormat::Json {
+                        println!("{}", serde_json::to_string(&names)?);
+                    } else {
+                        let time_display = TimeDisplay::from_config(&cfg);
+                        for name in names {
+                            match played.as_ref().and_then(|p| p.get(name)) {
+                                Some(t) => println!("{name} (played {})", time_display.show(t)),
+                                None => println!("{name}"),
+                            }
+                        }
+                    }
+                }
+                GamesArgs::Info { name } => {
+                    let Some(game) = manifests.get(name) else {
+                        bail!(
+                            "could not find '{name}' in the manifest, try searching for it with `cinc games search`"
+                        );
+                    };
+                    let opts = SyncOptions::from_config(&cfg);
+                    let detected = detect_local_template_info(name, game, &opts);
+                    if let Err(e) = &detected {
+                        warn!("failed to resolve the platform for '{name}': {e}");
+                    }
+                    if args.output == OutputFormat::Json {
+                        let (platform, save_files) = match &detected {
+                            Ok((platform, info)) => {
+                                (Some(*platform), resolve_templates(game, info, &opts))
+                            }
+                            Err(_) => (None, Vec::new()),
+                        };
+                        let info = GameInfo {
+                            name,
+                            manifest: game,
+                            platform,
+                            save_files,
+                        };
+                        println!("{}", serde_json::to_string(&info)?);
+                        return Ok(());
+                    }
+                    println!("{}", serde_yaml::to_string(game)?);
+                    let Ok((platform, info)) = detected else {
+                        return Ok(());
+                    };
+                    println!("save files (resolved as {platform:?}):");
+                    print_templates(game, &info, &opts);
+                }
+                GamesArgs::Search { query } => {
+                    let query = query.to_lowercase();
+                    let found = manifests
+                        .iter()
+                        .filter(|(name, _)| name.to_lowercase().contains(&query))
+                        .sorted_by_key(|(name, _)| *name)
+                        .map(|(name, m)| GameSearchResult {
+                            name,
+                            steam_id: m.steam.as_ref().map(|s| s.id.id()),
+                            gog_id: m.gog.as_ref().map(|g| g.id),
+                        })
+                        .collect_vec();
+                    if args.output == OutputFormat::Json {
+                        println!("{}", serde_json::to_string(&found)?);
+                    } else {
+                        for r in found {
+                            println!("- {r}");
+                        }
+                    }
+                }
+            }
+        }
+        cinc::args::Operation::Prune { backend, keep } => {
+            let name = backend.as_ref().unwrap_or(&cfg.default_backend);
+            let Some(info) = cfg.backends.iter().find(|b| &b.name == name) else {
+                bail!("backend '{name}' does not exist");
+            };
+            let manifests;
+            let keep: Vec<&str> = if keep.is_empty() {
+                manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+                manifests.keys().map(String::as_str).collect()
+            } else {
+                keep.iter().map(String::as_str).collect()
+            };
+            let b = info.to_root_backend(&secrets, cfg.network_timeout())?;
+            let orphans = info.orphaned_games(&b, &keep).await?;
+            if orphans.is_empty() {
+                println!("nothing to prune on '{name}'");
+                return Ok(());
+            }
+            if args.dry_run {
+                println!(
+                    "dry-run: would remove {} game(s) from '{name}'",
+                    orphans.len()
+                );
+                for p in &orphans {
+                    println!("- {}", p.display());
+                }
+                return Ok(());
+            }
+            for p in orphans {
+                let remove = args.yes
+                    || user_input_yesno(
+                        &format!("remove '{}' from '{name}'? [y/N]: ", p.display()),
+                        false,
+                    )?;
+                if remove {
+                    b.remove_dir(&p).await?;
+                    print_success!("removed '{}'", p.display());
+                }
+            }
+        }
+        cinc::args::Operation::Import { from, games } => {
+            let manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+            let Some(info) = cfg.backends.iter().find(|b| b.name == cfg.default_backend) else {
+                bail!("no backends or default backend is invalid");
+            };
+            let opts = SyncOptions::from_config(&cfg);
+            let backups = ludusavi::read_backups(from)?
+                .into_iter()
+                .filter(|g| games.is_empty() || games.contains(&g.name))
+                .collect_vec();
+            if backups.is_empty() {
+                bail!("no ludusavi backups to import in {from:?}");
+            }
+            for backup in backups {
+                let Some((game_name, game)) = manifests.get_key_value(&backup.name) else {
+                    warn!("skipping '{}' as it isn't in the manifest", backup.name);
+                    continue;
+                };
+                let files = backup
+                    .files
+                    .iter()
+                    .map(|f| (f.original.clone(), f.stored.clone()))
+                    .collect_vec();
+                let (mgr, unmatched) =
+                    SyncMgr::from_backup(game_name, game, &files, &info.name, &opts)?;
+                for f in &unmatched {
+                    warn!(
+                        "skipping {f:?} from '{game_name}' as it doesn't match any of its save paths"
+                    );
+                }
+                let b = info.to_backend(game_name, &secrets, cfg.network_timeout())?;
+                if b.read_sync_time().await?.is_some() && !args.yes {
+                    println!(
+                        "skipping '{game_name}' as it already has saves on '{}', pass --yes to overwrite them",
+                        info.name
+                    );
+                    continue;
+                }
+                let plan = mgr.upload_plan()?;
+                if plan.is_empty() {
+                    warn!("nothing to import for '{game_name}'");
+                    continue;
+                }
+                if args.dry_run {
+                    println!("{game_name}:");
+                    print_plan("import", &plan, |t| {
+                        format!("{} -> {}", t.local_path.display(), t.remote_path.display())
+                    });
+                    continue;
+                }
+                mgr.upload(&b, true).await?;
+                print_success!("imported '{game_name}' from {}", backup.when);
+            }
+        }
+        cinc::args::Operation::Export { game, out, local } => {
+            let manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+            let Some((game_name, manifest)) = manifests.get_key_value(game) else {
+                bail!(
+                    "could not find '{game}' in the manifest, try searching for it with `cinc games search`"
+                );
+            };
+            let Some(info) = cfg.backends.iter().find(|b| b.name == cfg.default_backend) else {
+                bail!("no backends or default backend is invalid");
+            };
+            let opts = SyncOptions::from_config(&cfg);
+            let mgr = SyncMgr::from_detected(game_name, manifest, &info.name, &opts)?;
+            let b = if *local {
+                None
+            } else {
+                Some(info.to_backend(game_name, &secrets, cfg.network_timeout())?)
+            };
+            if args.dry_run {
+                println!("dry-run: would export '{game_name}' to {}", out.display());
+                return Ok(());
+            }
+            let n = mgr.export_zip(b.as_ref(), out).await?;
+            print_success!("exported {n} file(s) to {}", out.display());
+        }
+        cinc::args::Operation::Extract { game, dest } => {
+            let Some(info) = cfg.backends.iter().find(|b| b.name == cfg.default_backend) else {
+                bail!("no backends or default backend is invalid");
+            };
+            let b = info.to_backend(game, &secrets, cfg.network_timeout())?;
+            if args.dry_run {
+                println!(
+                    "dry-run: would extract the saves of '{game}' to {}",
+                    dest.display()
+                );
+                return Ok(());
+            }
+            let n = extract_remote(&b, dest)
+                .await
+                .with_context(|| format!("failed to extract the saves of '{game}'"))?;
+            print_success!("extracted {n} file(s) to {}", dest.display());
+        }
+        cinc::args::Operation::Verify { game } => {
+            let manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+            let Some((game_name, manifest)) = manifests.get_key_value(game) else {
+                bail!(
+                    "could not find '{game}' in the manifest, try searching for it with `cinc games search`"
+                );
+            };
+            let Some(info) = cfg.backends.iter().find(|b| b.name == cfg.default_backend) else {
+                bail!("no backends or default backend is invalid");
+            };
+            let opts = SyncOptions::from_config(&cfg);
+            let mgr = SyncMgr::from_detected(game_name, manifest, &info.name, &opts)?;
+            let b = info.to_backend(game_name, &secrets, cfg.network_timeout())?;
+            let r = mgr.verify(&b).await?;
+            if args.output == OutputFormat::Json {
+                let out = VerifyOutput {
+                    game: game_name,
+                    backend: &info.name,
+                    consistent: r.is_consistent(),
+                    verification: &r,
+                };
+                println!("{}", serde_json::to_string(&out)?);
+                if !r.is_consistent() {
+                    bail!(
+                        "the saves of '{game_name}' on '{}' don't match this machine's",
+                        info.name
+                    );
+                }
+                return Ok(());
+            }
+            for p in &r.differs {
+                println!("- {} {}", p.display(), "(differs)".yellow());
+            }
+            for p in &r.missing_locally {
+                println!("- {} {}", p.display(), "(only on the backend)".red());
+            }
+            for p in &r.not_uploaded {
+                println!("- {} {}", p.display(), "(not uploaded)".yellow());
+            }
+            if !r.is_consistent() {
+                bail!(
+                    "the saves of '{game_name}' on '{}' don't match this machine's, {} file(s) match",
+                    info.name,
+                    r.matching
+                );
+            }
+            print_success!(
+                "all {} file(s) of '{game_name}' match '{}'",
+                r.matching,
+                info.name
+            );
+        }
+        cinc::args::Operation::Restore(RestoreArgs::Config { game }) => {
+            let manifests = get_game_manifests(manifest_url, cfg.network_timeout()).await?;
+            let Some((game_name, manifest)) = manifests.get_key_value(game) else {
+                bail!(
+                    "could not find '{game}' in the manifest, try searching for it with `cinc games search`"
+                );
+            };
+            let Some(info) = cfg.backends.iter().find(|b| b.name == cfg.default_backend) else {
+                bail!("no backends or default backend is invalid");
+            };
+            let opts = SyncOptions {
+                config_files: true,
+                ..SyncOptions::from_config(&cfg)
+            };
+            let mgr = SyncMgr::from_detected(game_name, manifest, &info.name, &opts)?;
+            let b = info.to_backend(game_name, &secrets, cfg.network_timeout())?;
+            if args.dry_run {
+                println!("dry-run: would restore the config files of '{game_name}'");
+                return Ok(());
+            }
+            let n = mgr.restore_config(&b).await?;
+            print_success!("restored {n} config file(s) for '{game_name}'");
+        }
+        Operation::ClearCache => unreachable!("clear-cache is handled before config"),
         cinc::args::Operation::DebugVersionIncompat { read } => {
             let curr_v = curr_crate_ver();
             let new_v = semver::Version::new(curr_v.major + 1, curr_v.minor, curr_v.patch);
@@ -439,7 +1469,11 @@ async fn main() {
                 });
             if is_without_term {
                 if let Some(msg) = msg {
-                    wrap(ui::show_panic_dialog(msg, info.location()));
+                    wrap(ui::show_panic_dialog(
+                        msg,
+                        info.location(),
+                        DIALOG_TIMEOUT.get().copied(),
+                    ));
                 }
             }
 
@@ -447,17 +1481,23 @@ async fn main() {
         }));
     }
     if let Err(e) = run().await {
-        tracing::error!("{e:?}");
-        if is_without_term {
-            if let Some(e @ IncomaptibleCincVersionError { .. }) = e.downcast_ref() {
-                wrap(ui::version_mismatch(e));
+        let code = ExitCode::for_error(&e);
+        if code == ExitCode::ConflictAborted {
+            // the user chose this so it isn't worth an error dialog
+            info!("{e}");
+        } else {
+            tracing::error!("{e:?}");
+            if is_without_term {
+                if let Some(e @ IncomaptibleCincVersionError { .. }) = e.downcast_ref() {
+                    wrap(ui::version_mismatch(e, DIALOG_TIMEOUT.get().copied()));
+                } else {
+                    wrap(ui::show_error_dialog(&e, DIALOG_TIMEOUT.get().copied()));
+                }
             } else {
-                wrap(ui::show_error_dialog(&e));
+                eprintln!("{}", format!("{e:?}").red());
             }
-        } else {
-            eprintln!("{}", format!("{e:?}").red());
         }
-        std::process::exit(1);
+        std::process::exit(code as i32);
     }
 
     fn wrap<E: std::fmt::Debug, T>(r: Result<T, E>) {
@@ -1,7 +1,6 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     env,
-    ops::Deref,
     path::{Path, PathBuf},
 };
 
@@ -15,25 +14,47 @@ use crate::{config::SteamId, paths::PathExt};
 pub struct GameManifest {
     pub steam: Option<SteamInfo>,
     pub gog: Option<GogInfo>,
+    pub epic: Option<EpicInfo>,
+    pub amazon: Option<AmazonInfo>,
     #[serde(default)]
     pub files: HashMap<TemplatePath, FileConfig>,
     #[serde(default)]
     pub launch: HashMap<TemplatePath, Vec<LaunchConfig>>,
+    /// Registry keys, e.g. `HKEY_CURRENT_USER/Software/Game`, only the predicates on the store
+    /// are used by the manifest
+    #[serde(default)]
+    pub registry: HashMap<String, FileConfig>,
     pub install_dir: Option<GameInstallDir>,
 }
 
+impl GameManifest {
+    /// Whether any of the files for this game are tagged as saves
+    pub fn has_saves(&self) -> bool {
+        self.files
+            .values()
+            .chain(self.registry.values())
+            .any(|f| f.tags.contains(&FileTag::Save))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 struct EmptyObj {}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
-// the manifest format is weird and has a hashmap here
-pub struct GameInstallDir(HashMap<String, EmptyObj>);
-
-impl Deref for GameInstallDir {
-    type Target = str;
+// the manifest format is weird and has a map here, it is sorted so the first name is the same on
+// every machine
+pub struct GameInstallDir(BTreeMap<String, EmptyObj>);
+
+impl GameInstallDir {
+    /// Name of the install dir, the first if there are several. This is none if the manifest has
+    /// an empty entry
+    pub fn name(&self) -> Option<&str> {
+        self.names().next()
+    }
 
-    fn deref(&self) -> &Self::Target {
-        self.0.keys().next().unwrap().as_str()
+    /// All the names the game may be installed under
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|k| k.as_str())
     }
 }
 
@@ -146,6 +167,10 @@ impl Arch {
 }
 pub type SteamInfo = StoreInfo<SteamId>;
 pub type GogInfo = StoreInfo<u32>;
+/// Id is the epic app name, e.g. what legendary passes as `-epicapp`
+pub type EpicInfo = StoreInfo<String>;
+/// Id is the amazon product id, as heroic gives in `HEROIC_APP_NAME`
+pub type AmazonInfo = StoreInfo<String>;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StoreInfo<Id> {
@@ -165,10 +190,16 @@ pub enum TemplateError {
     VariableNotAvailable(&'static str),
     #[error("unknown template variable '{0}'")]
     UnknownVariable(String),
+    #[error("template variable '{0}' requires a wine prefix but the game is running natively")]
+    NoWinePrefix(String),
+    #[error("template variable '{0}' resolved to a path that is not valid utf8 {1:?}")]
+    NonUtf8Path(String, PathBuf),
 }
 
+#[derive(Debug)]
 pub struct TemplateInfo {
-    pub win_prefix: PathBuf,
+    /// drive_c of the wine prefix, none for games running natively
+    pub win_prefix: Option<PathBuf>,
     pub win_user: String,
     pub base_dir: Option<PathBuf>,
     /// directory where games are installed. Under steam it should be the steam of the game otherwise the wine prefix usually
@@ -179,6 +210,10 @@ pub struct TemplateInfo {
     pub xdg_data: Option<PathBuf>,
     /// Install dir of the game in the manifest, or the game name
     pub install_dir: Option<PathBuf>,
+    /// Steam's userdata dir for the game, i.e. `<steam>/userdata/<id3>/<app id>`
+    pub steam_user_data: Option<PathBuf>,
+    /// User defined variables, only used if the variable is not a builtin one
+    pub custom_vars: HashMap<String, PathBuf>,
 }
 
 impl TemplatePath {
@@ -191,6 +226,12 @@ impl TemplatePath {
         Path::new(&self.0)
     }
 
+    fn win_prefix<'i>(var: &str, info: &'i TemplateInfo) -> Result<&'i Path, TemplateError> {
+        info.win_prefix
+            .as_deref()
+            .ok_or_else(|| TemplateError::NoWinePrefix(var.to_owned()))
+    }
+
     fn do_repl(var: &str, info: &TemplateInfo) -> Result<PathBuf, TemplateError> {
         let repl = match var {
             "xdgData" => info
@@ -209,24 +250,32 @@ impl TemplatePath {
                 .to_owned()
                 .or_else(env::home_dir)
                 .ok_or_else(|| TemplateError::FailedToLocateDir(var.to_owned()))?,
-            "winAppData" => info
-                .win_prefix
+            "winAppData" => Self::win_prefix(var, info)?
                 .join("users") // linux capitalisation senstive filesystems require this to be lowercase and windows doesn't care
                 .join(&info.win_user)
                 .join("AppData")
                 .join("Roaming"),
 
-            "winLocalAppData" => info
-                .win_prefix
+            "winLocalAppData" => Self::win_prefix(var, info)?
                 .join("users")
                 .join(&info.win_user)
                 .join("AppData")
                 .join("Local"),
-            "winDocuments" => info
-                .win_prefix
+            "winDocuments" => Self::win_prefix(var, info)?
                 .join("users")
                 .join(&info.win_user)
                 .join("Documents"),
+            "winDir" => Self::win_prefix(var, info)?.join("windows"),
+            "osUserName" => {
+                if info.win_prefix.is_some() {
+                    info.win_user.clone().into()
+                } else {
+                    env::var("USER")
+                        .or_else(|_| env::var("USERNAME"))
+                        .map_err(|_| TemplateError::FailedToLocateDir(var.to_owned()))?
+                        .into()
+                }
+            }
             "base" => info
                 .base_dir
                 .clone()
@@ -244,39 +293,41 @@ impl TemplatePath {
                 .ok_or(TemplateError::VariableNotAvailable("<storeUserId>"))?
                 .to_owned()
                 .into(),
+            "steamUserData" => info
+                .steam_user_data
+                .to_owned()
+                .ok_or(TemplateError::VariableNotAvailable("<steamUserData>"))?,
             "game" => info
                 .install_dir
                 .as_ref()
                 .ok_or(TemplateError::VariableNotAvailable("<game>"))?
                 .to_owned(),
-            _ => return Err(TemplateError::UnknownVariable(var.to_owned())),
+            _ => info
+                .custom_vars
+                .get(var)
+                .cloned()
+                .ok_or_else(|| TemplateError::UnknownVariable(var.to_owned()))?,
         };
         Ok(repl)
     }
 
     pub fn apply_substs(&self, info: &TemplateInfo) -> Result<String, TemplateError> {
-        let mut end = 0;
-        let mut substs = Vec::new();
-        while let Some(start) = self.0[end..].find('<').map(|s| s + end) {
-            end = self.0[end..]
+        let mut out = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        // a '>' without a '<' before it is just part of the path
+        while let Some(start) = rest.find('<') {
+            let len = rest[start + 1..]
                 .find('>')
-                .ok_or(TemplateError::NoClosingDelim)?
-                + end;
-            let var = &self.0[start + 1..end];
+                .ok_or(TemplateError::NoClosingDelim)?;
+            let var = &rest[start + 1..start + 1 + len];
             let repl = Self::do_repl(var, info)?;
-            substs.push((start, end, repl));
-            end += 1;
-        }
-
-        // rebuild string with substitions
-        let mut out = String::new();
-        let mut prev = 0;
-        for (start, end, repl) in substs {
-            out += &self.0[prev..start];
-            out += repl.to_str().unwrap();
-            prev = end + 1;
+            out += &rest[..start];
+            out += repl
+                .to_str()
+                .ok_or_else(|| TemplateError::NonUtf8Path(var.to_owned(), repl.clone()))?;
+            rest = &rest[start + len + 2..];
         }
-        out += &self.0[prev..self.0.len()];
+        out += rest;
         Ok(out)
     }
 }
@@ -285,7 +336,7 @@ impl TemplatePath {
 mod tests {
     use std::path::PathBuf;
 
-    use super::{TemplateInfo, TemplatePath};
+    use super::{GameManifest, TemplateError, TemplateInfo, TemplatePath};
 
     #[test]
     fn repl_template() {
@@ -295,7 +346,7 @@ mod tests {
         let p = TemplatePath::new("<root>/hmm/<storeUserId>".to_owned());
         let got = p
             .apply_substs(&TemplateInfo {
-                win_prefix: "".into(),
+                win_prefix: None,
                 win_user: "".to_owned(),
                 base_dir: None,
                 root: Some(PathBuf::from(root)),
@@ -304,6 +355,8 @@ mod tests {
                 xdg_config: None,
                 xdg_data: None,
                 install_dir: None,
+                steam_user_data: None,
+                custom_vars: Default::default(),
             })
             .unwrap();
         assert_eq!(expected, got);
@@ -317,7 +370,7 @@ mod tests {
         let p = TemplatePath::new("<base>/hmm".to_owned());
         let got = p
             .apply_substs(&TemplateInfo {
-                win_prefix: "".into(),
+                win_prefix: None,
                 win_user: "".to_owned(),
                 base_dir: None,
                 home_dir: None,
@@ -326,8 +379,222 @@ mod tests {
                 root: Some(root.to_owned().into()),
                 store_user_id: None,
                 install_dir: Some(install_dir.to_owned().into()),
+                steam_user_data: None,
+                custom_vars: Default::default(),
             })
             .unwrap();
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn win_vars_need_a_prefix() {
+        let p = TemplatePath::new("<winAppData>/hmm".to_owned());
+        let r = p.apply_substs(&TemplateInfo {
+            win_prefix: None,
+            win_user: "".to_owned(),
+            base_dir: None,
+            home_dir: None,
+            xdg_config: None,
+            xdg_data: None,
+            root: None,
+            store_user_id: None,
+            install_dir: None,
+            steam_user_data: None,
+            custom_vars: Default::default(),
+        });
+        assert!(matches!(r, Err(TemplateError::NoWinePrefix(_))));
+    }
+
+    #[test]
+    fn repl_win_dir() {
+        let p = TemplatePath::new("<winDir>/hmm".to_owned());
+        let got = p
+            .apply_substs(&TemplateInfo {
+                win_prefix: Some("drive_c".into()),
+                win_user: "".to_owned(),
+                base_dir: None,
+                home_dir: None,
+                xdg_config: None,
+                xdg_data: None,
+                root: None,
+                store_user_id: None,
+                install_dir: None,
+                steam_user_data: None,
+                custom_vars: Default::default(),
+            })
+            .unwrap();
+        assert_eq!(got, "drive_c/windows/hmm");
+    }
+
+    #[test]
+    fn repl_os_user_name_uses_wine_user() {
+        let p = TemplatePath::new("<home>/<osUserName>".to_owned());
+        let got = p
+            .apply_substs(&TemplateInfo {
+                win_prefix: Some("drive_c".into()),
+                win_user: "steamuser".to_owned(),
+                base_dir: None,
+                home_dir: Some("home".into()),
+                xdg_config: None,
+                xdg_data: None,
+                root: None,
+                store_user_id: None,
+                install_dir: None,
+                steam_user_data: None,
+                custom_vars: Default::default(),
+            })
+            .unwrap();
+        assert_eq!(got, "home/steamuser");
+    }
+
+    #[test]
+    fn repl_os_user_name_native() {
+        let p = TemplatePath::new("<osUserName>".to_owned());
+        temp_env::with_var("USER", Some("bingle"), || {
+            let got = p
+                .apply_substs(&TemplateInfo {
+                    win_prefix: None,
+                    win_user: "steamuser".to_owned(),
+                    base_dir: None,
+                    home_dir: None,
+                    xdg_config: None,
+                    xdg_data: None,
+                    root: None,
+                    store_user_id: None,
+                    install_dir: None,
+                    steam_user_data: None,
+                    custom_vars: Default::default(),
+                })
+                .unwrap();
+            assert_eq!(got, "bingle");
+        });
+    }
+
+    #[test]
+    fn repl_custom_var() {
+        let p = TemplatePath::new("<myCloud>/hmm".to_owned());
+        let got = p
+            .apply_substs(&TemplateInfo {
+                win_prefix: None,
+                win_user: "".to_owned(),
+                base_dir: None,
+                home_dir: None,
+                xdg_config: None,
+                xdg_data: None,
+                root: None,
+                store_user_id: None,
+                install_dir: None,
+                steam_user_data: None,
+                custom_vars: [("myCloud".to_owned(), "mnt/cloud".into())]
+                    .into_iter()
+                    .collect(),
+            })
+            .unwrap();
+        assert_eq!(got, "mnt/cloud/hmm");
+    }
+
+    #[test]
+    fn repl_steam_user_data() {
+        let p = TemplatePath::new("<steamUserData>/remote".to_owned());
+        let info = TemplateInfo {
+            win_prefix: None,
+            win_user: "".to_owned(),
+            base_dir: None,
+            home_dir: None,
+            xdg_config: None,
+            xdg_data: None,
+            root: None,
+            store_user_id: None,
+            install_dir: None,
+            steam_user_data: Some("steam/userdata/1/2".into()),
+            custom_vars: Default::default(),
+        };
+        assert_eq!(p.apply_substs(&info).unwrap(), "steam/userdata/1/2/remote");
+        assert!(matches!(
+            p.apply_substs(&TemplateInfo {
+                steam_user_data: None,
+                ..info
+            }),
+            Err(TemplateError::VariableNotAvailable("<steamUserData>"))
+        ));
+    }
+
+    fn info_with_vars(vars: &[(&str, PathBuf)]) -> TemplateInfo {
+        TemplateInfo {
+            win_prefix: None,
+            win_user: "".to_owned(),
+            base_dir: None,
+            home_dir: None,
+            xdg_config: None,
+            xdg_data: None,
+            root: None,
+            store_user_id: None,
+            install_dir: None,
+            steam_user_data: None,
+            custom_vars: vars
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), v.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn repl_after_text() {
+        let info = info_with_vars(&[("a", "x".into())]);
+        let p = TemplatePath::new("saves/<a>/slot".to_owned());
+        assert_eq!(p.apply_substs(&info).unwrap(), "saves/x/slot");
+    }
+
+    #[test]
+    fn unmatched_delims() {
+        let info = info_with_vars(&[("a", "x".into())]);
+        let stray_close = TemplatePath::new("odd>name/<a>".to_owned());
+        assert_eq!(stray_close.apply_substs(&info).unwrap(), "odd>name/x");
+        let unterminated = TemplatePath::new("<a>/<a".to_owned());
+        assert!(matches!(
+            unterminated.apply_substs(&info),
+            Err(TemplateError::NoClosingDelim)
+        ));
+    }
+
+    #[test]
+    fn repl_non_ascii() {
+        let info = info_with_vars(&[("a", "sauvegardés".into())]);
+        let p = TemplatePath::new("ゲーム>💾/<a>/データ".to_owned());
+        assert_eq!(
+            p.apply_substs(&info).unwrap(),
+            "ゲーム>💾/sauvegardés/データ"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn repl_non_utf8_is_an_error() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let info = info_with_vars(&[("a", OsStr::from_bytes(b"bad\xff").into())]);
+        let p = TemplatePath::new("<a>/saves".to_owned());
+        assert!(matches!(
+            p.apply_substs(&info),
+            Err(TemplateError::NonUtf8Path(var, _)) if var == "a"
+        ));
+    }
+
+    #[test]
+    fn empty_install_dir_has_no_name() {
+        let m: GameManifest = serde_yaml::from_str("installDir: {}").unwrap();
+        assert_eq!(m.install_dir.unwrap().name(), None);
+    }
+
+    #[test]
+    fn has_saves_checks_tags() {
+        let m: GameManifest = serde_yaml::from_str(
+            "files:\n  <base>/saves:\n    tags: [save]\n  <base>/cfg.ini:\n    tags: [config]",
+        )
+        .unwrap();
+        assert!(m.has_saves());
+        let m: GameManifest =
+            serde_yaml::from_str("files:\n  <base>/cfg.ini:\n    tags: [config]").unwrap();
+        assert!(!m.has_saves());
+    }
 }
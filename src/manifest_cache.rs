@@ -0,0 +1,252 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::SteamId,
+    curr_crate_ver,
+    manifest::{GameManifest, GameManifests},
+};
+
+/// Bump when the layout of the cache changes in a way the cinc version doesn't cover
+const CACHE_SCHEMA: u32 = 3;
+const CACHE_MAGIC: [u8; 4] = *b"cinc";
+
+/// Written at the start of the cache, a cache with a different header is never decoded
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct CacheHeader {
+    magic: [u8; 4],
+    schema: u32,
+    cinc_version: semver::Version,
+}
+
+impl CacheHeader {
+    fn current() -> Self {
+        Self {
+            magic: CACHE_MAGIC,
+            schema: CACHE_SCHEMA,
+            cinc_version: curr_crate_ver(),
+        }
+    }
+}
+
+/// The cache was written by another version of cinc and needs fetching again
+#[derive(thiserror::Error, Debug)]
+#[error("manifest cache was written by a different version of cinc ({written_by:?})")]
+pub struct StaleManifestCacheError {
+    /// None if the cache is from before the header was added
+    pub written_by: Option<semver::Version>,
+}
+
+/// What the server identified the cached manifest with, sent back when fetching it so the server
+/// can say it hasn't changed rather than sending all of it again
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ManifestVersion {
+    /// The `ETag` header
+    pub etag: Option<String>,
+    /// The `Last-Modified` header
+    pub last_modified: Option<String>,
+}
+
+/// Where a game is in the cache so it can be decoded without decoding the rest
+#[derive(Serialize, Deserialize, Debug)]
+struct IndexEntry {
+    name: String,
+    steam_id: Option<SteamId>,
+    /// Offset from the end of the index
+    offset: usize,
+    len: usize,
+}
+
+fn bincode_cfg() -> bincode::config::Configuration {
+    bincode::config::standard()
+}
+
+/// Write the manifest to the cache, each game is encoded separately after an index of them
+pub fn write(path: &Path, manifests: &GameManifests, version: &ManifestVersion) -> Result<()> {
+    let mut index = Vec::with_capacity(manifests.len());
+    let mut games = Vec::new();
+    for (name, game) in manifests {
+        let start = games.len();
+        bincode::serde::encode_into_std_write(game, &mut games, bincode_cfg())?;
+        index.push(IndexEntry {
+            name: name.clone(),
+            steam_id: game.steam.as_ref().map(|s| s.id),
+            offset: start,
+            len: games.len() - start,
+        });
+    }
+    let mut out = bincode::serde::encode_to_vec(CacheHeader::current(), bincode_cfg())?;
+    bincode::serde::encode_into_std_write(version, &mut out, bincode_cfg())?;
+    bincode::serde::encode_into_std_write(&index, &mut out, bincode_cfg())?;
+    out.extend_from_slice(&games);
+    fs::write(path, out)?;
+    Ok(())
+}
+
+/// Check the header and read the version of the manifest, returning it and what follows it
+fn read_header(data: &[u8]) -> Result<(ManifestVersion, &[u8])> {
+    let Ok((header, header_len)) =
+        bincode::serde::decode_from_slice::<CacheHeader, _>(data, bincode_cfg())
+    else {
+        Err(StaleManifestCacheError { written_by: None })?
+    };
+    if header != CacheHeader::current() {
+        Err(StaleManifestCacheError {
+            written_by: (header.magic == CACHE_MAGIC).then_some(header.cinc_version),
+        })?;
+    }
+    let data = &data[header_len..];
+    let (version, len) = bincode::serde::decode_from_slice(data, bincode_cfg())
+        .context("while decoding manifest cache version")?;
+    Ok((version, &data[len..]))
+}
+
+/// Check the header and read the index, returning it and the encoded games it points into
+fn read_index(data: &[u8]) -> Result<(Vec<IndexEntry>, &[u8])> {
+    let (_, data) = read_header(data)?;
+    let (index, len) = bincode::serde::decode_from_slice(data, bincode_cfg())
+        .context("while decoding manifest cache index")?;
+    Ok((index, &data[len..]))
+}
+
+/// Read what the server identified the cached manifest with
+pub fn read_version(path: &Path) -> Result<ManifestVersion> {
+    let data = fs::read(path)?;
+    Ok(read_header(&data)?.0)
+}
+
+fn decode_game(games: &[u8], entry: &IndexEntry) -> Result<GameManifest> {
+    let bytes = games
+        .get(entry.offset..entry.offset + entry.len)
+        .with_context(|| format!("manifest cache entry for {} is truncated", entry.name))?;
+    let (game, _) = bincode::serde::decode_from_slice(bytes, bincode_cfg())
+        .with_context(|| format!("while decoding manifest cache entry for {}", entry.name))?;
+    Ok(game)
+}
+
+/// Read every game in the cache
+pub fn read_all(path: &Path) -> Result<GameManifests> {
+    let data = fs::read(path)?;
+    let (index, games) = read_index(&data)?;
+    index
+        .iter()
+        .map(|e| Ok((e.name.clone(), decode_game(games, e)?)))
+        .collect()
+}
+
+/// Read only the game with a steam id, this is much faster than reading all of them
+pub fn read_by_steam_id(path: &Path, app_id: SteamId) -> Result<Option<(String, GameManifest)>> {
+    let data = fs::read(path)?;
+    let (index, games) = read_index(&data)?;
+    index
+        .iter()
+        .find(|e| e.steam_id == Some(app_id))
+        .map(|e| Ok((e.name.clone(), decode_game(games, e)?)))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_fs::TempDir;
+
+    use crate::{
+        config::SteamId,
+        manifest::{GameManifest, GameManifests, SteamInfo},
+    };
+
+    use super::{
+        CACHE_MAGIC, CACHE_SCHEMA, CacheHeader, ManifestVersion, StaleManifestCacheError,
+        bincode_cfg, read_all, read_by_steam_id, read_version, write,
+    };
+
+    fn manifests() -> GameManifests {
+        [
+            (
+                "A Game".to_owned(),
+                GameManifest {
+                    steam: Some(SteamInfo {
+                        id: SteamId::new(10),
+                    }),
+                    ..Default::default()
+                },
+            ),
+            ("Another Game".to_owned(), GameManifest::default()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn cache_roundtrips() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        write(&path, &manifests(), &ManifestVersion::default()).unwrap();
+
+        let mut names = read_all(&path).unwrap().into_keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["A Game", "Another Game"]);
+    }
+
+    #[test]
+    fn version_is_kept_with_the_cache() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        let version = ManifestVersion {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+        };
+        write(&path, &manifests(), &version).unwrap();
+
+        assert_eq!(read_version(&path).unwrap(), version);
+        assert_eq!(read_all(&path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn single_game_is_read_by_steam_id() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        write(&path, &manifests(), &ManifestVersion::default()).unwrap();
+
+        let (name, game) = read_by_steam_id(&path, SteamId::new(10)).unwrap().unwrap();
+        assert_eq!(name, "A Game");
+        assert_eq!(game.steam.unwrap().id, SteamId::new(10));
+        assert!(read_by_steam_id(&path, SteamId::new(11)).unwrap().is_none());
+    }
+
+    #[test]
+    fn cache_from_other_version_is_stale() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            schema: CACHE_SCHEMA,
+            cinc_version: semver::Version::new(0, 0, 1),
+        };
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(header, bincode_cfg()).unwrap(),
+        )
+        .unwrap();
+
+        let err = read_all(&path).unwrap_err();
+        let stale = err.downcast_ref::<StaleManifestCacheError>().unwrap();
+        assert_eq!(stale.written_by, Some(semver::Version::new(0, 0, 1)));
+    }
+
+    #[test]
+    fn cache_without_header_is_stale() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("manifest.bin");
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(manifests(), bincode_cfg()).unwrap(),
+        )
+        .unwrap();
+
+        let err = read_all(&path).unwrap_err();
+        assert!(err.downcast_ref::<StaleManifestCacheError>().is_some());
+    }
+}
@@ -1,37 +1,210 @@
-use std::path::{Path, PathBuf};
-use steamlocate::SteamDir;
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use steamlocate::{App, Library, SteamDir};
+use tracing::debug;
 
 /// Get the steam directory info
 ///
-/// # Panics
-/// If steam does not exist on the system or the environment is incorrectly set up
+/// Falls back to the flatpak install locations if steam cannot be located normally
 pub fn steam_dir() -> anyhow::Result<SteamDir> {
-    Ok(steamlocate::SteamDir::locate()?)
+    match steamlocate::SteamDir::locate() {
+        Ok(d) => Ok(d),
+        Err(e) => {
+            debug!("failed to locate steam ({e}), trying fallback locations");
+            fallback_steam_roots()
+                .iter()
+                .find_map(|p| SteamDir::from_dir(p).ok())
+                .ok_or_else(|| e.into())
+        }
+    }
+}
+
+/// Steam install directories which may be missed by [`SteamDir::locate`], e.g. for flatpak steam
+fn fallback_steam_roots() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+    let flatpak = home
+        .join(".var")
+        .join("app")
+        .join("com.valvesoftware.Steam");
+    vec![
+        flatpak.join(".steam").join("steam"),
+        flatpak.join(".steam").join("root"),
+        flatpak.join(".local").join("share").join("Steam"),
+        flatpak.join("data").join("Steam"),
+        home.join(".local").join("share").join("Steam"),
+        home.join(".steam").join("steam"),
+    ]
+    .into_iter()
+    .filter(|p| p.is_dir())
+    .collect()
+}
+
+/// Library roots which may not be listed by the steam install we found, e.g. the
+/// SD card on the steam deck
+fn fallback_steam_libraries() -> Vec<PathBuf> {
+    let mut libs = vec![PathBuf::from("/run/media/mmcblk0p1")];
+    if let Ok(rd) = std::fs::read_dir("/run/media/deck") {
+        libs.extend(rd.filter_map(|e| e.ok()).map(|e| e.path()));
+    }
+    libs.into_iter()
+        .filter(|p| p.join("steamapps").is_dir())
+        .collect()
+}
+
+/// Find an installed steam app, searching every steam install and library we know about
+pub fn find_steam_app(app_id: u32) -> anyhow::Result<Option<(App, Library)>> {
+    let located = steamlocate::SteamDir::locate();
+    let mut roots = fallback_steam_roots()
+        .iter()
+        .filter_map(|p| SteamDir::from_dir(p).ok())
+        .collect::<Vec<_>>();
+    let locate_err = match located {
+        Ok(d) => {
+            roots.insert(0, d);
+            None
+        }
+        Err(e) => {
+            debug!("failed to locate steam ({e}), only using fallback locations");
+            Some(e)
+        }
+    };
+    let mut libs = Vec::new();
+    for root in &roots {
+        match root.libraries() {
+            Ok(l) => libs.extend(l.filter_map(|l| {
+                l.inspect_err(|e| debug!("failed to read steam library: {e}"))
+                    .ok()
+            })),
+            Err(e) => debug!("failed to search steam dir {:?}: {e}", root.path()),
+        }
+    }
+    libs.extend(
+        fallback_steam_libraries()
+            .iter()
+            .filter_map(|l| Library::from_dir(l).ok()),
+    );
+    // the same library can be listed by multiple steam installs
+    let mut seen = std::collections::HashSet::new();
+    libs.retain(|l| seen.insert(l.path().to_owned()));
+
+    match find_app_in_libraries(libs, app_id) {
+        Some(r) => Ok(Some(r)),
+        None => match locate_err {
+            Some(e) if roots.is_empty() => Err(e.into()),
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Find the library an app is installed in
+///
+/// An app can have a manifest in more than one library, e.g. if it was moved and the old
+/// manifest was left behind, so prefer the library that actually has the install dir
+fn find_app_in_libraries(
+    libs: impl IntoIterator<Item = Library>,
+    app_id: u32,
+) -> Option<(App, Library)> {
+    let mut fallback = None;
+    for lib in libs {
+        match lib.app(app_id) {
+            Some(Ok(app)) => {
+                if lib.resolve_app_dir(&app).is_dir() {
+                    return Some((app, lib));
+                }
+                debug!(
+                    "{app_id} has a manifest in {:?} but isn't installed there",
+                    lib.path()
+                );
+                fallback.get_or_insert((app, lib));
+            }
+            Some(Err(e)) => debug!(
+                "failed to read the manifest for {app_id} in {:?}: {e}",
+                lib.path()
+            ),
+            None => {}
+        }
+    }
+    fallback
+}
+
+/// Where local saves are backed up to before a download overwrites them
+pub fn download_backup_dir() -> PathBuf {
+    data_dir().join("backups")
+}
+
+/// Where [`LastPlayed`](crate::last_played::LastPlayed) is kept
+pub fn last_played_file() -> PathBuf {
+    data_dir().join("last-played.ron")
 }
 
 pub fn log_dir() -> PathBuf {
     cache_dir().join("logs")
 }
 
+/// Prefix of the per-run log files in [`log_dir`]
+pub const LOG_FILE_PREFIX: &str = "general-";
+
+/// Delete the oldest log files in `dir` so that at most `keep` are left
+///
+/// Log files are named with a timestamp so the oldest ones sort first
+pub fn prune_log_files(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut logs = std::fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .filter(|p| {
+            p.as_ref().is_ok_and(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(LOG_FILE_PREFIX) && n.ends_with(".log"))
+            })
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    logs.sort();
+    let excess = logs.len().saturating_sub(keep);
+    for old in &logs[..excess] {
+        debug!("removing old log file {old:?}");
+        std::fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+static DATA_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Put all of cinc's directories under `root`, this must be called before any of them are used
+pub fn set_data_root(root: PathBuf) {
+    if DATA_ROOT.set(root).is_err() {
+        tracing::warn!("data root was set twice, ignoring the second one");
+    }
+}
+
 macro_rules! dir_override {
     ($name:ident : $fname:ident) => {
         #[cfg(not(debug_assertions))]
         pub fn $fname() -> PathBuf {
+            if let Some(root) = DATA_ROOT.get() {
+                return root.join(stringify!($name));
+            }
             dirs::$fname().map(|c| c.join("cinc")).unwrap_or_else(|| {
                 tracing::warn!(
                     "could not locate system {} directory, falling back to ~/.cinc/{}",
                     stringify!($name),
-                    stringify!(name)
+                    stringify!($name)
                 );
 
                 let home = std::env::home_dir().expect("could not locate home directory");
-                home.join(".cinc").join(stringify!(name))
+                home.join(".cinc").join(stringify!($name))
             })
         }
 
         #[cfg(debug_assertions)]
         pub fn $fname() -> PathBuf {
-            concat!("./cinc-data/", stringify!($name)).into()
+            match DATA_ROOT.get() {
+                Some(root) => root.join(stringify!($name)),
+                None => concat!("./cinc-data/", stringify!($name)).into(),
+            }
         }
     };
 }
@@ -41,16 +214,9 @@ dir_override!(cache : cache_dir);
 
 /// Extract the postfix of two paths
 ///
-/// # Panics
-/// If base is not a prefix of child
-pub fn extract_postfix<'p>(base: &'p Path, child: &'p Path) -> &'p Path {
-    assert!(
-        base.components()
-            .zip(child.components())
-            .all(|(a, b)| a == b),
-        "child is not prefix of base"
-    );
-    child.strip_prefix(base).unwrap()
+/// Returns None if base is not a prefix of child
+pub fn extract_postfix<'p>(base: &Path, child: &'p Path) -> Option<&'p Path> {
+    child.strip_prefix(base).ok()
 }
 
 /// Extract the common prefix of two paths
@@ -94,6 +260,44 @@ pub fn extract_prefix<'p>(base: &'p Path, child: &Path) -> &'p Path {
     }
 }
 
+/// Correct the case of each component of a path to match what is on the filesystem
+///
+/// Components are matched exactly first and then ignoring case. Once a component cannot be
+/// found the rest of the path is appended as is
+pub fn resolve_case_insensitive(p: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    let mut comps = p.components();
+    for c in comps.by_ref() {
+        let exact = out.join(c);
+        if exact.exists() {
+            out = exact;
+            continue;
+        }
+        let dir = if out.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            out.as_path()
+        };
+        let name = c.as_os_str().to_string_lossy().to_lowercase();
+        let found = std::fs::read_dir(dir).ok().and_then(|rd| {
+            rd.filter_map(|e| e.ok())
+                .find(|e| e.file_name().to_string_lossy().to_lowercase() == name)
+        });
+        match found {
+            Some(e) => {
+                debug!("resolved {c:?} to {:?} ignoring case", e.file_name());
+                out.push(e.file_name());
+            }
+            None => {
+                out.push(c);
+                break;
+            }
+        }
+    }
+    out.extend(comps);
+    out
+}
+
 pub trait PathExt {
     /// Join but without the overwriting that happens if other is an absolute path
     fn join_good(&self, other: impl Into<PathBuf>) -> PathBuf;
@@ -114,7 +318,17 @@ impl PathExt for Path {
 mod tests {
     use std::path::Path;
 
-    use crate::paths::{extract_postfix, extract_prefix};
+    use assert_fs::{
+        TempDir,
+        prelude::{FileTouch, FileWriteStr, PathChild, PathCreateDir},
+    };
+
+    use steamlocate::Library;
+
+    use crate::paths::{
+        extract_postfix, extract_prefix, find_app_in_libraries, prune_log_files,
+        resolve_case_insensitive,
+    };
 
     #[test]
     fn postfix_extract() {
@@ -122,14 +336,100 @@ mod tests {
         let child = base.join("yipee").join("yay");
         assert_eq!(
             extract_postfix(base, &child),
-            Path::new("yipee").join("yay")
+            Some(Path::new("yipee").join("yay").as_path())
         );
     }
 
+    #[test]
+    fn postfix_of_unrelated_path_is_none() {
+        let base = Path::new("saves").join("slot1");
+        let child = Path::new("saves").join("slot2").join("data.sav");
+        assert_eq!(extract_postfix(&base, &child), None);
+    }
+
     #[test]
     fn prefix_extract_unicode() {
         let child = Path::new("🥀");
         let base = Path::new("💀").join("😔").join("🥀");
         assert_eq!(extract_prefix(&base, child), Path::new("💀").join("😔"));
     }
+
+    #[test]
+    fn case_insensitive_resolves_existing_components() {
+        let root = TempDir::new().unwrap();
+        root.child("users")
+            .child("AppData")
+            .create_dir_all()
+            .unwrap();
+        let p = root.path().join("Users").join("appdata").join("Saves");
+        assert_eq!(
+            resolve_case_insensitive(&p),
+            root.path().join("users").join("AppData").join("Saves")
+        );
+    }
+
+    #[test]
+    fn prune_log_files_keeps_newest() {
+        let dir = TempDir::new().unwrap();
+        for name in [
+            "general-20250101-000000-1.log",
+            "general-20250102-000000-1.log",
+            "general-20250103-000000-1.log",
+            "other.log",
+        ] {
+            dir.child(name).touch().unwrap();
+        }
+        prune_log_files(dir.path(), 2).unwrap();
+        let mut left = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                "general-20250102-000000-1.log",
+                "general-20250103-000000-1.log",
+                "other.log"
+            ]
+        );
+    }
+
+    /// Make a library with a manifest for `app_id` installed to `Game`
+    fn mk_library(dir: &TempDir, name: &str, app_id: u32, installed: bool) -> Library {
+        let lib = dir.child(name);
+        lib.child("steamapps")
+            .child(format!("appmanifest_{app_id}.acf"))
+            .write_str(&format!(
+                "\"AppState\"\n{{\n\t\"appid\"\t\t\"{app_id}\"\n\t\"installdir\"\t\t\"Game\"\n}}\n"
+            ))
+            .unwrap();
+        if installed {
+            lib.child("steamapps")
+                .child("common")
+                .child("Game")
+                .create_dir_all()
+                .unwrap();
+        }
+        Library::from_dir(lib.path()).unwrap()
+    }
+
+    #[test]
+    fn app_is_found_in_the_library_it_is_installed_in() {
+        let dir = TempDir::new().unwrap();
+        let stale = mk_library(&dir, "stale", 10, false);
+        let real = mk_library(&dir, "real", 10, true);
+        let (app, lib) = find_app_in_libraries([stale.clone(), real], 10).unwrap();
+        assert_eq!(app.app_id, 10);
+        assert_eq!(lib.path(), dir.child("real").path());
+        assert_eq!(
+            lib.resolve_app_dir(&app),
+            dir.child("real").join("steamapps/common/Game")
+        );
+
+        // still found if it isn't installed anywhere, e.g. mid download
+        let (_, lib) = find_app_in_libraries([stale], 10).unwrap();
+        assert_eq!(lib.path(), dir.child("stale").path());
+        assert!(find_app_in_libraries([], 11).is_none());
+    }
 }
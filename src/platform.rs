@@ -1,27 +1,35 @@
 use std::{
     env,
     path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
     time::SystemTime,
 };
 
 use crate::{
-    args::{LaunchArgs, PlatformOpt},
-    backends::StorageBackend,
+    args::{LaunchArgs, PlatformOpt, is_bottles_cli},
+    backends::{self, StorageBackend, SyncLock, SyncMetadata},
     config::{Config, SteamId},
-    manifest::{self, GameManifest, GameManifests},
+    events::{SyncEvent, SyncObserver},
+    hooks::{HookEnv, run_hook, run_hook_or_warn},
+    manifest::{self, GameManifest, GameManifests, TemplateInfo},
     secrets::SecretsApi,
-    sync::SyncMgr,
+    sync::{self, PlannedTransfer, SyncMgr, SyncOptions},
     time,
-    ui::{self, SyncChoices},
+    ui::{self, SyncChoices, SyncIssueInfo, TimeDisplay},
 };
 use anyhow::Result;
-use anyhow::{anyhow, bail};
+use anyhow::{Context, anyhow, bail};
+use chrono::{DateTime, TimeDelta, Utc};
 use itertools::Itertools;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
 
+#[derive(Debug)]
 pub enum PlatformInfo {
     Steam { app_id: SteamId },
     Umu { exe_path: PathBuf },
+    Lutris { exe_path: PathBuf },
+    Bottles { bottle: String, exe_path: PathBuf },
+    Native { exe_path: PathBuf },
 }
 impl PlatformInfo {
     fn find_game_in_manifest<'a>(
@@ -43,10 +51,23 @@ impl PlatformInfo {
                         debug!(
                             "failed to discover game from env vars (reason: {reason}), falling back to executable name"
                         );
-                        find_likelist_umu_match(manifests, exe_path)
+                        find_likelist_exe_match(manifests, exe_path, true)
                     }
                 }
             }
+            PlatformInfo::Lutris { exe_path } => find_game_from_lutris_env(manifests)
+                .or_else(|| {
+                    debug!(
+                        "failed to discover game from lutris game name, falling back to executable name"
+                    );
+                    find_likelist_exe_match(manifests, exe_path, true)
+                }),
+            PlatformInfo::Bottles { exe_path, .. } => {
+                find_likelist_exe_match(manifests, exe_path, true)
+            }
+            PlatformInfo::Native { exe_path } => {
+                find_likelist_exe_match(manifests, exe_path, false)
+            }
         }
     }
 }
@@ -60,14 +81,12 @@ fn find_in_manifest_by_steam_id(
         .map(|(s, g)| (s.as_str(), g))
 }
 
-fn find_likelist_umu_match<'a>(
+fn find_likelist_exe_match<'a>(
     manifest: &'a GameManifests,
     exe_path: &Path,
+    wine: bool,
 ) -> Option<(&'a str, &'a GameManifest)> {
-    let platform = manifest::PlatformInfo {
-        store: None,
-        wine: true,
-    };
+    let platform = manifest::PlatformInfo { store: None, wine };
     let exe_comps = exe_path.components().rev().collect_vec();
     let mut max_len = 0;
     let mut max = None;
@@ -97,6 +116,49 @@ pub const HEROIC_APP_SOURCE: &str = "HEROIC_APP_SOURCE";
 /// Set to the app name for that store. For gog this seems to be the app id
 pub const HEROIC_APP_NAME: &str = "HEROIC_APP_NAME";
 
+/// Set to the app id of the game by steam and proton respectively
+pub const STEAM_APP_ID_VARS: [&str; 2] = ["SteamAppId", "STEAM_COMPAT_APP_ID"];
+
+/// Set by lutris for every game it launches
+pub const LUTRIS_GAME_UUID: &str = "LUTRIS_GAME_UUID";
+/// Set by lutris to the display name of the game
+pub const LUTRIS_GAME_NAME: &str = "GAME_NAME";
+
+/// Bottle name and program from a `bottles-cli run` command. The program is either the exe
+/// path given with `-e` or the name of a program added to the bottle given with `-p`
+fn parse_bottles_command(command: &[String]) -> Option<(String, PathBuf)> {
+    let cli = command.iter().position(|c| is_bottles_cli(c))?;
+    let run = cli + command[cli..].iter().position(|c| c == "run")?;
+    let mut args = command[run + 1..].iter();
+    let (mut bottle, mut program) = (None, None);
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) => (f, Some(v.to_owned())),
+            None => (arg.as_str(), None),
+        };
+        match flag {
+            "-b" | "--bottle" => bottle = inline.or_else(|| args.next().cloned()),
+            "-e" | "--executable" | "-p" | "--program" => {
+                program = inline.or_else(|| args.next().cloned()).map(PathBuf::from)
+            }
+            // the rest are arguments for the game
+            "--" => break,
+            _ => {}
+        }
+    }
+    Some((bottle?, program?))
+}
+
+/// Try and find the game by the name lutris gives it, this is usually the same as the manifest name
+fn find_game_from_lutris_env(manifest: &GameManifests) -> Option<(&str, &GameManifest)> {
+    let name = env::var(LUTRIS_GAME_NAME).ok()?;
+    debug!("found lutris game name {name}, attempting to match on it");
+    manifest
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(&name))
+        .map(|(s, m)| (s.as_str(), m))
+}
+
 /// Try and find the game match based on environment variables set by some launchers (e.g. heroic)
 fn find_game_from_env_vars(manifest: &GameManifests) -> Result<Option<(&str, &GameManifest)>> {
     // Heroic sets 2 environment variables that are of interest to us (https://github.com/Heroic-Games-Launcher/HeroicGamesLauncher/blob/a7feb36ad98c72be8fc58cd2976276a03910f9ee/src/backend/launcher.ts#L840)
@@ -112,15 +174,68 @@ fn find_game_from_env_vars(manifest: &GameManifests) -> Result<Option<(&str, &Ga
             .find(|(_, m)| m.gog.as_ref().map(|g| g.id == gog_id).unwrap_or(false))
             .map(|(s, m)| (s.as_str(), m)));
     }
+    if source == "epic" {
+        debug!("found epic source, attempting to match on app name {name}");
+        return Ok(find_in_manifest_by_epic_app(manifest, &name));
+    }
+    if source == "amazon" {
+        debug!("found amazon source, attempting to match on product id {name}");
+        return Ok(manifest
+            .iter()
+            .find(|(_, m)| m.amazon.as_ref().map(|a| a.id == name).unwrap_or(false))
+            .map(|(s, m)| (s.as_str(), m)));
+    }
     Ok(None)
 }
 
+/// Argument passed to epic games by legendary (and so heroic) with the epic app name
+const EPIC_APP_ARG: &str = "-epicapp=";
+
+/// Try and find the game from the arguments legendary passes to epic games
+fn find_game_from_epic_args<'a>(
+    manifest: &'a GameManifests,
+    command: &[String],
+) -> Option<(&'a str, &'a GameManifest)> {
+    let app_name = command.iter().find_map(|a| a.strip_prefix(EPIC_APP_ARG))?;
+    debug!("found epic app name {app_name} in the command, attempting to match on it");
+    find_in_manifest_by_epic_app(manifest, app_name)
+}
+
+fn find_in_manifest_by_epic_app<'a>(
+    manifest: &'a GameManifests,
+    app_name: &str,
+) -> Option<(&'a str, &'a GameManifest)> {
+    manifest
+        .iter()
+        .find(|(_, m)| m.epic.as_ref().map(|e| e.id == app_name).unwrap_or(false))
+        .map(|(s, m)| (s.as_str(), m))
+}
+
 pub struct LaunchInfo<'s, 'm> {
     platform: PlatformInfo,
     b: StorageBackend<'s>,
     bname: String,
     game: &'m GameManifest,
     game_name: &'m str,
+    sync_opts: SyncOptions,
+    verify_upload: bool,
+    /// Skip confirmations and go with the default
+    assume_yes: bool,
+    /// Show desktop notifications
+    notify: bool,
+    /// How to show times in dialogs
+    time_display: TimeDisplay,
+    /// Game is in the skip list so nothing should be synced
+    skip_sync: bool,
+    /// Upload the config files to their own archive after the saves
+    backup_config: bool,
+    observer: Arc<dyn SyncObserver>,
+    /// How old a lock on the remote has to be to be ignored, None if locking is disabled
+    lock_stale_after: Option<TimeDelta>,
+    /// When the remote was last written at the time of [`Self::sync_down`], None if it was empty
+    ///
+    /// Not set if sync_down didn't check the remote
+    seen_remote_write: OnceLock<Option<DateTime<Utc>>>,
 }
 
 impl<'s, 'm> LaunchInfo<'s, 'm> {
@@ -129,7 +244,11 @@ impl<'s, 'm> LaunchInfo<'s, 'm> {
         manifests: &'m GameManifests,
         secrets: &'s SecretsApi<'_>,
         largs @ LaunchArgs { command, .. }: &LaunchArgs,
+        assume_yes: bool,
     ) -> Result<Self> {
+        if let Some(name) = &cfg.device_name {
+            backends::set_device_name(name.clone());
+        }
         let Some(platform) = largs.resolve_platform() else {
             bail!(
                 "failed to resolve platform we are running on, try specifying it explicitly with --platform"
@@ -139,18 +258,15 @@ impl<'s, 'm> LaunchInfo<'s, 'm> {
 
         let platform = match platform {
             PlatformOpt::Steam => {
-                let app_id = command
-                    .iter()
-                    .find(|e| e.starts_with("AppId="))
-                    .map(|s| {
-                        s.split_once("=")
-                            .expect("invalid AppId field, has the steam arg format changed?")
-                            .1
-                            .parse::<u32>()
-                            .map(SteamId::new)
-                            .expect("failed to parse app id")
-                    })
-                    .expect("couldn't find steam id");
+                let app_id = largs
+                    .launched_steam_app_id()
+                    .context("invalid steam app id")?
+                    .or(manifest_steam_id)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "couldn't find the steam app id, launch cinc from steam with `cinc launch -- %command%` or pass --steam-app-id"
+                        )
+                    })?;
 
                 PlatformInfo::Steam { app_id }
             }
@@ -163,25 +279,73 @@ impl<'s, 'm> LaunchInfo<'s, 'm> {
                     exe_path: exe_path.into(),
                 }
             }
+            PlatformOpt::Lutris => {
+                // lutris runs wine/umu with the exe as the argument, but native games are run directly
+                let exe_path = command
+                    .get(1)
+                    .or(command.first())
+                    .ok_or_else(|| anyhow!("expected a command to invoke for lutris"))?
+                    .to_owned();
+                PlatformInfo::Lutris {
+                    exe_path: exe_path.into(),
+                }
+            }
+            PlatformOpt::Bottles => {
+                let (bottle, exe_path) = parse_bottles_command(command).ok_or_else(|| {
+                    anyhow!("expected a `bottles-cli run` command with --bottle and --executable or --program")
+                })?;
+                PlatformInfo::Bottles { bottle, exe_path }
+            }
+            PlatformOpt::Native => {
+                let exe_path = command
+                    .first()
+                    .ok_or_else(|| anyhow!("expected a command to invoke"))?
+                    .to_owned();
+                PlatformInfo::Native {
+                    exe_path: exe_path.into(),
+                }
+            }
             PlatformOpt::Auto => unreachable!(),
         };
         time! {
         "finding the game manifest":
         {
-        let (game_name, game) = manifest_steam_id.and_then(|id|{
-            debug!("using supplied steam id to find game in the manifest");
-            find_in_manifest_by_steam_id(manifests, id)
-        }).or_else(||  platform.find_game_in_manifest(manifests)).ok_or_else(|| anyhow!("failed to find game in manifest"))?;
+        let (game_name, game) = if let Some(name) = &largs.manifest_game_override {
+            debug!("using supplied name to find game in the manifest");
+            manifests
+                .get_key_value(name)
+                .map(|(n, g)| (n.as_str(), g))
+                .ok_or_else(|| anyhow!("'{name}' is not in the manifest, try `cinc games search`"))?
+        } else {
+            manifest_steam_id.and_then(|id|{
+                debug!("using supplied steam id to find game in the manifest");
+                find_in_manifest_by_steam_id(manifests, id)
+            }).or_else(|| find_game_from_epic_args(manifests, command))
+              .or_else(||  platform.find_game_in_manifest(manifests)).ok_or_else(|| anyhow!("failed to find game in manifest"))?
+        };
         }}
 
         debug!("found game manifest for {game_name}\n{game:#?}");
+        let mut sync_opts = SyncOptions::from_config(cfg);
+        if largs.allow_large_upload {
+            sync_opts.max_upload_size = None;
+        }
+        let skip_sync = if largs.offline {
+            info!("offline, {game_name} will not be synced");
+            true
+        } else if cfg.skip_games.iter().any(|g| g == game_name) {
+            debug!("{game_name} is in skip_games, it will not be synced");
+            true
+        } else {
+            false
+        };
 
         let (bname, b) = cfg
             .backends
             .iter()
             .find(|b| b.name == cfg.default_backend)
             .map(|b| {
-                b.to_backend(game_name, secrets)
+                b.to_backend(game_name, secrets, cfg.network_timeout())
                     .map(|bk| (b.name.clone(), bk))
             })
             .ok_or_else(|| anyhow!("no backends or default backend is invalid"))??;
@@ -191,55 +355,453 @@ impl<'s, 'm> LaunchInfo<'s, 'm> {
             bname,
             game,
             game_name,
+            sync_opts,
+            verify_upload: largs.verify_upload,
+            assume_yes,
+            notify: cfg.notifications,
+            time_display: TimeDisplay::from_config(cfg),
+            skip_sync,
+            backup_config: cfg.backup_config_files,
+            observer: Arc::new(()),
+            lock_stale_after: cfg
+                .sync_lock
+                .then(|| TimeDelta::seconds(cfg.sync_lock_stale_secs as i64)),
+            seen_remote_write: OnceLock::new(),
         })
     }
 
-    fn mk_sync_mgr(&self) -> Result<SyncMgr> {
+    /// Send events about the syncs to `observer` rather than ignoring them, it can also answer
+    /// conflicts instead of the dialog
+    pub fn with_observer(mut self, observer: Arc<dyn SyncObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Ask what to do about a conflict, --yes keeps the local files
+    fn resolve_conflict(&self, issue: SyncIssueInfo) -> Result<SyncChoices> {
+        self.observer
+            .event(SyncEvent::ConflictDetected(issue.clone()));
+        if self.assume_yes {
+            info!("not asking due to --yes, keeping the local files");
+            Ok(SyncChoices::Continue)
+        } else if let Some(choice) = self.observer.resolve_conflict(&issue) {
+            debug!("observer chose {choice:?}");
+            Ok(choice)
+        } else {
+            ui::spawn_sync_confirm(issue, &self.time_display)
+        }
+    }
+
+    fn mk_sync_mgr(&self) -> Result<SyncMgr<'_>> {
+        self.mk_sync_mgr_with(&self.sync_opts)
+    }
+
+    fn mk_sync_mgr_with(&self, opts: &SyncOptions) -> Result<SyncMgr<'_>> {
         let r = match &self.platform {
             PlatformInfo::Steam { app_id, .. } => {
-                SyncMgr::from_steam_game(self.game_name, self.game, *app_id, &self.bname)
+                SyncMgr::from_steam_game(self.game_name, self.game, *app_id, &self.bname, opts)
+            }
+            PlatformInfo::Umu { exe_path } => {
+                SyncMgr::from_umu_env(self.game_name, self.game, exe_path, &self.bname, opts)
             }
-            PlatformInfo::Umu { .. } => {
-                SyncMgr::from_umu_env(self.game_name, self.game, &self.bname)
+            PlatformInfo::Lutris { .. } => {
+                SyncMgr::from_lutris_env(self.game_name, self.game, &self.bname, opts)
+            }
+            PlatformInfo::Bottles { bottle, exe_path } => SyncMgr::from_bottles_env(
+                self.game_name,
+                self.game,
+                bottle,
+                exe_path,
+                &self.bname,
+                opts,
+            ),
+            PlatformInfo::Native { .. } => {
+                SyncMgr::from_native(self.game_name, self.game, &self.bname, opts)
             }
         };
         if let Err(e) = r.as_ref() {
             error!("failed to get information about game: {e}");
         }
-        r
+        r.map(|m| m.with_observer(self.observer.clone()))
+    }
+
+    /// Upload the config files, see [`SyncOptions::config_files`]
+    async fn upload_config(&self) -> Result<()> {
+        let opts = SyncOptions {
+            config_files: true,
+            ..self.sync_opts.clone()
+        };
+        self.mk_sync_mgr_with(&opts)?.upload_config(&self.b).await
     }
 
     pub async fn sync_down(&self) -> Result<()> {
+        if self.skip_sync {
+            return Ok(());
+        }
         let info = self.mk_sync_mgr()?;
+        self.acquire_lock().await?;
 
         time! {
             "cloud sync down": {
-            cloud_sync_down(&self.b, info).await?;
+            self.cloud_sync_down(info).await?;
             }
         }
         Ok(())
     }
 
-    pub async fn sync_up(&self) -> Result<()> {
-        if let Some(metadata) = self.b.read_sync_time().await? {
-            if !metadata.is_version_write_compatabible() {
-                Err(IncomaptibleCincVersionError {
-                    server_version: metadata.last_write_cinc_version.clone(),
-                    read: false,
-                })?;
+    /// Lock on the remote held by another device, None if there isn't one or it is stale
+    async fn foreign_lock(&self) -> Result<Option<SyncLock>> {
+        let Some(stale_after) = self.lock_stale_after else {
+            return Ok(None);
+        };
+        let Some(lock) = self.b.read_lock().await? else {
+            return Ok(None);
+        };
+        if lock.is_ours() {
+            debug!("replacing our own lock from {}", lock.timestamp);
+            Ok(None)
+        } else if Utc::now() - lock.timestamp > stale_after {
+            info!(
+                "ignoring stale lock taken by {} at {}",
+                lock.hostname, lock.timestamp
+            );
+            Ok(None)
+        } else {
+            Ok(Some(lock))
+        }
+    }
+
+    /// Lock the remote for this device, asking the user first if another device has it locked
+    async fn acquire_lock(&self) -> Result<()> {
+        if self.lock_stale_after.is_none() {
+            return Ok(());
+        }
+        if let Some(lock) = self.foreign_lock().await? {
+            warn!(
+                "{} has had the remote locked since {}, showing confirmation box to the user...",
+                lock.hostname, lock.timestamp
+            );
+            let take_over = if self.assume_yes {
+                info!("not asking due to --yes, taking over the lock");
+                true
+            } else {
+                ui::spawn_lock_confirm(&lock, &self.time_display)?
+            };
+            if !take_over {
+                Err(SyncConflictAbortedError)?;
+            }
+        }
+        self.b.write_lock(&SyncLock::now()).await?;
+        Ok(())
+    }
+
+    /// Remove this device's lock from the remote, call once the game has closed
+    ///
+    /// A lock another device has taken since is left alone
+    pub async fn release_lock(&self) -> Result<()> {
+        if self.skip_sync || self.lock_stale_after.is_none() {
+            return Ok(());
+        }
+        match self.b.read_lock().await? {
+            Some(lock) if lock.is_ours() => self.b.remove_lock().await?,
+            Some(lock) => warn!("not removing the lock as {} has taken it", lock.hostname),
+            None => {}
+        }
+        Ok(())
+    }
+
+    async fn cloud_sync_down(&self, info: SyncMgr<'_>) -> Result<()> {
+        let b = &self.b;
+        let metadata = b.read_sync_time().await?;
+        let _ = self
+            .seen_remote_write
+            .set(metadata.as_ref().map(|m| m.last_write_timestamp));
+        let Some(metadata) = metadata else {
+            debug!("server has no metadata, we don't have to do anything");
+            return Ok(());
+        };
+        IncomaptibleCincVersionError::check_read(&metadata)?;
+        if let Some(sync_info) = info.are_local_files_newer(&metadata).await? {
+            warn!("found local files newer than local, showing confirmation box to the user...");
+            if self.notify {
+                ui::notify(
+                    &format!("Save conflict for {}", self.game_name),
+                    &format!(
+                        "local saves are newer than the ones on '{}' last written by {}",
+                        self.bname, metadata.last_write_hostname
+                    ),
+                );
+            }
+
+            let choice = self.resolve_conflict(sync_info)?;
+            match choice {
+                SyncChoices::Download => {
+                    info.download(b, true, &metadata).await?;
+                }
+                // nothing was downloaded so don't notify
+                SyncChoices::Continue => return Ok(()),
+                SyncChoices::Exit => Err(SyncConflictAbortedError)?,
+            }
+        } else {
+            info.download(b, false, &metadata).await?;
+        }
+        if self.notify {
+            ui::notify(
+                &format!("Downloaded saves for {}", self.game_name),
+                &format!(
+                    "from '{}', last written by {}",
+                    self.bname, metadata.last_write_hostname
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether something else uploaded after [`Self::sync_down`] looked at the remote
+    fn remote_written_since_sync_down(&self, current: &SyncMetadata) -> bool {
+        self.seen_remote_write
+            .get()
+            .is_some_and(|seen| *seen != Some(current.last_write_timestamp))
+    }
+
+    /// Name of the game in the manifest
+    pub fn game_name(&self) -> &str {
+        self.game_name
+    }
+
+    /// Name of the backend being synced with
+    pub fn backend_name(&self) -> &str {
+        &self.bname
+    }
+
+    /// The platform the game is being launched on
+    pub fn platform(&self) -> &PlatformInfo {
+        &self.platform
+    }
+
+    /// The game's entry in the manifest
+    pub fn manifest(&self) -> &GameManifest {
+        self.game
+    }
+
+    pub fn sync_options(&self) -> &SyncOptions {
+        &self.sync_opts
+    }
+
+    /// What the manifest's templates are filled in with for this launch
+    pub fn template_info(&self) -> Result<TemplateInfo> {
+        let (name, game, opts) = (self.game_name, self.game, &self.sync_opts);
+        match &self.platform {
+            PlatformInfo::Steam { app_id } => sync::steam_template_info(name, game, *app_id, opts),
+            PlatformInfo::Umu { exe_path } => sync::umu_template_info(name, game, exe_path, opts),
+            PlatformInfo::Lutris { .. } => sync::lutris_template_info(name, game, opts),
+            PlatformInfo::Bottles { bottle, exe_path } => {
+                sync::bottles_template_info(name, game, bottle, exe_path, opts)
             }
+            PlatformInfo::Native { .. } => Ok(sync::native_template_info(name, game, opts)),
         }
+    }
+
+    /// What [`Self::sync_down`] would do, without writing anything
+    ///
+    /// The remote is read the same way so locks and conflicts are found, but nothing is asked
+    pub async fn plan_sync_down(&self) -> Result<SyncDownPlan> {
+        if self.skip_sync {
+            return Ok(SyncDownPlan::default());
+        }
+        let locked_by = self.foreign_lock().await?;
+        let Some(metadata) = self.b.read_sync_time().await? else {
+            return Ok(SyncDownPlan {
+                locked_by,
+                ..Default::default()
+            });
+        };
         let info = self.mk_sync_mgr()?;
+        let conflict = info.are_local_files_newer(&metadata).await?;
+        // --yes keeps the local files, see resolve_conflict
+        let transfers = if conflict.is_some() && self.assume_yes {
+            Vec::new()
+        } else {
+            info.download_plan(&metadata).await?
+        };
+        Ok(SyncDownPlan {
+            locked_by,
+            conflict,
+            transfers,
+        })
+    }
+
+    /// What [`Self::sync_up`] would do, without writing anything
+    pub fn plan_sync_up(&self) -> Result<Vec<PlannedTransfer>> {
+        if self.skip_sync {
+            return Ok(Vec::new());
+        }
+        self.mk_sync_mgr()?.upload_plan()
+    }
+
+    pub async fn sync_up(&self) -> Result<()> {
+        if self.skip_sync {
+            return Ok(());
+        }
+        let metadata = self.b.read_sync_time().await?;
+        if let Some(metadata) = &metadata {
+            IncomaptibleCincVersionError::check_write(metadata)?;
+        }
+        let info = self.mk_sync_mgr()?;
+        if let Some(metadata) = metadata.filter(|m| self.remote_written_since_sync_down(m)) {
+            warn!(
+                "{} wrote to the remote while the game was running, showing confirmation box to the user...",
+                metadata.last_write_hostname
+            );
+            if self.notify {
+                ui::notify(
+                    &format!("Save conflict for {}", self.game_name),
+                    &format!(
+                        "{} uploaded saves to '{}' while the game was running",
+                        metadata.last_write_hostname, self.bname
+                    ),
+                );
+            }
+            let choice = self.resolve_conflict(info.sync_issue(&metadata)?)?;
+            match choice {
+                SyncChoices::Continue => {}
+                SyncChoices::Download => {
+                    info.download(&self.b, true, &metadata).await?;
+                    return Ok(());
+                }
+                SyncChoices::Exit => {
+                    info!("not uploading as the remote was written to by another device");
+                    Err(SyncConflictAbortedError)?;
+                }
+            }
+        }
 
         time! {
             "cloud sync up": {
-                info.upload(&self.b).await?;
+                info.upload(&self.b, self.verify_upload).await?;
+            }
+        }
+        let skipped = info.skipped_files();
+        if !skipped.is_empty() {
+            warn!(
+                "{} file(s) couldn't be read and were left out of the upload, other devices will not get them: {}",
+                skipped.len(),
+                skipped.iter().map(|p| p.display()).join(", ")
+            );
+        }
+        if self.backup_config {
+            // the saves are what matter, so this failing isn't an error
+            if let Err(e) = self.upload_config().await {
+                warn!("failed to upload config files: {e:?}");
             }
         }
+        if self.notify {
+            let mut body = format!("to '{}'", self.bname);
+            if !skipped.is_empty() {
+                body += &format!(
+                    ", {} file(s) couldn't be read and were left out",
+                    skipped.len()
+                );
+            }
+            ui::notify(&format!("Uploaded saves for {}", self.game_name), &body);
+        }
         Ok(())
     }
 }
 
+/// Sync a game's saves around `run`, which should start the game and return once it has exited
+///
+/// This is what `cinc launch` does minus running the command, for embedding cinc in a launcher.
+/// The saves are downloaded and the `post_download` and `pre_launch` hooks run before `run`, then
+/// unless `--no-upload` was given they are uploaded after it. The lock on the backend is released
+/// even if `run` fails
+pub async fn run_sync<T>(
+    cfg: &Config,
+    manifests: &GameManifests,
+    secrets: &SecretsApi<'_>,
+    largs: &LaunchArgs,
+    assume_yes: bool,
+    run: impl AsyncFnOnce(&LaunchInfo<'_, '_>) -> Result<T>,
+) -> Result<T> {
+    run_sync_with_observer(
+        cfg,
+        manifests,
+        secrets,
+        largs,
+        assume_yes,
+        Arc::new(()),
+        run,
+    )
+    .await
+}
+
+/// [`run_sync`] telling `observer` how the syncs are going
+pub async fn run_sync_with_observer<T>(
+    cfg: &Config,
+    manifests: &GameManifests,
+    secrets: &SecretsApi<'_>,
+    largs: &LaunchArgs,
+    assume_yes: bool,
+    observer: Arc<dyn SyncObserver>,
+    run: impl AsyncFnOnce(&LaunchInfo<'_, '_>) -> Result<T>,
+) -> Result<T> {
+    let launch =
+        LaunchInfo::new(cfg, manifests, secrets, largs, assume_yes)?.with_observer(observer);
+    let hook_env = HookEnv {
+        game_name: launch.game_name(),
+        backend: launch.backend_name(),
+    };
+    launch.sync_down().await?;
+    // the lock is held from here so it must be released however the rest goes
+    let res = async {
+        if let Some(hook) = &cfg.hooks.post_download {
+            run_hook_or_warn("post_download", hook, &hook_env);
+        }
+        if let Some(hook) = &cfg.hooks.pre_launch {
+            run_hook("pre_launch", hook, &hook_env)
+                .context("aborting launch as the pre_launch hook failed")?;
+        }
+
+        let r = run(&launch).await?;
+
+        if largs.no_upload {
+            info!("not uploading as --no-upload was passed");
+        } else {
+            launch.sync_up().await?;
+            if let Some(hook) = &cfg.hooks.post_upload {
+                run_hook_or_warn("post_upload", hook, &hook_env);
+            }
+        }
+        Ok(r)
+    }
+    .await;
+    match (res, launch.release_lock().await) {
+        (Err(e), Err(lock_err)) => {
+            warn!("failed to release the sync lock: {lock_err}");
+            Err(e)
+        }
+        (res, Ok(())) => res,
+        (Ok(_), Err(lock_err)) => Err(lock_err),
+    }
+}
+
+/// What [`LaunchInfo::sync_down`] would do, see [`LaunchInfo::plan_sync_down`]
+#[derive(Debug, Default)]
+pub struct SyncDownPlan {
+    /// Another device has the remote locked, the user would be asked whether to take it over
+    pub locked_by: Option<SyncLock>,
+    /// The local saves are newer than the remote ones, unless `--yes` was given the user would be
+    /// asked what to do and `transfers` only happen if they choose to download
+    pub conflict: Option<SyncIssueInfo>,
+    /// Files which would be downloaded
+    pub transfers: Vec<PlannedTransfer>,
+}
+
+/// The user chose to exit when asked about a sync conflict
+#[derive(thiserror::Error, Debug)]
+#[error("sync aborted due to a conflict with the remote")]
+pub struct SyncConflictAbortedError;
+
 #[derive(thiserror::Error, Debug)]
 #[error("incompatible cinc version on the server {server_version}")]
 pub struct IncomaptibleCincVersionError {
@@ -248,33 +810,30 @@ pub struct IncomaptibleCincVersionError {
     pub read: bool,
 }
 
-async fn cloud_sync_down(b: &StorageBackend<'_>, info: SyncMgr<'_>) -> Result<()> {
-    let Some(metadata) = b.read_sync_time().await? else {
-        debug!("server has no metadata, we don't have to do anything");
-        return Ok(());
-    };
-    if !metadata.is_version_read_compatabible() {
-        Err(IncomaptibleCincVersionError {
-            server_version: metadata.last_write_cinc_version.clone(),
-            read: true,
-        })?;
-    }
-    if let Some(sync_info) = info.are_local_files_newer(&metadata).await? {
-        warn!("found local files newer than local, showing confirmation box to the user...");
-
-        match ui::spawn_sync_confirm(sync_info)? {
-            SyncChoices::Download => {
-                info.download(b, true, &metadata).await?;
-            }
-            SyncChoices::Continue => {}
-            SyncChoices::Exit => {
-                return Ok(());
-            }
+impl IncomaptibleCincVersionError {
+    /// Error if the remote was written by a version of cinc we can't read
+    pub fn check_read(metadata: &SyncMetadata) -> Result<(), Self> {
+        if metadata.is_version_read_compatabible() {
+            Ok(())
+        } else {
+            Err(Self {
+                server_version: metadata.last_write_cinc_version.clone(),
+                read: true,
+            })
+        }
+    }
+
+    /// Error if the remote was written by a version of cinc we can't overwrite
+    pub fn check_write(metadata: &SyncMetadata) -> Result<(), Self> {
+        if metadata.is_version_write_compatabible() {
+            Ok(())
+        } else {
+            Err(Self {
+                server_version: metadata.last_write_cinc_version.clone(),
+                read: false,
+            })
         }
-    } else {
-        info.download(b, false, &metadata).await?;
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -284,23 +843,30 @@ mod tests {
         path::{Path, PathBuf},
     };
 
+    use chrono::{TimeDelta, Utc};
+
     use crate::{
         args::{LaunchArgs, PlatformOpt},
-        config::{BackendInfo, BackendTy, Config, SteamId},
+        backends::{FileMetaTable, LOCK_FILE, SYNC_TIME_FILE, SyncLock, SyncMetadata},
+        config::{BackendInfo, BackendTy, CONFIG_VERSION, Config, HooksConfig, SteamId},
         manifest::{
-            FileConfig, FileTag, GameManifest, GameManifests, GogInfo, SteamInfo, TemplatePath,
+            AmazonInfo, EpicInfo, FileConfig, FileTag, GameManifest, GameManifests, GogInfo,
+            SteamInfo, TemplatePath,
         },
         paths::PathExt,
-        platform::{HEROIC_APP_NAME, HEROIC_APP_SOURCE, find_game_from_env_vars},
+        platform::{
+            HEROIC_APP_NAME, HEROIC_APP_SOURCE, LUTRIS_GAME_NAME, find_game_from_env_vars,
+            find_game_from_epic_args, find_game_from_lutris_env, parse_bottles_command, run_sync,
+        },
         secrets::SecretsApi,
         sync::ARCHIVE_NAME,
     };
     use assert_fs::{
         TempDir,
         fixture::ChildPath,
-        prelude::{PathChild, PathCreateDir},
+        prelude::{FileWriteStr, PathChild, PathCreateDir},
     };
-    use temp_env::async_with_vars;
+    use temp_env::{async_with_vars, with_vars};
     use test_log::test;
 
     use super::LaunchInfo;
@@ -321,7 +887,7 @@ mod tests {
 
         let cfg = test_cfg(local_path.to_path_buf());
         let secrets = SecretsApi::new_unavailable();
-        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs).unwrap();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
 
         launch.sync_down().await.unwrap();
         assert!(!std::fs::exists(&archive_p).unwrap());
@@ -356,21 +922,25 @@ mod tests {
         let wine_prefix = root.child("wineprefix");
         wine_prefix.create_dir_all().unwrap();
         async_with_vars(
-            [("WINEPREFIX", Some(wine_prefix.to_str().unwrap()))],
+            [
+                ("WINEPREFIX", Some(wine_prefix.to_str().unwrap())),
+                // we can only resolve <root> (and so <base>) when under heroic
+                (HEROIC_APP_NAME, Some("1")),
+            ],
             async {
                 run_sync_test(
                     &root,
                     &file_path,
                     &LaunchArgs {
-                        platform: PlatformOpt::Auto,
-                        no_upload: false,
-                        no_download: false,
-                        manifest_app_id_override: None,
-                        command: vec!["/usr/bin/umu-run".to_owned(), launch_exe.to_owned()],
+                        verify_upload: true,
+                        ..test_largs(PlatformOpt::Auto, &["/usr/bin/umu-run", launch_exe])
                     },
                     GameManifest {
                         steam: None,
                         gog: None,
+                        epic: None,
+                        amazon: None,
+                        registry: Default::default(),
                         install_dir: None,
                         files: [(
                             TemplatePath::new(
@@ -396,10 +966,49 @@ mod tests {
         )
         .await;
     }
+    fn test_largs(platform: PlatformOpt, command: &[&str]) -> LaunchArgs {
+        LaunchArgs {
+            platform,
+            no_upload: false,
+            no_download: false,
+            confirm_upload_only: false,
+            verify_upload: false,
+            allow_large_upload: false,
+            manifest_app_id_override: None,
+            manifest_game_override: None,
+            list_templates: false,
+            offline: false,
+            command: command.iter().map(|c| (*c).to_owned()).collect(),
+        }
+    }
+
     fn test_cfg(root: PathBuf) -> Config {
         Config {
+            version: CONFIG_VERSION,
             default_backend: "t".to_owned(),
             manifest_url: None,
+            network_timeout_secs: 30,
+            template_vars: Default::default(),
+            case_insensitive_paths: false,
+            follow_symlinks: false,
+            hooks: Default::default(),
+            notifications: false,
+            skip_games: Vec::new(),
+            incremental_uploads: false,
+            max_upload_size_mb: 0,
+            conflict_skew_secs: 0,
+            max_download_backups: 0,
+            max_concurrent_requests: 4,
+            compression_threads: 0,
+            skip_unreadable_files: false,
+            backup_config_files: false,
+            sync_lock: false,
+            sync_lock_stale_secs: 60,
+            device_name: None,
+            allow_plaintext_secrets: false,
+            dialog_timeout_secs: 0,
+            dialog_time_format: "%c".to_owned(),
+            dialog_time_zone: None,
             backends: vec![BackendInfo {
                 name: "t".to_owned(),
                 info: BackendTy::Filesystem { root },
@@ -425,35 +1034,482 @@ mod tests {
                 ..Default::default()
             };
         let largs = &LaunchArgs {
-            platform: PlatformOpt::Auto,
-            no_upload: false,
-            no_download: false,
             manifest_app_id_override: Some(id),
-            command: vec!["/usr/bin/umu-run".to_owned(), launch_exe.to_owned()],
+            ..test_largs(PlatformOpt::Auto, &["/usr/bin/umu-run", launch_exe])
         };
         let manifest = mk_manifest(game);
         let cfg = test_cfg(root.to_path_buf());
         let secrets = SecretsApi::new_unavailable();
-        LaunchInfo::new(&cfg, &manifest, &secrets, largs).unwrap();
+        LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
     }
+    #[test(tokio::test)]
+    async fn forced_game_name_overrides_discovery() {
+        let root = TempDir::new().unwrap();
+        let mut manifest = mk_manifest(GameManifest::default());
+        manifest.insert(
+            "other".to_owned(),
+            GameManifest {
+                steam: Some(SteamInfo {
+                    id: SteamId::new(5),
+                }),
+                ..Default::default()
+            },
+        );
+        let mut largs = LaunchArgs {
+            manifest_game_override: Some("test".to_owned()),
+            ..test_largs(PlatformOpt::Steam, &["AppId=5"])
+        };
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, &largs, false).unwrap();
+        assert_eq!(launch.game_name, "test");
+
+        largs.manifest_game_override = Some("missing".to_owned());
+        assert!(LaunchInfo::new(&cfg, &manifest, &secrets, &largs, false).is_err());
+    }
+
+    #[test(tokio::test)]
+    async fn template_info_is_for_the_launch_platform() {
+        let root = TempDir::new().unwrap();
+        let wine_prefix = root.child("wineprefix");
+        let manifest = mk_manifest(GameManifest::default());
+        let largs = &LaunchArgs {
+            manifest_game_override: Some("test".to_owned()),
+            list_templates: true,
+            ..test_largs(PlatformOpt::Auto, &["/usr/bin/umu-run", "run.exe"])
+        };
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let info = async_with_vars(
+            [("WINEPREFIX", Some(wine_prefix.to_str().unwrap()))],
+            async {
+                LaunchInfo::new(&cfg, &manifest, &secrets, largs, false)
+                    .unwrap()
+                    .template_info()
+                    .unwrap()
+            },
+        )
+        .await;
+        assert_eq!(
+            info.win_prefix,
+            Some(wine_prefix.join("pfx").join("drive_c"))
+        );
+    }
+
+    #[test]
+    fn steam_app_id_falls_back_to_the_environment() {
+        let largs = test_largs(PlatformOpt::Auto, &["./start.sh"]);
+        with_vars(
+            [("SteamAppId", None), ("STEAM_COMPAT_APP_ID", Some("5"))],
+            || {
+                assert_eq!(largs.resolve_platform(), Some(PlatformOpt::Steam));
+                assert_eq!(largs.steam_app_id(), Some(SteamId::new(5)));
+            },
+        );
+        // non-steam games launched by steam
+        with_vars(
+            [
+                ("SteamAppId", Some("0")),
+                ("STEAM_COMPAT_APP_ID", Some("0")),
+            ],
+            || assert_eq!(largs.launched_steam_app_id().unwrap(), None),
+        );
+    }
+
+    #[test]
+    fn missing_or_bad_steam_app_id_is_an_error() {
+        let root = TempDir::new().unwrap();
+        let manifest = mk_manifest(GameManifest::default());
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let mut largs = LaunchArgs {
+            manifest_game_override: Some("test".to_owned()),
+            ..test_largs(PlatformOpt::Steam, &["./start.sh"])
+        };
+        with_vars(
+            [("SteamAppId", None::<&str>), ("STEAM_COMPAT_APP_ID", None)],
+            || {
+                assert!(LaunchInfo::new(&cfg, &manifest, &secrets, &largs, false).is_err());
+                largs.command.push("AppId=nope".to_owned());
+                assert!(LaunchInfo::new(&cfg, &manifest, &secrets, &largs, false).is_err());
+            },
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn skipped_game_is_not_synced() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            ..test_largs(PlatformOpt::Steam, &["AppId=0"])
+        };
+        let cfg = Config {
+            skip_games: vec!["test".to_owned()],
+            ..test_cfg(root.to_path_buf())
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
+        // these would fail trying to find the game in steam if they did anything
+        launch.sync_down().await.unwrap();
+        launch.sync_up().await.unwrap();
+        assert!(!root.child("test").child(ARCHIVE_NAME).exists());
+    }
+
+    #[test(tokio::test)]
+    async fn offline_launch_is_not_synced() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            offline: true,
+            ..test_largs(PlatformOpt::Steam, &["AppId=0"])
+        };
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
+        // these would fail trying to find the game in steam if they did anything
+        launch.sync_down().await.unwrap();
+        launch.sync_up().await.unwrap();
+        assert!(!root.child("test").child(ARCHIVE_NAME).exists());
+    }
+
+    #[test(tokio::test)]
+    async fn run_sync_runs_the_game_between_syncs() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            ..test_largs(PlatformOpt::Steam, &["AppId=0"])
+        };
+        // skipped so the syncs don't need steam
+        let mut cfg = Config {
+            skip_games: vec!["test".to_owned()],
+            ..test_cfg(root.to_path_buf())
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let r = run_sync(&cfg, &manifest, &secrets, largs, false, async |launch| {
+            Ok(launch.game_name().to_owned())
+        })
+        .await
+        .unwrap();
+        assert_eq!(r, "test");
+
+        cfg.hooks = HooksConfig {
+            pre_launch: Some(vec!["false".to_owned()]),
+            ..Default::default()
+        };
+        let mut ran = false;
+        let r = run_sync(&cfg, &manifest, &secrets, largs, false, async |_| {
+            ran = true;
+            Ok(())
+        })
+        .await;
+        assert!(r.is_err());
+        assert!(!ran, "game was run even though the pre_launch hook failed");
+    }
+
+    #[test(tokio::test)]
+    async fn write_from_another_device_during_launch_is_detected() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            ..test_largs(PlatformOpt::Native, &["game"])
+        };
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, true).unwrap();
+        let other_device = SyncMetadata::from_sys_info(
+            FileMetaTable {
+                entries: Vec::new(),
+                oldest_modified_time: Utc::now(),
+            },
+            String::new(),
+        );
+        // sync_down hasn't looked at the remote so there's nothing to compare with
+        assert!(!launch.remote_written_since_sync_down(&other_device));
+
+        launch.sync_down().await.unwrap();
+        launch.b.write_sync_time(&other_device).await.unwrap();
+        let current = launch.b.read_sync_time().await.unwrap().unwrap();
+        assert!(launch.remote_written_since_sync_down(&current));
+
+        // --yes keeps the local files
+        launch.sync_up().await.unwrap();
+        let current = launch.b.read_sync_time().await.unwrap().unwrap();
+        assert_ne!(
+            current.last_write_timestamp,
+            other_device.last_write_timestamp
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn lock_is_taken_and_released() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            ..test_largs(PlatformOpt::Native, &["game"])
+        };
+        let cfg = Config {
+            sync_lock: true,
+            ..test_cfg(root.to_path_buf())
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let other_device = |age| SyncLock {
+            hostname: "another-device".to_owned(),
+            timestamp: Utc::now() - TimeDelta::seconds(age),
+        };
+
+        // a stale lock is ignored without asking
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
+        launch.b.write_lock(&other_device(120)).await.unwrap();
+        launch.sync_down().await.unwrap();
+        assert!(launch.b.read_lock().await.unwrap().unwrap().is_ours());
+        launch.release_lock().await.unwrap();
+        assert!(launch.b.read_lock().await.unwrap().is_none());
+
+        // --yes takes over a fresh lock
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, true).unwrap();
+        launch.b.write_lock(&other_device(0)).await.unwrap();
+        launch.sync_down().await.unwrap();
+        assert!(launch.b.read_lock().await.unwrap().unwrap().is_ours());
+
+        // another device taking the lock while the game runs keeps it
+        launch.b.write_lock(&other_device(0)).await.unwrap();
+        launch.release_lock().await.unwrap();
+        assert!(!launch.b.read_lock().await.unwrap().unwrap().is_ours());
+    }
+
+    #[test]
+    fn list_templates_for_umu_without_prefix_is_an_error() {
+        let root = TempDir::new().unwrap();
+        let manifest = mk_manifest(GameManifest::default());
+        let largs = &LaunchArgs {
+            manifest_game_override: Some("test".to_owned()),
+            list_templates: true,
+            ..test_largs(PlatformOpt::Umu, &["umu-run", "game.exe"])
+        };
+        let cfg = test_cfg(root.to_path_buf());
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
+        temp_env::with_var_unset("WINEPREFIX", || {
+            let err = launch.template_info().unwrap_err().to_string();
+            assert!(err.contains("WINEPREFIX"), "{err}");
+        });
+    }
+
+    #[test(tokio::test)]
+    async fn lock_is_released_when_upload_fails() {
+        let root = TempDir::new().unwrap();
+        let id = SteamId::new(0);
+        let manifest = mk_manifest(GameManifest {
+            steam: Some(SteamInfo { id }),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_app_id_override: Some(id),
+            ..test_largs(PlatformOpt::Native, &["game"])
+        };
+        let cfg = Config {
+            sync_lock: true,
+            ..test_cfg(root.to_path_buf())
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let r = run_sync(&cfg, &manifest, &secrets, largs, true, async |_| {
+            // unreadable metadata makes the upload fail
+            root.child("test").child(SYNC_TIME_FILE).create_dir_all()?;
+            Ok(())
+        })
+        .await;
+        assert!(r.is_err());
+        assert!(!root.child("test").child(LOCK_FILE).exists());
+    }
+
+    #[test(tokio::test)]
+    async fn dry_run_plan_reads_the_remote() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.create_dir_all().unwrap();
+        saves.child("a").write_str("a").unwrap();
+        let manifest = mk_manifest(GameManifest {
+            files: [(
+                TemplatePath::new("<saves>"),
+                FileConfig {
+                    preds: vec![],
+                    tags: vec![FileTag::Save],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        });
+        let largs = &LaunchArgs {
+            manifest_game_override: Some("test".to_owned()),
+            ..test_largs(PlatformOpt::Native, &["game"])
+        };
+        let cfg = Config {
+            sync_lock: true,
+            template_vars: [("saves".to_owned(), saves.to_path_buf())]
+                .into_iter()
+                .collect(),
+            ..test_cfg(root.path().join("store"))
+        };
+        let secrets = SecretsApi::new_unavailable();
+        let launch = LaunchInfo::new(&cfg, &manifest, &secrets, largs, false).unwrap();
+        launch.sync_up().await.unwrap();
+        let mut metadata = launch.b.read_sync_time().await.unwrap().unwrap();
+        metadata.last_write_timestamp -= TimeDelta::days(1);
+        launch.b.write_sync_time(&metadata).await.unwrap();
+        let other_device = SyncLock {
+            hostname: "another-device".to_owned(),
+            timestamp: Utc::now(),
+        };
+        launch.b.write_lock(&other_device).await.unwrap();
+
+        let plan = launch.plan_sync_down().await.unwrap();
+        assert_eq!(plan.locked_by.unwrap().hostname, other_device.hostname);
+        assert!(plan.conflict.is_some());
+        // the local files are what was uploaded
+        assert!(plan.transfers.is_empty());
+        // nothing was written
+        assert!(!launch.b.read_lock().await.unwrap().unwrap().is_ours());
+        assert_eq!(saves.child("a").path().metadata().unwrap().len(), 1);
+    }
+
     #[test]
     fn find_game_from_vars_heroic() {
         let id = 1;
         let mut manifest = HashMap::new();
-        unsafe {
-            std::env::set_var(HEROIC_APP_SOURCE, "gog");
-            std::env::set_var(HEROIC_APP_NAME, "1");
-        }
         manifest.insert(
             "test".to_owned(),
             GameManifest {
                 steam: None,
                 gog: Some(GogInfo { id }),
+                epic: None,
+                amazon: None,
                 files: Default::default(),
                 launch: Default::default(),
+                registry: Default::default(),
                 install_dir: None,
             },
         );
-        assert!(find_game_from_env_vars(&manifest).unwrap().is_some());
+        temp_env::with_vars(
+            [
+                (HEROIC_APP_SOURCE, Some("gog")),
+                (HEROIC_APP_NAME, Some("1")),
+            ],
+            || {
+                assert!(find_game_from_env_vars(&manifest).unwrap().is_some());
+            },
+        );
+    }
+
+    #[test]
+    fn find_game_from_vars_heroic_amazon() {
+        let manifest = mk_manifest(GameManifest {
+            amazon: Some(AmazonInfo {
+                id: "amzn1.adg.product.1".to_owned(),
+            }),
+            ..Default::default()
+        });
+        temp_env::with_vars(
+            [
+                (HEROIC_APP_SOURCE, Some("amazon")),
+                (HEROIC_APP_NAME, Some("amzn1.adg.product.1")),
+            ],
+            || {
+                assert!(find_game_from_env_vars(&manifest).unwrap().is_some());
+            },
+        );
+    }
+
+    #[test]
+    fn find_game_from_vars_lutris() {
+        let manifest = mk_manifest(GameManifest::default());
+        temp_env::with_var(LUTRIS_GAME_NAME, Some("Test"), || {
+            assert_eq!(
+                find_game_from_lutris_env(&manifest).map(|(n, _)| n),
+                Some("test")
+            );
+        });
+    }
+
+    #[test]
+    fn bottles_command_is_parsed() {
+        let cmd = |s: &str| s.split(' ').map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(
+            parse_bottles_command(&cmd("bottles-cli run -b Games -e /games/Game/game.exe")),
+            Some(("Games".to_owned(), "/games/Game/game.exe".into()))
+        );
+        assert_eq!(
+            parse_bottles_command(&cmd(
+                "flatpak run --command=bottles-cli com.usebottles.bottles run --bottle=Games -p Game -- -b x"
+            )),
+            Some(("Games".to_owned(), "Game".into()))
+        );
+        assert_eq!(
+            parse_bottles_command(&cmd("bottles-cli run -b Games")),
+            None
+        );
+        assert_eq!(parse_bottles_command(&cmd("umu-run game.exe")), None);
+    }
+
+    #[test]
+    fn find_game_from_vars_heroic_epic() {
+        let manifest = mk_manifest(GameManifest {
+            epic: Some(EpicInfo {
+                id: "Bingle".to_owned(),
+            }),
+            ..Default::default()
+        });
+        temp_env::with_vars(
+            [
+                (HEROIC_APP_SOURCE, Some("epic")),
+                (HEROIC_APP_NAME, Some("Bingle")),
+            ],
+            || {
+                assert!(find_game_from_env_vars(&manifest).unwrap().is_some());
+            },
+        );
+    }
+
+    #[test]
+    fn find_game_from_legendary_args() {
+        let manifest = mk_manifest(GameManifest {
+            epic: Some(EpicInfo {
+                id: "Bingle".to_owned(),
+            }),
+            ..Default::default()
+        });
+        let command = [
+            "/usr/bin/wine",
+            "game.exe",
+            "-epicapp=Bingle",
+            "-epicenv=Prod",
+        ]
+        .map(str::to_owned);
+        assert!(find_game_from_epic_args(&manifest, &command).is_some());
     }
 }
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Registry roots which can be synced and the hive file wine keeps them in
+const HIVES: [(&str, &str); 2] = [
+    ("HKEY_CURRENT_USER", "user.reg"),
+    ("HKEY_LOCAL_MACHINE", "system.reg"),
+];
+const WINE_REGISTRY_HEADER: &str = "WINE REGISTRY Version 2";
+
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("registry key '{0}' is not under HKEY_CURRENT_USER or HKEY_LOCAL_MACHINE")]
+    UnsupportedRoot(String),
+    #[error("{0:?} is not a wine registry file")]
+    NotWineRegistry(PathBuf),
+}
+
+/// Split a key from the manifest, e.g. `HKEY_CURRENT_USER/Software/Game`, into the name of the
+/// hive file it is in and the key as it is written in that file
+pub fn hive_and_key(key: &str) -> Result<(&'static str, String), RegistryError> {
+    let mut comps = key.split(['/', '\\']).filter(|c| !c.is_empty());
+    let root = comps.next().unwrap_or_default();
+    let (_, hive) = HIVES
+        .iter()
+        .find(|(r, _)| r.eq_ignore_ascii_case(root))
+        .ok_or_else(|| RegistryError::UnsupportedRoot(key.to_owned()))?;
+    // backslashes are escaped in the hive files
+    Ok((hive, comps.collect::<Vec<_>>().join("\\\\")))
+}
+
+/// A key and its values as written in a hive file
+struct Section<'r> {
+    key: &'r str,
+    text: &'r str,
+}
+
+impl Section<'_> {
+    /// Whether this is one of `keys` or a subkey of one
+    fn is_under(&self, keys: &[String]) -> bool {
+        let key = self.key.to_ascii_lowercase();
+        keys.iter().any(|k| {
+            let k = k.to_ascii_lowercase();
+            key == k || key.starts_with(&format!("{k}\\\\"))
+        })
+    }
+}
+
+/// Split a hive into the header and its sections, which start at each `[key]` line
+fn parse_hive<'r>(
+    path: &Path,
+    text: &'r str,
+) -> Result<(&'r str, Vec<Section<'r>>), RegistryError> {
+    if !text.starts_with(WINE_REGISTRY_HEADER) {
+        return Err(RegistryError::NotWineRegistry(path.to_owned()));
+    }
+    let starts = text
+        .match_indices('\n')
+        .map(|(i, _)| i + 1)
+        .filter(|i| text[*i..].starts_with('['))
+        .collect::<Vec<_>>();
+    let header = &text[..starts.first().copied().unwrap_or(text.len())];
+    let sections = starts
+        .iter()
+        .enumerate()
+        .map(|(n, start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(text.len());
+            let text = &text[*start..end];
+            let line = text.lines().next().unwrap_or_default();
+            let key = line[1..]
+                .rsplit_once(']')
+                .map(|(k, _)| k)
+                .unwrap_or_default();
+            Section { key, text }
+        })
+        .collect();
+    Ok((header, sections))
+}
+
+/// Append a section, keeping the blank line wine puts between them
+fn push_section(out: &mut String, section: &str) {
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+    out.push_str(section);
+}
+
+/// Copy of `hive` with only `keys` and their subkeys, None if it has none of them
+pub fn export(path: &Path, hive: &str, keys: &[String]) -> Result<Option<String>, RegistryError> {
+    let (header, sections) = parse_hive(path, hive)?;
+    let mut out = header.to_owned();
+    let mut any = false;
+    for s in sections.iter().filter(|s| s.is_under(keys)) {
+        push_section(&mut out, s.text);
+        any = true;
+    }
+    Ok(any.then_some(out))
+}
+
+/// Replace `keys` and their subkeys in `hive` with the ones from an exported `fragment`
+pub fn import(
+    path: &Path,
+    hive: &str,
+    fragment: &str,
+    keys: &[String],
+) -> Result<String, RegistryError> {
+    let (header, sections) = parse_hive(path, hive)?;
+    let (_, new_sections) = parse_hive(path, fragment)?;
+    let mut out = header.to_owned();
+    for s in sections.iter().filter(|s| !s.is_under(keys)) {
+        push_section(&mut out, s.text);
+    }
+    for s in new_sections.iter().filter(|s| s.is_under(keys)) {
+        push_section(&mut out, s.text);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{export, hive_and_key, import};
+
+    const HIVE: &str = r#"WINE REGISTRY Version 2
+;; All keys relative to \\User\\S-1-5-21-0-0-0-1000
+
+#arch=win64
+
+[Software\\Game] 1700000000
+#time=1da0
+"Slot"=dword:00000001
+
+[Software\\Game\\Options] 1700000000
+"Volume"=dword:00000050
+
+[Software\\Wine] 1700000000
+"Version"="win10"
+"#;
+
+    #[test]
+    fn key_is_split_into_hive() {
+        assert_eq!(
+            hive_and_key("HKEY_CURRENT_USER/Software/Game").unwrap(),
+            ("user.reg", r"Software\\Game".to_owned())
+        );
+        assert_eq!(
+            hive_and_key("HKEY_LOCAL_MACHINE/Software/Game").unwrap().0,
+            "system.reg"
+        );
+        assert!(hive_and_key("HKEY_USERS/Game").is_err());
+    }
+
+    #[test]
+    fn export_takes_only_subkeys() {
+        let keys = [r"software\\game".to_owned()];
+        let out = export(Path::new("user.reg"), HIVE, &keys).unwrap().unwrap();
+        assert!(out.starts_with("WINE REGISTRY Version 2"));
+        assert!(out.contains(r#""Slot"=dword:00000001"#));
+        assert!(out.contains(r"[Software\\Game\\Options]"));
+        assert!(!out.contains("Wine"));
+
+        let none = [r"Software\\Other".to_owned()];
+        assert!(
+            export(Path::new("user.reg"), HIVE, &none)
+                .unwrap()
+                .is_none()
+        );
+        assert!(export(Path::new("user.reg"), "not a hive", &keys).is_err());
+    }
+
+    #[test]
+    fn import_replaces_keys() {
+        let keys = [r"Software\\Game".to_owned()];
+        let fragment = HIVE.replace("dword:00000001", "dword:00000002").replace(
+            "[Software\\\\Game\\\\Options] 1700000000\n\"Volume\"=dword:00000050\n\n",
+            "",
+        );
+        let out = import(Path::new("user.reg"), HIVE, &fragment, &keys).unwrap();
+        assert!(out.contains(r#""Slot"=dword:00000002"#));
+        assert!(!out.contains("Options"));
+        assert!(out.contains(r#""Version"="win10""#));
+        assert_eq!(out.matches("[Software").count(), 2);
+        // importing the export gives back the same keys
+        let exported = export(Path::new("user.reg"), &out, &keys).unwrap().unwrap();
+        assert_eq!(
+            import(Path::new("user.reg"), &out, &exported, &keys).unwrap(),
+            out
+        );
+    }
+}
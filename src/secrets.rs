@@ -6,6 +6,9 @@ use tracing::debug;
 
 const ATTR_ID: &str = "id";
 const ATTR_SERVICE: &str = "service";
+/// Name of the backend when the secret was made, only for people looking at the keyring.
+/// Backends can be renamed so this is never used to find secrets
+const ATTR_BACKEND: &str = "backend";
 
 struct Inner<'s> {
     hdl: SecretService<'s>,
@@ -34,10 +37,13 @@ impl<'s> Inner<'s> {
 pub struct SecretsApi<'s> {
     i: Option<Inner<'s>>,
 }
-fn mk_cinc_attrs(id: &str) -> HashMap<&str, &str> {
+fn mk_cinc_attrs<'a>(id: &'a str, backend: Option<&'a str>) -> HashMap<&'a str, &'a str> {
     let mut attrs = HashMap::new();
     attrs.insert(ATTR_ID, id);
     attrs.insert(ATTR_SERVICE, "cinc");
+    if let Some(backend) = backend {
+        attrs.insert(ATTR_BACKEND, backend);
+    }
     attrs
 }
 
@@ -78,14 +84,15 @@ impl<'s> SecretsApi<'s> {
         Ok(())
     }
 
-    pub async fn add_item(&self, label: &str, secret: &str) -> Result<()> {
-        debug!("storing secret '{label}'");
+    /// Store a secret for a backend, it is looked up later by `label` only
+    pub async fn add_item(&self, label: &str, backend: &str, secret: &str) -> Result<()> {
+        debug!("storing secret '{label}' for backend '{backend}'");
         let hdl = self.i.as_ref().expect("no available secrets API");
         hdl.collection()
             .await?
             .create_item(
-                &format!("cinc secret {label}"),
-                mk_cinc_attrs(label),
+                &format!("cinc secret for backend {backend} ({label})"),
+                mk_cinc_attrs(label, Some(backend)),
                 secret.as_bytes(),
                 true,
                 "text/plain",
@@ -98,7 +105,7 @@ impl<'s> SecretsApi<'s> {
         debug!("getting secret '{label}'");
         let hdl = self.i.as_ref().expect("no available secrets API");
         let items = hdl.collection().await?;
-        let items = items.search_items(mk_cinc_attrs(label)).await?;
+        let items = items.search_items(mk_cinc_attrs(label, None)).await?;
         let s = items.first().map(|i| i.get_secret());
         if let Some(s) = s {
             Ok(Some(s.await?))
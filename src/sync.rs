@@ -1,26 +1,47 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     io::{BufReader, prelude::*},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
 };
 
-use anyhow::{Result, anyhow, bail};
-use chrono::{DateTime, Local, Utc};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Datelike, Local, TimeDelta, Timelike, Utc};
+use futures_util::{StreamExt, TryStreamExt, stream};
 use itertools::Itertools;
-use tracing::{debug, info};
-use xz2::bufread::{XzDecoder, XzEncoder};
+use rayon::prelude::*;
+use tracing::{debug, info, warn};
+use xz2::{
+    bufread::{XzDecoder, XzEncoder},
+    stream::{Check, MtStreamBuilder},
+};
+use zip::write::SimpleFileOptions;
 
 use crate::{
-    backends::{FileMetaEntry, FileMetaTable, StorageBackend, SyncMetadata},
-    config::{SteamId, SteamId64},
-    manifest::{FileTag, GameManifest, PlatformInfo, TemplateInfo, TemplatePath},
-    paths::{self, PathExt, extract_postfix, steam_dir},
-    platform::HEROIC_APP_NAME,
-    ui::{SyncChoices, SyncIssueInfo},
+    args::PlatformOpt,
+    backends::{
+        FileMetaEntry, FileMetaTable, INCREMENTAL_DIR, StorageBackend, SyncMetadata, content_hash,
+    },
+    config::{Config, SteamId, SteamId64},
+    events::{SyncDirection, SyncEvent, SyncObserver},
+    heroic,
+    manifest::{FileTag, GameManifest, PlatformInfo, TemplateError, TemplateInfo, TemplatePath},
+    paths::{self, PathExt, extract_postfix, find_steam_app},
+    platform::{HEROIC_APP_NAME, HEROIC_APP_SOURCE, IncomaptibleCincVersionError},
+    registry, time,
+    ui::{self, SyncChoices, SyncIssueInfo},
 };
 
 pub const ARCHIVE_NAME: &str = "archive.tar.xz";
+/// Archive of the config files, see [`SyncOptions::config_files`]
+pub const CONFIG_ARCHIVE_NAME: &str = "config.tar.xz";
 const XZ_LEVEL: u32 = 5;
+/// Past this many files the requests for an incremental sync cost more than sending an archive
+const MAX_INCREMENTAL_FILES: usize = 500;
+/// Directory inside the wine prefix that registry keys are exported to so they can be synced
+const REGISTRY_EXPORT_DIR: &str = "<winLocalAppData>/cinc/registry";
 
 #[derive(Clone, Debug)]
 pub struct FileInfo<'f> {
@@ -30,10 +51,198 @@ pub struct FileInfo<'f> {
     tags: &'f [FileTag],
 }
 
+/// Registry keys from one hive in a wine prefix, they are synced through a copy of them exported
+/// to a file
+#[derive(Clone, Debug)]
+struct RegistryExport {
+    hive: PathBuf,
+    export_path: PathBuf,
+    template: TemplatePath,
+    keys: Vec<String>,
+}
+
+impl RegistryExport {
+    /// What an export of the keys would contain, `None` if the hive doesn't have any of them
+    fn exported(&self) -> Result<Option<String>> {
+        if !fs::exists(&self.hive)? {
+            return Ok(None);
+        }
+        let hive = fs::read_to_string(&self.hive)?;
+        Ok(registry::export(&self.hive, &hive, &self.keys)?)
+    }
+
+    /// Export the keys so they can be uploaded
+    fn export(&self) -> Result<()> {
+        let Some(out) = self.exported()? else {
+            return Ok(());
+        };
+        // rewriting an unchanged export would make the local saves look newer than they are
+        if fs::read_to_string(&self.export_path).ok().as_deref() != Some(out.as_str()) {
+            debug!(
+                "exporting registry keys {:?} to {:?}",
+                self.keys, self.export_path
+            );
+            fs::create_dir_all(self.export_path.parent().unwrap())?;
+            fs::write(&self.export_path, out)?;
+        }
+        Ok(())
+    }
+
+    /// Put the keys from a downloaded export back into the hive
+    fn import(&self) -> Result<()> {
+        if !fs::exists(&self.export_path)? {
+            return Ok(());
+        }
+        if !fs::exists(&self.hive)? {
+            warn!(
+                "can't restore registry keys to {:?} as it doesn't exist yet, run the game once first",
+                self.hive
+            );
+            return Ok(());
+        }
+        let hive = fs::read_to_string(&self.hive)?;
+        let export = fs::read_to_string(&self.export_path)?;
+        let out = registry::import(&self.hive, &hive, &export, &self.keys)?;
+        if out != hive {
+            debug!("importing registry keys {:?} to {:?}", self.keys, self.hive);
+            fs::write(&self.hive, out)?;
+        }
+        Ok(())
+    }
+}
+
+/// A save file read to be exported
+struct ExportFile {
+    local_path: PathBuf,
+    data: Vec<u8>,
+    modified: Option<DateTime<Utc>>,
+}
+
+/// A file that would be copied by a sync, used to show what a dry-run would do
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedTransfer {
+    pub local_path: PathBuf,
+    pub remote_path: PathBuf,
+}
+
+/// The archive on the backend doesn't have the hash its metadata says it should, e.g. the upload
+/// was cut off or the backend damaged it
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "archive corrupt on the backend, its hash is {actual} but the sync metadata says {expected}"
+)]
+pub struct CorruptArchiveError {
+    pub expected: String,
+    pub actual: String,
+}
+
+impl CorruptArchiveError {
+    /// Check `archive` before decompressing it, metadata written before archives were hashed
+    /// has nothing to check against
+    pub fn check(archive: &[u8], metadata: &SyncMetadata) -> Result<(), Self> {
+        let Some(expected) = &metadata.archive_hash else {
+            return Ok(());
+        };
+        let actual = content_hash(archive);
+        if &actual == expected {
+            Ok(())
+        } else {
+            Err(Self {
+                expected: expected.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+/// How the local saves compare to those on the backend, see [`SyncMgr::verify`]
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Verification {
+    /// Files which are the same in both
+    pub matching: usize,
+    /// Files in both whose contents are different
+    pub differs: Vec<PathBuf>,
+    /// Files on the backend which aren't on this machine
+    pub missing_locally: Vec<PathBuf>,
+    /// Files on this machine which aren't on the backend
+    pub not_uploaded: Vec<PathBuf>,
+}
+
+impl Verification {
+    pub fn is_consistent(&self) -> bool {
+        self.differs.is_empty() && self.missing_locally.is_empty() && self.not_uploaded.is_empty()
+    }
+}
+
+/// Options from the config that affect how files are found and synced
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// User defined template variables
+    pub template_vars: HashMap<String, PathBuf>,
+    /// Match save paths against the filesystem ignoring case
+    pub case_insensitive_paths: bool,
+    /// Include files and directories that are symlinked into the save dir
+    pub follow_symlinks: bool,
+    /// Only upload files which have changed, rather than an archive of everything
+    pub incremental: bool,
+    /// Most files to transfer at once in an incremental sync
+    pub max_concurrent_requests: usize,
+    /// Refuse to upload more than this many bytes of files
+    pub max_upload_size: Option<u64>,
+    /// How much newer the local files have to be for it to be a conflict
+    pub conflict_skew: TimeDelta,
+    /// Where local files are copied to before a download overwrites them, None to not back them up
+    pub backup_dir: Option<PathBuf>,
+    /// How many of those backups to keep for each game
+    pub max_backups: usize,
+    /// Threads to compress archives with, 0 for one per cpu
+    pub compression_threads: u32,
+    /// Upload without the files that can't be read rather than failing, see
+    /// [`SyncMgr::skipped_files`]
+    pub skip_unreadable_files: bool,
+    /// Sync the files tagged as config instead of the saves, files tagged as both are left with
+    /// the saves. These go in [`CONFIG_ARCHIVE_NAME`] rather than the normal archive
+    pub config_files: bool,
+}
+
+impl SyncOptions {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            template_vars: cfg.template_vars.clone(),
+            case_insensitive_paths: cfg.case_insensitive_paths,
+            follow_symlinks: cfg.follow_symlinks,
+            incremental: cfg.incremental_uploads,
+            max_concurrent_requests: cfg.max_concurrent_requests,
+            max_upload_size: (cfg.max_upload_size_mb != 0)
+                .then(|| cfg.max_upload_size_mb * 1024 * 1024),
+            conflict_skew: TimeDelta::seconds(cfg.conflict_skew_secs as i64),
+            backup_dir: (cfg.max_download_backups != 0).then(paths::download_backup_dir),
+            max_backups: cfg.max_download_backups,
+            compression_threads: cfg.compression_threads,
+            skip_unreadable_files: cfg.skip_unreadable_files,
+            config_files: false,
+        }
+    }
+}
+
 pub struct SyncMgr<'f> {
+    game_name: &'f str,
     files: Vec<FileInfo<'f>>,
+    registry: Vec<RegistryExport>,
     local_info: TemplateInfo,
     remote_name: &'f str,
+    case_insensitive_paths: bool,
+    incremental: bool,
+    max_concurrent_requests: usize,
+    max_upload_size: Option<u64>,
+    conflict_skew: TimeDelta,
+    backup_dir: Option<PathBuf>,
+    max_backups: usize,
+    compression_threads: u32,
+    skip_unreadable_files: bool,
+    /// Files left out of the last upload as they couldn't be read
+    skipped: Mutex<Vec<PathBuf>>,
+    observer: Arc<dyn SyncObserver>,
 }
 
 impl<'f> SyncMgr<'f> {
@@ -42,135 +251,288 @@ impl<'f> SyncMgr<'f> {
         manifest: &'f GameManifest,
         app_id: SteamId,
         remote_name: &'f str,
+        opts: &SyncOptions,
     ) -> Result<Self> {
-        let steam_info = steam_dir()?;
-        let (steam_app_manifest, steam_app_lib) = steam_info
-            .find_app(app_id.id())?
-            .ok_or_else(|| anyhow!("could not find steam app with id '{app_id}'"))?;
-
-        let store_user_id = steam_app_manifest
-            .last_user
-            .map(SteamId64::new)
-            .map(|id| id.to_id3().to_string());
-        // local template subst
-        let install_dir = Some(manifest.install_dir.as_deref().unwrap_or(game_name).into());
-        let local_info = TemplateInfo {
-            win_prefix: steam_app_lib
-                .path()
-                .join("steamapps")
-                .join("compatdata")
-                .join(app_id.to_string())
-                .join("pfx")
-                .join("drive_c"),
-            win_user: "steamuser".to_owned(),
-            base_dir: Some(steam_app_lib.resolve_app_dir(&steam_app_manifest)),
-            root: Some(steam_app_lib.path().to_owned()),
-            store_user_id: store_user_id.clone(),
-
-            home_dir: None,
-            xdg_config: None,
-            xdg_data: None,
-            install_dir: install_dir.clone(),
-        };
-
-        // remote template substs
-        let remote_info = TemplateInfo {
-            win_prefix: PathBuf::from("win_prefix"),
-            win_user: "steamuser".to_owned(),
-            base_dir: Some("base_dir".into()),
-            root: Some("steam_root".into()),
-            store_user_id,
-
-            home_dir: Some("home_dir".into()),
-            xdg_config: Some("xdg_config".into()),
-            xdg_data: Some("xdg_data".into()),
-            install_dir,
-        };
-        Self::from_manifest(manifest, local_info, &remote_info, remote_name)
+        let local_info = steam_template_info(game_name, manifest, app_id, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            local_info.store_user_id.clone(),
+            opts,
+        );
+        let wine = windows_host().is_none();
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            wine,
+            opts,
+        )
     }
     pub fn from_umu_env(
         game_name: &'f str,
         manifest: &'f GameManifest,
+        exe_path: &Path,
         remote_name: &'f str,
+        opts: &SyncOptions,
     ) -> Result<Self> {
-        let wine_prefix = std::env::var("WINEPREFIX").unwrap_or_else(|_| {
-            todo!("WINEPREFIX not found, todo: we need to fallback to the umu id here https://umu.openwinecomponents.org/");
-        });
-        let wine_prefix = Path::new(&wine_prefix);
-        // we need to work out the base dir using a little magic
-        let install_dir = Some(manifest.install_dir.as_deref().unwrap_or(game_name).into());
-        let root_dir = if are_we_launched_by_heroic() {
-            let r = dirs::home_dir().map(|h| h.join("Games").join("Heroic"));
-            debug!("we are running under heroic, setting the root dir to {r:?}");
-            r
-        } else {
-            debug!(
-                "not sure what launcher we're running under, can't set the root dir let's hope that's okay"
-            );
-            None
-        };
+        let local_info = umu_template_info(game_name, manifest, exe_path, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            None,
+            opts,
+        );
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            true,
+            opts,
+        )
+    }
 
-        // local template subst
-        let local_info = TemplateInfo {
-            win_prefix: wine_prefix.join("pfx").join("drive_c"),
-            win_user: "steamuser".to_owned(),
-            base_dir: None,
-            root: root_dir,
-            store_user_id: None,
-
-            home_dir: Some(
-                wine_prefix
-                    .join("pfx")
-                    .join("drive_c")
-                    .join("users")
-                    .join("steamuser"),
-            ),
-            xdg_config: None,
-            xdg_data: None,
-            install_dir: install_dir.clone(),
-        };
+    pub fn from_lutris_env(
+        game_name: &'f str,
+        manifest: &'f GameManifest,
+        remote_name: &'f str,
+        opts: &SyncOptions,
+    ) -> Result<Self> {
+        let local_info = lutris_template_info(game_name, manifest, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            None,
+            opts,
+        );
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            true,
+            opts,
+        )
+    }
 
-        // remote template substs
-        let remote_info = TemplateInfo {
-            win_prefix: PathBuf::from("win_prefix"),
-            win_user: "steamuser".to_owned(),
-            base_dir: Some("base_dir".into()),
-            root: Some("steam_root".into()),
-            store_user_id: None,
-
-            home_dir: Some("home_dir".into()),
-            xdg_config: Some("xdg_config".into()),
-            xdg_data: Some("xdg_data".into()),
-            install_dir,
-        };
-        Self::from_manifest(manifest, local_info, &remote_info, remote_name)
+    /// For games run with `bottles-cli`, the prefix is the bottle's directory
+    pub fn from_bottles_env(
+        game_name: &'f str,
+        manifest: &'f GameManifest,
+        bottle: &str,
+        exe_path: &Path,
+        remote_name: &'f str,
+        opts: &SyncOptions,
+    ) -> Result<Self> {
+        let local_info = bottles_template_info(game_name, manifest, bottle, exe_path, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            None,
+            opts,
+        );
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            true,
+            opts,
+        )
+    }
+
+    /// For the game as it is installed on this machine, steam if it is installed there otherwise
+    /// native
+    pub fn from_detected(
+        game_name: &'f str,
+        manifest: &'f GameManifest,
+        remote_name: &'f str,
+        opts: &SyncOptions,
+    ) -> Result<Self> {
+        let (platform, local_info) = detect_local_template_info(game_name, manifest, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            local_info.store_user_id.clone(),
+            opts,
+        );
+        let wine = platform == PlatformOpt::Steam && windows_host().is_none();
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            wine,
+            opts,
+        )
+    }
+
+    /// For games running natively, i.e. without wine
+    pub fn from_native(
+        game_name: &'f str,
+        manifest: &'f GameManifest,
+        remote_name: &'f str,
+        opts: &SyncOptions,
+    ) -> Result<Self> {
+        let local_info = native_template_info(game_name, manifest, opts);
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            None,
+            opts,
+        );
+        Self::from_manifest(
+            game_name,
+            manifest,
+            local_info,
+            &remote_info,
+            remote_name,
+            false,
+            opts,
+        )
+    }
+
+    /// For uploading a backup made by something else, `files` are where each file was backed up
+    /// from and where it is now
+    ///
+    /// The paths are matched against the game's save paths on this machine, those that don't
+    /// match any are returned
+    pub fn from_backup(
+        game_name: &'f str,
+        manifest: &'f GameManifest,
+        files: &[(PathBuf, PathBuf)],
+        remote_name: &'f str,
+        opts: &SyncOptions,
+    ) -> Result<(Self, Vec<PathBuf>)> {
+        let (_, local_info) = detect_local_template_info(game_name, manifest, opts)?;
+        let remote_info = remote_template_info(
+            Some(game_install_dir(manifest, game_name, None)),
+            local_info.store_user_id.clone(),
+            opts,
+        );
+        let mut roots = Vec::new();
+        for (template, cfg) in &manifest.files {
+            if !cfg.tags.contains(&FileTag::Save) {
+                continue;
+            }
+            // only some of the paths will resolve, e.g. not windows ones on linux
+            let (Ok(local), Ok(remote)) = (
+                template.apply_substs(&local_info),
+                template.apply_substs(&remote_info),
+            ) else {
+                continue;
+            };
+            roots.push((template, cfg, PathBuf::from(local), PathBuf::from(remote)));
+        }
+
+        let mut matched = Vec::new();
+        let mut unmatched = Vec::new();
+        for (original, stored) in files {
+            // the deepest root is the most specific, windows paths may differ in case
+            let root = roots
+                .iter()
+                .filter_map(|r| Some((r, strip_prefix_ignore_case(&r.2, original)?)))
+                .max_by_key(|((_, _, local, _), _)| local.components().count());
+            let Some(((template, cfg, _, remote), postfix)) = root else {
+                unmatched.push(original.clone());
+                continue;
+            };
+            let join = |base: &Path| {
+                if postfix.as_os_str().is_empty() {
+                    base.to_owned()
+                } else {
+                    base.join_good(&postfix)
+                }
+            };
+            // the names come from whatever disk the backup was made on
+            let Some(file_template) = join(template.as_raw_path()).to_str().map(str::to_owned)
+            else {
+                warn!("skipping {original:?} as its name is not valid utf8");
+                continue;
+            };
+            matched.push(FileInfo {
+                local_path: stored.clone(),
+                remote_path: join(remote),
+                template: TemplatePath::new(file_template),
+                tags: cfg.tags.as_slice(),
+            });
+        }
+        matched.sort_by(|a, b| a.local_path.cmp(&b.local_path));
+
+        Ok((
+            Self {
+                game_name,
+                files: matched,
+                registry: Vec::new(),
+                local_info,
+                remote_name,
+                case_insensitive_paths: opts.case_insensitive_paths,
+                incremental: false,
+                max_concurrent_requests: opts.max_concurrent_requests,
+                max_upload_size: opts.max_upload_size,
+                conflict_skew: opts.conflict_skew,
+                backup_dir: opts.backup_dir.clone(),
+                max_backups: opts.max_backups,
+                compression_threads: opts.compression_threads,
+                skip_unreadable_files: opts.skip_unreadable_files,
+                skipped: Mutex::default(),
+                observer: Arc::new(()),
+            },
+            unmatched,
+        ))
     }
 
     fn from_manifest(
+        game_name: &'f str,
         manifest: &'f GameManifest,
         local_info: TemplateInfo,
         remote_info: &TemplateInfo,
         remote_name: &'f str,
+        wine: bool,
+        opts: &SyncOptions,
     ) -> Result<Self> {
-        let mut files = Vec::new();
+        let mut roots = Vec::new();
+        // files that would be synced if they existed
+        let mut candidates = 0;
         for (filename, cfg) in &manifest.files {
-            if !cfg.preds.iter().all(|p| {
-                p.sat(PlatformInfo {
-                    store: None,
-                    wine: true, // assume wine true and filter out when it's not later
-                })
-            }) {
+            if !cfg
+                .preds
+                .iter()
+                .all(|p| p.sat(PlatformInfo { store: None, wine }))
+            {
                 debug!("rejecting {filename:?} as predicates were not satisfied");
                 continue;
             }
-            let fname = filename.apply_substs(&local_info)?;
-            if !cfg.tags.contains(&FileTag::Save) {
-                debug!("skipping {} as it is not a savegame file", fname);
+            let fname = match filename.apply_substs(&local_info) {
+                Ok(f) => f,
+                Err(e @ TemplateError::NoWinePrefix(_)) => {
+                    debug!("skipping {filename:?}: {e}");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if !is_synced(&cfg.tags, opts) {
+                debug!(
+                    "skipping {} as it is not a {} file",
+                    fname,
+                    if opts.config_files {
+                        "config"
+                    } else {
+                        "savegame"
+                    }
+                );
                 continue;
             }
+            candidates += 1;
             let remote_name = filename.apply_substs(remote_info)?;
+            let fname = if opts.case_insensitive_paths {
+                paths::resolve_case_insensitive(Path::new(&fname))
+            } else {
+                fname.into()
+            };
             let info = FileInfo {
-                local_path: fname.into(),
+                local_path: fname,
                 remote_path: remote_name.into(),
                 tags: cfg.tags.as_slice(),
                 template: filename.to_owned(),
@@ -182,85 +544,375 @@ impl<'f> SyncMgr<'f> {
                 );
                 continue;
             }
+            roots.push(info);
+        }
 
-            for r in walkdir::WalkDir::new(&info.local_path).follow_links(false) {
-                let dir = r?;
-                if dir.path().is_dir() {
-                    continue;
-                }
+        let registry = if wine && !opts.config_files {
+            Self::registry_exports(manifest, &local_info)?
+        } else {
+            Vec::new()
+        };
+        // the exports are only written when uploading, so they may not exist yet
+        let mut registry_files = Vec::new();
+        for r in &registry {
+            if r.exported()?.is_some() {
+                registry_files.push(FileInfo {
+                    local_path: r.export_path.clone(),
+                    remote_path: r.template.apply_substs(remote_info)?.into(),
+                    template: r.template.clone(),
+                    tags: &[FileTag::Save],
+                });
+            }
+        }
+        if roots.is_empty() && registry_files.is_empty() {
+            let kind = if opts.config_files { "config" } else { "save" };
+            if candidates == 0 && registry.is_empty() {
+                warn!(
+                    "{game_name} is in the manifest but none of its files are tagged as {kind} files, nothing will be synced"
+                );
+            } else {
+                info!("none of the {kind} files of {game_name} exist yet, nothing will be synced");
+            }
+        }
 
-                let fname = &info.local_path;
-                let remote_path = &info.remote_path;
-                let p = dir.path();
-                let postfix = extract_postfix(fname, p);
-                let rp = remote_path.join_good(postfix);
-                assert!(!rp.is_dir(), "{rp:?} {remote_path:?}  {p:?}");
-                assert!(!p.is_dir());
-                let template = info.template.as_raw_path().join_good(postfix);
-
-                files.push(FileInfo {
-                    local_path: dir.path().to_owned(),
-                    remote_path: rp,
-                    tags: info.tags,
-                    template: TemplatePath::new(template.to_str().unwrap().to_owned()),
-                })
+        time! {
+            "walking save dirs": {
+                let mut files = roots
+                    .par_iter()
+                    .map(|root| Self::walk_save_root(root, opts))
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect_vec();
             }
         }
+        files.extend(registry_files);
+        // the manifest is a hashmap so sort to keep the archive the same between runs
+        files.sort_by(|a, b| a.local_path.cmp(&b.local_path));
 
         Ok(Self {
+            game_name,
             files,
+            registry,
             local_info,
             remote_name,
+            case_insensitive_paths: opts.case_insensitive_paths,
+            incremental: opts.incremental,
+            max_concurrent_requests: opts.max_concurrent_requests,
+            max_upload_size: opts.max_upload_size,
+            conflict_skew: opts.conflict_skew,
+            backup_dir: opts.backup_dir.clone(),
+            max_backups: opts.max_backups,
+            compression_threads: opts.compression_threads,
+            skip_unreadable_files: opts.skip_unreadable_files,
+            skipped: Mutex::default(),
+            observer: Arc::new(()),
+        })
+    }
+
+    /// Registry keys the manifest has saves in, grouped by the hive in the prefix they are in
+    fn registry_exports(
+        manifest: &GameManifest,
+        local_info: &TemplateInfo,
+    ) -> Result<Vec<RegistryExport>> {
+        let Some(prefix) = local_info.win_prefix.as_deref().and_then(Path::parent) else {
+            return Ok(Vec::new());
+        };
+        let mut by_hive = BTreeMap::<_, Vec<_>>::new();
+        for (key, cfg) in &manifest.registry {
+            let platform = PlatformInfo {
+                store: None,
+                wine: true,
+            };
+            if !cfg.tags.contains(&FileTag::Save) || !cfg.preds.iter().all(|p| p.sat(platform)) {
+                debug!("skipping registry key {key} as it is not a save or not for this platform");
+                continue;
+            }
+            match registry::hive_and_key(key) {
+                Ok((hive, key)) => by_hive.entry(hive).or_default().push(key),
+                Err(e) => warn!("skipping registry key: {e}"),
+            }
+        }
+        by_hive
+            .into_iter()
+            .map(|(hive, keys)| {
+                let template = TemplatePath::new(format!("{REGISTRY_EXPORT_DIR}/{hive}"));
+                Ok(RegistryExport {
+                    hive: prefix.join(hive),
+                    export_path: template.apply_substs(local_info)?.into(),
+                    template,
+                    keys,
+                })
+            })
+            .collect()
+    }
+
+    /// Find all the files under a save path from the manifest
+    fn walk_save_root(info: &FileInfo<'f>, opts: &SyncOptions) -> Result<Vec<FileInfo<'f>>> {
+        let mut files = Vec::new();
+        // used to stop symlinks taking us outside of the save dir
+        let canonical_root = fs::canonicalize(&info.local_path)?;
+        for r in walkdir::WalkDir::new(&info.local_path).follow_links(opts.follow_symlinks) {
+            let dir = match r {
+                Ok(d) => d,
+                Err(e) if e.loop_ancestor().is_some() => {
+                    warn!("skipping symlink loop at {:?}", e.path());
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // the save path itself being a symlink is fine
+            if dir.depth() > 0 && dir.path_is_symlink() {
+                if !opts.follow_symlinks {
+                    debug!(
+                        "skipping symlink {:?}, set follow_symlinks to include it",
+                        dir.path()
+                    );
+                    continue;
+                }
+                let target = fs::canonicalize(dir.path())?;
+                if !target.starts_with(&canonical_root) {
+                    warn!(
+                        "skipping symlink {:?} as it points outside of the save dir to {target:?}",
+                        dir.path()
+                    );
+                    continue;
+                }
+            }
+            // this is from the walk so doesn't need another stat
+            if dir.file_type().is_dir() {
+                continue;
+            }
+
+            let fname = &info.local_path;
+            let remote_path = &info.remote_path;
+            let p = dir.path();
+            let Some(postfix) = extract_postfix(fname, p) else {
+                warn!("skipping {p:?} as it is not inside the save path {fname:?}");
+                continue;
+            };
+            // joining an empty postfix adds a trailing slash, which would make the file a dir
+            let join = |base: &Path| {
+                if postfix.as_os_str().is_empty() {
+                    base.to_owned()
+                } else {
+                    base.join_good(postfix)
+                }
+            };
+            let rp = join(remote_path);
+            if rp.is_dir() || p.is_dir() {
+                warn!("skipping {p:?} as it or its remote path {rp:?} is a directory");
+                continue;
+            }
+            let template = join(info.template.as_raw_path());
+
+            files.push(FileInfo {
+                local_path: dir.path().to_owned(),
+                remote_path: rp,
+                tags: info.tags,
+                template: TemplatePath::new(template.to_str().unwrap().to_owned()),
+            })
+        }
+        Ok(files)
+    }
+
+    /// Get the local path for a template
+    fn localise(&self, template: &TemplatePath) -> Result<PathBuf> {
+        let p = PathBuf::from(template.apply_substs(&self.local_info)?);
+        Ok(if self.case_insensitive_paths {
+            paths::resolve_case_insensitive(&p)
+        } else {
+            p
         })
     }
     fn get_modified_times(&self) -> Result<Vec<DateTime<Utc>>> {
         self.files
-            .iter()
+            .par_iter()
             .map(|f| &f.local_path)
-            .map(fs::metadata)
-            .map_ok(|m| Ok(DateTime::<Utc>::from(m.modified()?)))
-            .flatten()
+            .filter_map(|p| match fs::metadata(p) {
+                Ok(m) => Some(m.modified().map(DateTime::<Utc>::from)),
+                // a registry export which hasn't been written yet
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => Some(Err(e)),
+            })
             .collect::<Result<_, std::io::Error>>()
             .map_err(|e| e.into())
     }
 
+    /// Write the registry exports so they are up to date for an upload
+    fn export_registry(&self) -> Result<()> {
+        for r in &self.registry {
+            r.export()?;
+        }
+        Ok(())
+    }
+
     fn get_latest_modified_time(&self) -> Result<Option<DateTime<Utc>>> {
         Ok(self.get_modified_times()?.into_iter().max())
     }
 
     pub async fn rhaid_lawrlwytho(&self, metadata: &SyncMetadata) -> Result<bool> {
-        for file in metadata.file_table.localise_entries(&self.local_info) {
-            let file = file?;
-            if let Some(f) = self.files.iter().find(|f| f.local_path == file) {
-                let mod_time = std::fs::metadata(&f.local_path)?.modified()?;
-                let mod_time = DateTime::<Utc>::from(mod_time);
-                if mod_time < metadata.file_table.oldest_modified_time {
-                    return Ok(true);
-                }
-            } else {
+        for entry in &metadata.file_table.entries {
+            let file = self.localise(&entry.template)?;
+            let Some(f) = self.files.iter().find(|f| f.local_path == file) else {
+                return Ok(true);
+            };
+            // a registry export which hasn't been written yet
+            if !fs::exists(&f.local_path)? {
+                return Ok(true);
+            }
+            let mod_time = std::fs::metadata(&f.local_path)?.modified()?;
+            let mod_time = DateTime::<Utc>::from(mod_time);
+            if mod_time < metadata.file_table.oldest_modified_time {
                 return Ok(true);
             }
         }
         Ok(false)
     }
 
+    /// Compare the saves on the backend with the local ones by their contents, nothing is changed
+    pub async fn verify(&self, backend: &StorageBackend<'_>) -> Result<Verification> {
+        let Some(metadata) = backend.read_sync_time().await? else {
+            bail!("no saves have been uploaded for {}", self.game_name);
+        };
+        IncomaptibleCincVersionError::check_read(&metadata)?;
+
+        let mut remote = BTreeMap::new();
+        if metadata.incremental {
+            for entry in &metadata.file_table.entries {
+                let data = backend
+                    .read_file(&Path::new(INCREMENTAL_DIR).join(&entry.remote_path))
+                    .await?;
+                remote.insert(self.localise(&entry.template)?, content_hash(&data));
+            }
+        } else {
+            let archive = backend.read_file(Path::new(ARCHIVE_NAME)).await?;
+            CorruptArchiveError::check(&archive, &metadata)?;
+            let uncomp = decompress_files(&archive)?;
+            self.for_each_archived(&uncomp, &metadata.file_table, |local_path, ent| {
+                let mut data = Vec::new();
+                ent.read_to_end(&mut data)?;
+                remote.insert(local_path.to_owned(), content_hash(&data));
+                Ok(())
+            })?;
+        }
+
+        let mut r = Verification::default();
+        let mut local = HashSet::new();
+        for f in &self.files {
+            let data = match fs::read(&f.local_path) {
+                Ok(d) => d,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to read {:?}", f.local_path));
+                }
+            };
+            local.insert(f.local_path.as_path());
+            match remote.get(&f.local_path) {
+                Some(hash) if *hash == content_hash(&data) => r.matching += 1,
+                Some(_) => r.differs.push(f.local_path.clone()),
+                None => r.not_uploaded.push(f.local_path.clone()),
+            }
+        }
+        r.missing_locally = remote
+            .into_keys()
+            .filter(|p| !local.contains(p.as_path()))
+            .collect();
+        Ok(r)
+    }
+
+    /// Files which would be uploaded, without touching the backend
+    ///
+    /// Registry exports are only written by a real upload, so they are listed if the hive has the
+    /// keys even when the export doesn't exist yet
+    pub fn upload_plan(&self) -> Result<Vec<PlannedTransfer>> {
+        let mut plan = Vec::new();
+        for f in &self.files {
+            let is_export = match self.registry.iter().find(|r| r.export_path == f.local_path) {
+                Some(r) => r.exported()?.is_some(),
+                None => false,
+            };
+            if is_export || fs::exists(&f.local_path)? {
+                plan.push(PlannedTransfer {
+                    local_path: f.local_path.clone(),
+                    remote_path: f.remote_path.clone(),
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Files in the remote archive which would be unpacked, and where to
+    ///
+    /// This is empty if nothing would be downloaded
+    pub async fn download_plan(&self, metadata: &SyncMetadata) -> Result<Vec<PlannedTransfer>> {
+        IncomaptibleCincVersionError::check_read(metadata)?;
+        if !self.rhaid_lawrlwytho(metadata).await? {
+            return Ok(Vec::new());
+        }
+        metadata
+            .file_table
+            .entries
+            .iter()
+            .map(|e| {
+                Ok(PlannedTransfer {
+                    local_path: self.localise(&e.template)?,
+                    remote_path: e.remote_path.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether none of the files in the remote's table exist here yet, e.g. on a new machine. Any
+    /// local files are then strays the game made which downloading won't overwrite
+    fn is_first_sync(&self, metadata: &SyncMetadata) -> Result<bool> {
+        if metadata.file_table.entries.is_empty() {
+            return Ok(false);
+        }
+        for entry in &metadata.file_table.entries {
+            let file = self.localise(&entry.template)?;
+            if self.files.iter().any(|f| f.local_path == file) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     pub async fn are_local_files_newer(
         &self,
         cloud_time: &SyncMetadata,
     ) -> Result<Option<SyncIssueInfo>> {
+        if self.is_first_sync(cloud_time)? {
+            info!(
+                "none of the saves of {} on '{}' exist locally yet, skipping the conflict check",
+                self.game_name, self.remote_name
+            );
+            return Ok(None);
+        }
         if let Some(newest_local) = self.get_latest_modified_time()? {
-            if newest_local > cloud_time.last_write_timestamp {
-                return Ok(Some(SyncIssueInfo {
-                    local_time: newest_local,
-                    remote_time: cloud_time.last_write_timestamp,
-                    remote_name: self.remote_name.to_owned(),
-                    remote_last_writer: cloud_time.last_write_hostname.clone(),
-                }));
+            if newest_local - cloud_time.last_write_timestamp > self.conflict_skew {
+                return Ok(Some(self.issue_info(newest_local, cloud_time)));
             }
         }
         Ok(None)
     }
 
+    /// Describe a conflict between the local files and the remote ones
+    pub fn sync_issue(&self, cloud_time: &SyncMetadata) -> Result<SyncIssueInfo> {
+        let newest_local = self.get_latest_modified_time()?.unwrap_or_else(Utc::now);
+        Ok(self.issue_info(newest_local, cloud_time))
+    }
+
+    fn issue_info(&self, newest_local: DateTime<Utc>, cloud_time: &SyncMetadata) -> SyncIssueInfo {
+        SyncIssueInfo {
+            local_time: newest_local,
+            remote_time: cloud_time.last_write_timestamp,
+            remote_name: self.remote_name.to_owned(),
+            remote_last_writer: cloud_time.last_write_hostname.clone(),
+            recent_writers: cloud_time.last_writers.clone(),
+        }
+    }
+
     pub async fn download(
         &self,
         backend: &StorageBackend<'_>,
@@ -268,6 +920,7 @@ impl<'f> SyncMgr<'f> {
         metadata: &SyncMetadata,
     ) -> Result<Option<SyncChoices>> {
         info!("downloading files from cloud...");
+        IncomaptibleCincVersionError::check_read(metadata)?;
         // check that we are not overwriting anything
         debug_assert!(force_overwrite || self.are_local_files_newer(metadata).await?.is_none());
         if !self.rhaid_lawrlwytho(metadata).await? {
@@ -275,37 +928,407 @@ impl<'f> SyncMgr<'f> {
             return Ok(None);
         }
 
-        let ap = Path::new(ARCHIVE_NAME);
-        if !backend.exists(ap).await? {
-            debug!("...nothing to do");
-            return Ok(None);
-        }
+        self.observer.event(SyncEvent::SyncStarted {
+            game: self.game_name.to_owned(),
+            direction: SyncDirection::Download,
+        });
+        if metadata.incremental {
+            self.backup_local_files(&metadata.file_table)?;
+            self.download_incremental(backend, metadata).await?;
+        } else {
+            let ap = Path::new(ARCHIVE_NAME);
+            if !backend.exists(ap).await? {
+                debug!("...nothing to do");
+                return Ok(None);
+            }
 
-        let archive = backend.read_file(ap).await?;
-        let uncomp = self.decompress_files(&archive)?;
-        self.untar_files(&uncomp, &metadata.file_table)?;
+            let archive = backend.read_file(ap).await?;
+            self.observer
+                .event(SyncEvent::Downloading(archive.len() as u64));
+            self.unpack_archive(&archive, metadata)?;
+        }
+        for r in &self.registry {
+            r.import()?;
+        }
+        self.observer.event(SyncEvent::Finished {
+            game: self.game_name.to_owned(),
+            direction: SyncDirection::Download,
+        });
 
         Ok(None)
     }
-    pub async fn upload(&self, backend: &StorageBackend<'_>) -> Result<()> {
-        info!("uploading files to cloud...");
+    /// Send events about the sync to `observer` rather than ignoring them
+    pub fn with_observer(mut self, observer: Arc<dyn SyncObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
 
-        let latest_write = SyncMetadata::from_sys_info(self.build_file_table()?);
-        // need to do this before any of the others
-        backend.write_sync_time(&latest_write).await?;
+    /// Check an archive matches `metadata` then unpack it over the local files, which are backed
+    /// up first
+    fn unpack_archive(&self, archive: &[u8], metadata: &SyncMetadata) -> Result<()> {
+        CorruptArchiveError::check(archive, metadata)
+            .context("local files have not been changed")?;
+        let uncomp = decompress_files(archive)?;
+        self.verify_archive(&uncomp, &metadata.file_table).context(
+            "archive on the backend is incomplete or corrupt, local files have not been changed",
+        )?;
+        self.backup_local_files(&metadata.file_table)?;
+        self.untar_files(&uncomp, &metadata.file_table)
+    }
 
+    /// Upload the files to [`CONFIG_ARCHIVE_NAME`], for a [`SyncMgr`] made with
+    /// [`SyncOptions::config_files`]
+    pub async fn upload_config(&self, backend: &StorageBackend<'_>) -> Result<()> {
+        if self.files.is_empty() {
+            debug!("no config files to upload");
+            return Ok(());
+        }
+        info!("uploading config files to cloud...");
+        if let Some(previous) = backend.read_config_meta().await? {
+            IncomaptibleCincVersionError::check_write(&previous)?;
+        }
+        // the table decides which files are skipped so it has to come before the archive
+        let table = self.build_file_table(true)?;
         let archive = self.compress_files()?;
-
+        let hash = content_hash(&archive);
+        let metadata = SyncMetadata::from_sys_info(table, hash);
         backend
-            .write_file(Path::new(ARCHIVE_NAME), &archive)
+            .write_file_owned(Path::new(CONFIG_ARCHIVE_NAME), archive)
             .await?;
-
+        backend.write_config_meta(&metadata).await?;
         Ok(())
     }
 
-    fn untar_files(&self, from: &[u8], metadata: &FileMetaTable) -> Result<()> {
-        let mut archive = tar::Archive::new(from);
-        let entries = archive.entries()?;
+    /// Unpack [`CONFIG_ARCHIVE_NAME`] over the local config files, returns how many files it had
+    pub async fn restore_config(&self, backend: &StorageBackend<'_>) -> Result<usize> {
+        let Some(metadata) = backend.read_config_meta().await? else {
+            bail!("no config files have been uploaded for {}", self.game_name);
+        };
+        IncomaptibleCincVersionError::check_read(&metadata)?;
+        info!("restoring config files from cloud...");
+        let archive = backend.read_file(Path::new(CONFIG_ARCHIVE_NAME)).await?;
+        self.unpack_archive(&archive, &metadata)?;
+        Ok(metadata.file_table.entries.len())
+    }
+
+    /// Copy the local files a download would overwrite into a new timestamped directory under
+    /// [`SyncOptions::backup_dir`], removing the oldest backups past [`SyncOptions::max_backups`]
+    ///
+    /// Returns where they were copied to, if there was anything to back up
+    fn backup_local_files(&self, metadata: &FileMetaTable) -> Result<Option<PathBuf>> {
+        let Some(backup_dir) = &self.backup_dir else {
+            return Ok(None);
+        };
+        let game_dir = backup_dir.join(self.game_name);
+        let dest = game_dir.join(Local::now().format("%Y%m%d-%H%M%S").to_string());
+        let mut any = false;
+        for entry in &metadata.entries {
+            let local_path = self.localise(&entry.template)?;
+            if !local_path.is_file() {
+                continue;
+            }
+            let to = dest.join(&entry.remote_path);
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&local_path, &to)?;
+            any = true;
+        }
+        if !any {
+            return Ok(None);
+        }
+        info!("backed up the local saves to {dest:?} before overwriting them");
+
+        let mut backups = fs::read_dir(&game_dir)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        // named by timestamp so the oldest sort first
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.max_backups.max(1));
+        for old in &backups[..excess] {
+            debug!("removing old backup {old:?}");
+            fs::remove_dir_all(old)?;
+        }
+        Ok(Some(dest))
+    }
+
+    /// Download the files that differ from the local ones for an incremental sync
+    async fn download_incremental(
+        &self,
+        backend: &StorageBackend<'_>,
+        metadata: &SyncMetadata,
+    ) -> Result<()> {
+        let mut changed = Vec::new();
+        for entry in &metadata.file_table.entries {
+            let local_path = self.localise(&entry.template)?;
+            if let Some(hash) = &entry.hash {
+                if fs::exists(&local_path)? && &content_hash(&fs::read(&local_path)?) == hash {
+                    debug!("{local_path:?} is unchanged, not downloading it");
+                    continue;
+                }
+            }
+            changed.push((entry, local_path));
+        }
+        stream::iter(changed)
+            .map(|(entry, local_path)| async move {
+                debug!("downloading {:?} to {local_path:?}...", entry.remote_path);
+                let data = backend
+                    .read_file(&Path::new(INCREMENTAL_DIR).join(&entry.remote_path))
+                    .await?;
+                self.observer
+                    .event(SyncEvent::Downloading(data.len() as u64));
+                if let Some(parent) = local_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_over_readonly(&local_path, || Ok(fs::write(&local_path, &data)?))
+            })
+            .buffer_unordered(self.max_concurrent_requests.max(1))
+            .try_collect::<()>()
+            .await
+    }
+
+    pub async fn upload(&self, backend: &StorageBackend<'_>, verify: bool) -> Result<()> {
+        self.export_registry()?;
+        self.check_upload_size()?;
+        self.observer.event(SyncEvent::SyncStarted {
+            game: self.game_name.to_owned(),
+            direction: SyncDirection::Upload,
+        });
+        self.upload_files(backend, verify).await?;
+        self.observer.event(SyncEvent::Finished {
+            game: self.game_name.to_owned(),
+            direction: SyncDirection::Upload,
+        });
+        Ok(())
+    }
+
+    async fn upload_files(&self, backend: &StorageBackend<'_>, verify: bool) -> Result<()> {
+        if self.incremental {
+            if !backend.supports_per_file_writes() {
+                debug!("backend doesn't support per-file writes, uploading an archive");
+            } else if self.files.len() > MAX_INCREMENTAL_FILES {
+                debug!(
+                    "{} files is too many for an incremental upload, uploading an archive",
+                    self.files.len()
+                );
+            } else {
+                return self.upload_incremental(backend, verify).await;
+            }
+        }
+        info!("uploading files to cloud...");
+
+        let previous = backend.read_sync_time().await?;
+        if let Some(previous) = &previous {
+            IncomaptibleCincVersionError::check_write(previous)?;
+        }
+        // the table decides which files are skipped so it has to come before the archive
+        let table = self.build_file_table(true)?;
+        time! {
+            "compressing files": {
+                let archive = self.compress_files()?;
+            }
+        }
+        let hash = content_hash(&archive);
+        let latest_write = SyncMetadata::from_sys_info(table, hash.clone());
+        let archive_len = archive.len();
+
+        // the archive must be written first so a failed upload doesn't leave metadata
+        // describing an archive that isn't there
+        backend
+            .write_file_owned(Path::new(ARCHIVE_NAME), archive)
+            .await?;
+        self.observer
+            .event(SyncEvent::Uploading(archive_len as u64));
+
+        if verify {
+            debug!("verifying uploaded archive...");
+            let remote = backend.read_file(Path::new(ARCHIVE_NAME)).await?;
+            let remote_hash = content_hash(&remote);
+            if remote_hash != hash {
+                bail!(
+                    "archive on the backend does not match what was uploaded (expected {hash}, got {remote_hash} with {} of {} bytes), the upload may have been truncated",
+                    remote.len(),
+                    archive_len
+                );
+            }
+        }
+
+        backend
+            .write_sync_time(&latest_write.with_history(previous.as_ref()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Error if the files are over [`SyncOptions::max_upload_size`], listing the largest ones
+    fn check_upload_size(&self) -> Result<()> {
+        let Some(max) = self.max_upload_size else {
+            return Ok(());
+        };
+        let mut sizes = Vec::new();
+        for f in &self.files {
+            match fs::metadata(&f.local_path) {
+                Ok(m) => sizes.push((&f.local_path, m.len())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let total: u64 = sizes.iter().map(|(_, s)| s).sum();
+        if total <= max {
+            return Ok(());
+        }
+        sizes.sort_by_key(|(_, s)| std::cmp::Reverse(*s));
+        bail!(
+            "refusing to upload {} of files as it is over the limit of {}, the largest are:\n{}\nif this is intended raise max_upload_size_mb in the config or launch with --allow-large-upload",
+            human_size(total),
+            human_size(max),
+            sizes
+                .iter()
+                .take(5)
+                .map(|(p, s)| format!("- {p:?} ({})", human_size(*s)))
+                .join("\n")
+        );
+    }
+
+    /// Upload only the files which have changed since the last incremental upload
+    async fn upload_incremental(&self, backend: &StorageBackend<'_>, verify: bool) -> Result<()> {
+        info!("uploading changed files to cloud...");
+        let previous = backend.read_sync_time().await?;
+        if let Some(previous) = &previous {
+            IncomaptibleCincVersionError::check_write(previous)?;
+        }
+        let remote_hashes: HashMap<&Path, &str> = previous
+            .iter()
+            .filter(|m| m.incremental)
+            .flat_map(|m| &m.file_table.entries)
+            .filter_map(|e| Some((e.remote_path.as_path(), e.hash.as_deref()?)))
+            .collect();
+        let table = self.build_file_table(true)?;
+
+        // like the archive, files must be written before the metadata that describes them
+        let changed = table.entries.iter().filter_map(|entry| {
+            let hash = entry.hash.as_deref().expect("hashes were requested");
+            if remote_hashes.get(entry.remote_path.as_path()) == Some(&hash) {
+                debug!("{:?} is unchanged, not uploading it", entry.remote_path);
+                None
+            } else {
+                Some((entry, hash))
+            }
+        });
+        stream::iter(changed)
+            .map(|(entry, hash)| async move {
+                let remote_path = Path::new(INCREMENTAL_DIR).join(&entry.remote_path);
+                debug!("uploading {remote_path:?}...");
+                let data = fs::read(self.localise(&entry.template)?)?;
+                let len = data.len();
+                backend.write_file_owned(&remote_path, data).await?;
+                self.observer.event(SyncEvent::Uploading(len as u64));
+                if verify {
+                    let remote_hash = content_hash(&backend.read_file(&remote_path).await?);
+                    if remote_hash != hash {
+                        bail!(
+                            "{remote_path:?} on the backend does not match what was uploaded (expected {hash}, got {remote_hash})"
+                        );
+                    }
+                }
+                Ok(())
+            })
+            .buffer_unordered(self.max_concurrent_requests.max(1))
+            .try_collect::<()>()
+            .await?;
+
+        backend
+            .write_sync_time(
+                &SyncMetadata::incremental_from_sys_info(table.clone())
+                    .with_history(previous.as_ref()),
+            )
+            .await?;
+
+        // clean up what is no longer described by the metadata, skipped files still exist locally
+        let skipped = self.skipped_files();
+        for (remote_path, _) in remote_hashes {
+            let is_skipped = self
+                .files
+                .iter()
+                .any(|f| f.remote_path == remote_path && skipped.contains(&f.local_path));
+            if !is_skipped && !table.entries.iter().any(|e| e.remote_path == remote_path) {
+                debug!("removing {remote_path:?} as it no longer exists locally");
+                if let Err(e) = backend
+                    .remove_file(&Path::new(INCREMENTAL_DIR).join(remote_path))
+                    .await
+                {
+                    warn!("failed to remove old file {remote_path:?}: {e}");
+                }
+            }
+        }
+        if previous.is_some_and(|m| !m.incremental)
+            && backend.exists(Path::new(ARCHIVE_NAME)).await?
+        {
+            debug!("removing archive from before switching to incremental uploads");
+            backend.remove_file(Path::new(ARCHIVE_NAME)).await?;
+        }
+        Ok(())
+    }
+
+    fn untar_files(&self, from: &[u8], metadata: &FileMetaTable) -> Result<()> {
+        self.for_each_archived(from, metadata, |local_path, ent| {
+            // it's "okay" that this is insecure because we trust the local path (it comes from the manifest)
+            write_over_readonly(local_path, || {
+                ent.unpack(local_path)?;
+                Ok(())
+            })
+        })
+    }
+
+    /// Check the archive has exactly the files in `metadata`, with matching hashes where they were
+    /// recorded, before anything is unpacked from it
+    fn verify_archive(&self, from: &[u8], metadata: &FileMetaTable) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut archive = tar::Archive::new(from);
+        for ent in archive.entries()? {
+            let mut ent = ent?;
+            let remote_path = ent.path()?.into_owned();
+            let Some(mfile) = metadata
+                .entries
+                .iter()
+                .find(|f| f.remote_path == remote_path)
+            else {
+                bail!("found in the archive that isn't in the metadata: {remote_path:?}");
+            };
+            let mut data = Vec::new();
+            ent.read_to_end(&mut data)?;
+            if let Some(expected) = &mfile.hash {
+                let hash = content_hash(&data);
+                if &hash != expected {
+                    bail!(
+                        "{remote_path:?} does not match the metadata (expected {expected}, got {hash})"
+                    );
+                }
+            }
+            seen.insert(remote_path);
+        }
+        if let Some(missing) = metadata
+            .entries
+            .iter()
+            .find(|f| !seen.contains(&f.remote_path))
+        {
+            bail!(
+                "{:?} is in the metadata but not the archive",
+                missing.remote_path
+            );
+        }
+        Ok(())
+    }
+
+    /// Call `f` with the local path of each file in an archive
+    fn for_each_archived(
+        &self,
+        from: &[u8],
+        metadata: &FileMetaTable,
+        mut f: impl FnMut(&Path, &mut tar::Entry<&[u8]>) -> Result<()>,
+    ) -> Result<()> {
+        let mut archive = tar::Archive::new(from);
+        let entries = archive.entries()?;
 
         for ent in entries {
             let mut ent = ent?;
@@ -318,42 +1341,156 @@ impl<'f> SyncMgr<'f> {
                 bail!("found in the archive that isn't in the metadata: {remote_path:?}");
             };
             // reconstruct the local path
-            let local_path = mfile.template.apply_substs(&self.local_info)?;
+            let local_path = self.localise(&mfile.template)?;
             debug!("unpacking {remote_path:?} from archive to {local_path:?}...",);
-
-            // it's "okay" that this is insecure because we trust the local path (it comes from the manifest)
-            ent.unpack(&local_path)?;
+            f(&local_path, &mut ent)?;
         }
         Ok(())
     }
 
-    fn decompress_files(&self, from: &[u8]) -> Result<Vec<u8>> {
-        let mut decoder = XzDecoder::new(from);
-        let mut buf = Vec::new();
-        decoder.read_to_end(&mut buf)?;
-        Ok(buf)
+    /// Write the saves to a zip with each file at its path on this machine, from the backend if
+    /// one is given otherwise from the local files. Returns how many files were written
+    pub async fn export_zip(
+        &self,
+        backend: Option<&StorageBackend<'_>>,
+        out: &Path,
+    ) -> Result<usize> {
+        let files = match backend {
+            Some(b) => self.read_remote_files(b).await?,
+            None => {
+                self.export_registry()?;
+                self.files
+                    .iter()
+                    .map(|f| {
+                        Ok(ExportFile {
+                            local_path: f.local_path.clone(),
+                            data: fs::read(&f.local_path)?,
+                            modified: Some(fs::metadata(&f.local_path)?.modified()?.into()),
+                        })
+                    })
+                    .collect::<Result<_>>()?
+            }
+        };
+        let mut zip = zip::ZipWriter::new(fs::File::create(out)?);
+        for f in &files {
+            let mut opts = SimpleFileOptions::default();
+            if let Some(t) = f.modified.and_then(zip_time) {
+                opts = opts.last_modified_time(t);
+            }
+            zip.start_file(zip_entry_name(&f.local_path), opts)?;
+            zip.write_all(&f.data)?;
+        }
+        zip.finish()?;
+        Ok(files.len())
+    }
+
+    async fn read_remote_files(&self, backend: &StorageBackend<'_>) -> Result<Vec<ExportFile>> {
+        let Some(metadata) = backend.read_sync_time().await? else {
+            bail!("there are no saves on the backend");
+        };
+        IncomaptibleCincVersionError::check_read(&metadata)?;
+        let mut files = Vec::new();
+        if metadata.incremental {
+            for entry in &metadata.file_table.entries {
+                files.push(ExportFile {
+                    local_path: self.localise(&entry.template)?,
+                    data: backend
+                        .read_file(&Path::new(INCREMENTAL_DIR).join(&entry.remote_path))
+                        .await?,
+                    modified: None,
+                });
+            }
+            return Ok(files);
+        }
+        let archive = backend.read_file(Path::new(ARCHIVE_NAME)).await?;
+        let uncomp = decompress_files(&archive)?;
+        self.for_each_archived(&uncomp, &metadata.file_table, |local_path, ent| {
+            let mut data = Vec::new();
+            ent.read_to_end(&mut data)?;
+            files.push(ExportFile {
+                local_path: local_path.to_owned(),
+                data,
+                modified: ent
+                    .header()
+                    .mtime()
+                    .ok()
+                    .and_then(|t| DateTime::from_timestamp(t as i64, 0)),
+            });
+            Ok(())
+        })?;
+        Ok(files)
     }
 
     fn compress_files(&self) -> Result<Vec<u8>> {
         let files = self.tar_files()?;
-        let mut encoder = XzEncoder::new(BufReader::new(files.as_slice()), XZ_LEVEL);
+        let threads = match self.compression_threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+            n => n,
+        };
+        debug!("compressing with {threads} threads");
+        // the decoder doesn't care how many threads the archive was made with
+        let stream = MtStreamBuilder::new()
+            .threads(threads)
+            .preset(XZ_LEVEL)
+            .check(Check::Crc64)
+            .encoder()?;
+        let progress = ui::byte_progress("compressing", Some(files.len() as u64));
+        let mut encoder =
+            XzEncoder::new_stream(progress.wrap_read(BufReader::new(files.as_slice())), stream);
         let mut out = Vec::new();
         encoder.read_to_end(&mut out)?;
+        progress.finish_and_clear();
         Ok(out)
     }
-    fn build_file_table(&self) -> Result<FileMetaTable> {
+
+    /// Files left out of the last upload because they couldn't be read, only ever non-empty with
+    /// [`SyncOptions::skip_unreadable_files`]
+    pub fn skipped_files(&self) -> Vec<PathBuf> {
+        self.skipped.lock().unwrap().clone()
+    }
+
+    /// Build the metadata for the files which exist, optionally hashing them
+    ///
+    /// Files which can't be read are recorded in [`Self::skipped_files`] and left out if they are
+    /// being skipped
+    fn build_file_table(&self, hash: bool) -> Result<FileMetaTable> {
+        self.skipped.lock().unwrap().clear();
+        time! {
+            "reading file metadata": {
+                let stats = self
+                    .files
+                    .par_iter()
+                    .map(|file| {
+                        let stat = || -> std::io::Result<_> {
+                            let mod_time = DateTime::<Utc>::from(fs::metadata(&file.local_path)?.modified()?);
+                            let hash = if hash {
+                                Some(content_hash(&fs::read(&file.local_path)?))
+                            } else {
+                                None
+                            };
+                            Ok(Some((file, mod_time, hash)))
+                        };
+                        match stat() {
+                            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                            Err(e) if self.skip_unreadable_files => {
+                                warn!("skipping {:?} as it couldn't be read: {e}", file.local_path);
+                                self.skipped.lock().unwrap().push(file.local_path.clone());
+                                Ok(None)
+                            }
+                            r => r,
+                        }
+                    })
+                    .collect::<Result<Vec<_>, std::io::Error>>()?;
+            }
+        }
         let mut entries = Vec::new();
         let mut oldest_modified_time = Local::now().to_utc();
-        for file in self
-            .files
-            .iter()
-            .filter(|e| std::fs::exists(&e.local_path).unwrap())
-        {
+        for (file, mod_time, hash) in stats.into_iter().flatten() {
             entries.push(FileMetaEntry {
                 template: file.template.to_owned(),
                 remote_path: file.remote_path.clone(),
+                hash,
             });
-            let mod_time = DateTime::<Utc>::from(fs::metadata(&file.local_path)?.modified()?);
             if mod_time < oldest_modified_time {
                 oldest_modified_time = mod_time;
             }
@@ -367,6 +1504,7 @@ impl<'f> SyncMgr<'f> {
 
     fn tar_files(&self) -> Result<Vec<u8>> {
         let mut b = tar::Builder::new(Vec::new());
+        let skipped = self.skipped_files();
 
         for FileInfo {
             local_path,
@@ -374,7 +1512,9 @@ impl<'f> SyncMgr<'f> {
             ..
         } in &self.files
         {
-            if fs::exists(local_path)? {
+            if skipped.contains(local_path) {
+                debug!("not uploading {local_path:?} because it couldn't be read");
+            } else if fs::exists(local_path)? {
                 debug!("adding {local_path:?} to the archive...");
                 b.append_path_with_name(local_path, remote_path)?;
             } else {
@@ -385,15 +1525,514 @@ impl<'f> SyncMgr<'f> {
     }
 }
 
-#[allow(unused)]
+/// Whether files with `tags` are synced with these options, see [`SyncOptions::config_files`]
+fn is_synced(tags: &[FileTag], opts: &SyncOptions) -> bool {
+    if opts.config_files {
+        tags.contains(&FileTag::Config) && !tags.contains(&FileTag::Save)
+    } else {
+        tags.contains(&FileTag::Save)
+    }
+}
+
+/// Work out `<base>` from the executable being run and a launch template for it
+///
+/// None if the template isn't under `<base>` or the executable doesn't match it. Windows paths
+/// in the manifest often differ in case from the install so this ignores case
 fn find_base_dir_from_exe_path<'p>(template: &TemplatePath, command: &'p Path) -> Option<&'p Path> {
     let comps = template.as_raw_path().iter().collect_vec();
     if comps.first().copied() != Some(std::ffi::OsStr::new("<base>")) {
         debug!("failed to find base dir from exe path as template path does not contain base dir");
         return None;
     }
-    let p = comps.iter().skip(1).fold(PathBuf::new(), |p, c| p.join(c));
-    Some(paths::extract_prefix(command, &p))
+    let rest = &comps[1..];
+    let command_comps = command.iter().collect_vec();
+    if rest.is_empty() || rest.len() >= command_comps.len() {
+        return None;
+    }
+    let matches = command_comps
+        .iter()
+        .rev()
+        .zip(rest.iter().rev())
+        .all(|(a, b)| a.eq_ignore_ascii_case(b));
+    matches
+        .then(|| command.ancestors().nth(rest.len()))
+        .flatten()
+}
+
+/// Find `<base>` by matching the executable against the game's launch entries
+fn base_dir_from_launch(manifest: &GameManifest, exe_path: &Path) -> Option<PathBuf> {
+    let exe_path = std::path::absolute(exe_path).ok()?;
+    // the deepest base is the most specific match
+    let base = manifest
+        .launch
+        .keys()
+        .filter_map(|t| find_base_dir_from_exe_path(t, &exe_path))
+        .max_by_key(|b| b.components().count())?;
+    debug!("found the base dir {base:?} from the executable {exe_path:?}");
+    Some(base.to_owned())
+}
+
+/// Local template info for a game installed through steam
+pub fn steam_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    app_id: SteamId,
+    opts: &SyncOptions,
+) -> Result<TemplateInfo> {
+    let (steam_app_manifest, steam_app_lib) = find_steam_app(app_id.id())?
+        .ok_or_else(|| anyhow!("could not find steam app with id '{app_id}'"))?;
+
+    let store_user_id = steam_app_manifest
+        .last_user
+        .map(SteamId64::new)
+        .map(|id| id.to_id3().to_string());
+    let steam_user_data = match &store_user_id {
+        Some(id3) => Some(
+            paths::steam_dir()?
+                .path()
+                .join("userdata")
+                .join(id3)
+                .join(app_id.to_string()),
+        ),
+        None => None,
+    };
+    let (win_prefix, win_user) = windows_host().unwrap_or_else(|| {
+        (
+            steam_app_lib
+                .path()
+                .join("steamapps")
+                .join("compatdata")
+                .join(app_id.to_string())
+                .join("pfx")
+                .join("drive_c"),
+            "steamuser".to_owned(),
+        )
+    });
+    let base_dir = steam_app_lib.resolve_app_dir(&steam_app_manifest);
+    let install_dir = game_install_dir(manifest, game_name, base_dir.parent());
+    Ok(TemplateInfo {
+        win_prefix: Some(win_prefix),
+        win_user,
+        base_dir: Some(base_dir),
+        root: Some(steam_app_lib.path().to_owned()),
+        store_user_id,
+
+        home_dir: None,
+        xdg_config: None,
+        xdg_data: None,
+        install_dir: Some(install_dir),
+        steam_user_data,
+        custom_vars: opts.template_vars.clone(),
+    })
+}
+
+/// Local template info for a game run by umu, e.g. through heroic
+pub fn umu_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    exe_path: &Path,
+    opts: &SyncOptions,
+) -> Result<TemplateInfo> {
+    // TODO: fallback to the umu id here https://umu.openwinecomponents.org/
+    let wine_prefix = std::env::var("WINEPREFIX")
+        .map_err(|_| anyhow!("WINEPREFIX not set, can't find the prefix of the umu game"))?;
+    let wine_prefix = Path::new(&wine_prefix);
+    // we need to work out the base dir using a little magic
+    let heroic_install = heroic_install_path();
+    let root_dir = if let Some(install) = &heroic_install {
+        let r = install.parent().map(Path::to_path_buf);
+        debug!("found the install in heroic's config, setting the root dir to {r:?}");
+        r
+    } else if are_we_launched_by_heroic() {
+        let r = dirs::home_dir().map(|h| h.join("Games").join("Heroic"));
+        debug!("we are running under heroic, setting the root dir to {r:?}");
+        r
+    } else {
+        debug!(
+            "not sure what launcher we're running under, can't set the root dir let's hope that's okay"
+        );
+        None
+    };
+
+    // the exe being launched is the real install, heroic's config is only a guess at which game
+    // this is
+    let base_dir = base_dir_from_launch(manifest, exe_path).or(heroic_install);
+    let install_dir = game_install_dir(
+        manifest,
+        game_name,
+        base_dir
+            .as_deref()
+            .and_then(Path::parent)
+            .or(root_dir.as_deref()),
+    );
+
+    Ok(TemplateInfo {
+        win_prefix: Some(wine_prefix.join("pfx").join("drive_c")),
+        win_user: "steamuser".to_owned(),
+        base_dir,
+        root: root_dir,
+        store_user_id: None,
+
+        home_dir: Some(
+            wine_prefix
+                .join("pfx")
+                .join("drive_c")
+                .join("users")
+                .join("steamuser"),
+        ),
+        xdg_config: None,
+        xdg_data: None,
+        install_dir: Some(install_dir),
+        steam_user_data: None,
+        custom_vars: opts.template_vars.clone(),
+    })
+}
+
+/// Local template info for a game run by lutris
+pub fn lutris_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    opts: &SyncOptions,
+) -> Result<TemplateInfo> {
+    let wine_prefix = std::env::var("WINEPREFIX")
+        .map_err(|_| anyhow!("WINEPREFIX not set, is this a lutris wine game?"))?;
+    // lutris prefixes are plain wine prefixes so drive_c is at the top level
+    let drive_c = Path::new(&wine_prefix).join("drive_c");
+    let win_user = prefix_wine_user(&drive_c);
+    let root_dir = dirs::home_dir().map(|h| h.join("Games"));
+    debug!("we are running under lutris, setting the root dir to {root_dir:?}");
+    let install_dir = game_install_dir(manifest, game_name, root_dir.as_deref());
+
+    Ok(TemplateInfo {
+        home_dir: Some(drive_c.join("users").join(&win_user)),
+        win_prefix: Some(drive_c),
+        win_user,
+        base_dir: None,
+        root: root_dir,
+        store_user_id: None,
+
+        xdg_config: None,
+        xdg_data: None,
+        install_dir: Some(install_dir),
+        steam_user_data: None,
+        custom_vars: opts.template_vars.clone(),
+    })
+}
+
+/// Local template info for a game run with `bottles-cli`
+pub fn bottles_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    bottle: &str,
+    exe_path: &Path,
+    opts: &SyncOptions,
+) -> Result<TemplateInfo> {
+    // bottles sets WINEPREFIX for the game but usually not for whatever is wrapping bottles-cli
+    let prefix = match std::env::var_os("WINEPREFIX") {
+        Some(p) => PathBuf::from(p),
+        None => bottles_prefix(bottle).ok_or_else(|| {
+            anyhow!("failed to find the prefix for bottle '{bottle}', try setting WINEPREFIX")
+        })?,
+    };
+    debug!("using the prefix {prefix:?} for bottle '{bottle}'");
+    // bottles are plain wine prefixes like lutris ones
+    let drive_c = prefix.join("drive_c");
+    let win_user = prefix_wine_user(&drive_c);
+
+    let base_dir = base_dir_from_launch(manifest, exe_path);
+    let install_dir = game_install_dir(
+        manifest,
+        game_name,
+        base_dir.as_deref().and_then(Path::parent),
+    );
+    Ok(TemplateInfo {
+        home_dir: Some(drive_c.join("users").join(&win_user)),
+        win_prefix: Some(drive_c),
+        win_user,
+        base_dir,
+        root: None,
+        store_user_id: None,
+
+        xdg_config: None,
+        xdg_data: None,
+        install_dir: Some(install_dir),
+        steam_user_data: None,
+        custom_vars: opts.template_vars.clone(),
+    })
+}
+
+/// The system drive and user when running on windows, these take the place of a wine prefix
+fn windows_host() -> Option<(PathBuf, String)> {
+    if !cfg!(windows) {
+        return None;
+    }
+    let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_owned());
+    let user = std::env::var("USERNAME").ok()?;
+    Some((PathBuf::from(format!("{drive}\\")), user))
+}
+
+/// Local template info for a game running natively, i.e. without wine
+///
+/// Everything except the game dir is resolved from the system, on windows the
+/// windows variables are for the real system
+pub fn native_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    opts: &SyncOptions,
+) -> TemplateInfo {
+    let (win_prefix, win_user) = windows_host().unzip();
+    TemplateInfo {
+        win_prefix,
+        win_user: win_user.unwrap_or_default(),
+        base_dir: None,
+        root: None,
+        store_user_id: None,
+
+        home_dir: None,
+        xdg_config: None,
+        xdg_data: None,
+        install_dir: Some(game_install_dir(manifest, game_name, None)),
+        steam_user_data: None,
+        custom_vars: opts.template_vars.clone(),
+    }
+}
+
+/// Work out the local template info for a game without launching it
+///
+/// If the game is installed through steam then that is used, otherwise it is assumed to be native
+pub fn detect_local_template_info(
+    game_name: &str,
+    manifest: &GameManifest,
+    opts: &SyncOptions,
+) -> Result<(PlatformOpt, TemplateInfo)> {
+    if let Some(steam) = &manifest.steam {
+        match find_steam_app(steam.id.id()) {
+            Ok(Some(_)) => {
+                return Ok((
+                    PlatformOpt::Steam,
+                    steam_template_info(game_name, manifest, steam.id, opts)?,
+                ));
+            }
+            Ok(None) => debug!("{game_name} has a steam id but is not installed through steam"),
+            // e.g. steam isn't installed at all
+            Err(e) => debug!("{game_name} has a steam id but steam could not be searched: {e}"),
+        }
+    }
+    Ok((
+        PlatformOpt::Native,
+        native_template_info(game_name, manifest, opts),
+    ))
+}
+
+/// Run `write` to replace `path`, if it is read-only it is made writable for it and then made
+/// read-only again afterwards. Some games mark their saves read-only
+fn write_over_readonly(path: &Path, write: impl FnOnce() -> Result<()>) -> Result<()> {
+    let perms = match fs::metadata(path) {
+        Ok(m) if m.is_file() && m.permissions().readonly() => m.permissions(),
+        Ok(_) => return write(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return write(),
+        Err(e) => return Err(e.into()),
+    };
+    debug!("{path:?} is read-only, making it writable to overwrite it");
+    let mut writable = perms.clone();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // only for the owner, set_readonly(false) would make it writable by everyone
+        writable.set_mode(writable.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    writable.set_readonly(false);
+    fs::set_permissions(path, writable)?;
+    let r = write();
+    if let Err(e) = fs::set_permissions(path, perms) {
+        warn!("failed to make {path:?} read-only again: {e}");
+    }
+    r
+}
+
+fn decompress_files(from: &[u8]) -> Result<Vec<u8>> {
+    let progress = ui::byte_progress("decompressing", Some(from.len() as u64));
+    let mut decoder = XzDecoder::new(progress.wrap_read(from));
+    let mut buf = Vec::new();
+    decoder.read_to_end(&mut buf)?;
+    progress.finish_and_clear();
+    Ok(buf)
+}
+
+/// Write the files on a backend to `dest` as they are stored there rather than where they would
+/// go on this machine, returning how many were written
+pub async fn extract_remote(backend: &StorageBackend<'_>, dest: &Path) -> Result<usize> {
+    let Some(metadata) = backend.read_sync_time().await? else {
+        bail!("there are no saves on the backend");
+    };
+    IncomaptibleCincVersionError::check_read(&metadata)?;
+    fs::create_dir_all(dest)?;
+    if metadata.incremental {
+        for entry in &metadata.file_table.entries {
+            if !entry
+                .remote_path
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                bail!(
+                    "refusing to extract {:?} as it is outside of the destination",
+                    entry.remote_path
+                );
+            }
+            let data = backend
+                .read_file(&Path::new(INCREMENTAL_DIR).join(&entry.remote_path))
+                .await?;
+            let to = dest.join(&entry.remote_path);
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(to, data)?;
+        }
+        return Ok(metadata.file_table.entries.len());
+    }
+    let archive = backend.read_file(Path::new(ARCHIVE_NAME)).await?;
+    let uncomp = decompress_files(&archive)?;
+    let mut n = 0;
+    for ent in tar::Archive::new(uncomp.as_slice()).entries()? {
+        let mut ent = ent?;
+        debug!("extracting {:?} to {dest:?}", ent.path()?);
+        // unpack_in refuses paths which would end up outside of dest
+        if ent.unpack_in(dest)? {
+            n += 1;
+        }
+    }
+    Ok(n)
+}
+
+fn human_size(bytes: u64) -> String {
+    format!("{:.1}MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Install dir from the manifest, falling back to the game name if it is missing or empty
+///
+/// If the manifest lists several the one that exists in `installed_under` is used, otherwise the
+/// first. The remote always uses the first so saves are in the same place whichever it is
+fn game_install_dir(
+    manifest: &GameManifest,
+    game_name: &str,
+    installed_under: Option<&Path>,
+) -> PathBuf {
+    let Some(dir) = &manifest.install_dir else {
+        return game_name.into();
+    };
+    installed_under
+        .and_then(|parent| dir.names().find(|n| parent.join(n).is_dir()))
+        .or_else(|| dir.name())
+        .unwrap_or(game_name)
+        .into()
+}
+
+/// Template substitutions used for the paths on the remote
+///
+/// These must be the same for every platform so that saves can be shared between them
+fn remote_template_info(
+    install_dir: Option<PathBuf>,
+    store_user_id: Option<String>,
+    opts: &SyncOptions,
+) -> TemplateInfo {
+    TemplateInfo {
+        win_prefix: Some(PathBuf::from("win_prefix")),
+        win_user: "steamuser".to_owned(),
+        base_dir: Some("base_dir".into()),
+        root: Some("steam_root".into()),
+        store_user_id,
+
+        home_dir: Some("home_dir".into()),
+        xdg_config: Some("xdg_config".into()),
+        xdg_data: Some("xdg_data".into()),
+        install_dir,
+        steam_user_data: Some("steam_user_data".into()),
+        // the values are local so just use the name
+        custom_vars: opts
+            .template_vars
+            .keys()
+            .map(|k| (k.clone(), PathBuf::from(k)))
+            .collect(),
+    }
+}
+
+/// Where heroic installed the game we were launched for, if heroic launched us
+fn heroic_install_path() -> Option<PathBuf> {
+    let source = std::env::var(HEROIC_APP_SOURCE).ok()?;
+    let name = std::env::var(HEROIC_APP_NAME).ok()?;
+    heroic::find_install_path(&source, &name)
+}
+
+/// Name of a file in an exported zip, its absolute path without the root as zips can't have
+/// absolute paths. Windows drives are kept as a directory, e.g. `C:\\Users` is `C/Users`
+fn zip_entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Prefix(p) => Some(
+                p.as_os_str()
+                    .to_string_lossy()
+                    .trim_end_matches(':')
+                    .to_owned(),
+            ),
+            Component::RootDir | Component::CurDir => None,
+            c => Some(c.as_os_str().to_string_lossy().into_owned()),
+        })
+        .join("/")
+}
+
+/// Zips store times in local time without a timezone
+fn zip_time(time: DateTime<Utc>) -> Option<zip::DateTime> {
+    let t = time.with_timezone(&Local).naive_local();
+    zip::DateTime::from_date_and_time(
+        t.year().try_into().ok()?,
+        t.month() as u8,
+        t.day() as u8,
+        t.hour() as u8,
+        t.minute() as u8,
+        t.second() as u8,
+    )
+    .ok()
+}
+
+/// `path` relative to `base`, ignoring the case of the components
+fn strip_prefix_ignore_case(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut comps = path.components();
+    for b in base.components() {
+        if !comps
+            .next()?
+            .as_os_str()
+            .eq_ignore_ascii_case(b.as_os_str())
+        {
+            return None;
+        }
+    }
+    Some(comps.collect())
+}
+
+/// Wine user of a plain wine prefix, this is the login name unless it was made by umu
+fn prefix_wine_user(drive_c: &Path) -> String {
+    if drive_c.join("users").join("steamuser").is_dir() {
+        "steamuser".to_owned()
+    } else {
+        std::env::var("USER").unwrap_or_else(|_| "steamuser".to_owned())
+    }
+}
+
+/// Directory of a bottle, from either the flatpak or a native install of bottles
+fn bottles_prefix(bottle: &str) -> Option<PathBuf> {
+    let flatpak = dirs::home_dir().map(|h| {
+        h.join(".var")
+            .join("app")
+            .join("com.usebottles.bottles")
+            .join("data")
+    });
+    [flatpak, dirs::data_dir()]
+        .into_iter()
+        .flatten()
+        .map(|d| d.join("bottles").join("bottles").join(bottle))
+        .find(|p| p.is_dir())
 }
 
 /// Try and work out if we were launched by heroic
@@ -403,9 +2042,115 @@ fn are_we_launched_by_heroic() -> bool {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{
+        io::Read,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use std::time::Duration;
+
+    use assert_fs::{TempDir, fixture::ChildPath, prelude::*};
+    use chrono::{TimeDelta, Utc};
+    use mockito::Matcher;
+    use test_log::test;
+
+    use crate::{
+        args::PlatformOpt,
+        backends::{
+            FileMetaEntry, FileMetaTable, INCREMENTAL_DIR, StorageBackend, SyncMetadata,
+            filesystem::FilesystemStore, webdav::WebDavStore,
+        },
+        config::{SteamId, WebDavAuth, WebDavInfo},
+        curr_crate_ver,
+        events::{SyncEvent, SyncObserver},
+        manifest::{FileConfig, FileTag, GameManifest, SteamInfo, TemplatePath},
+        platform::IncomaptibleCincVersionError,
+        secrets::SecretsApi,
+        sync::{
+            ARCHIVE_NAME, CorruptArchiveError, PlannedTransfer, SyncMgr, SyncOptions, Verification,
+            base_dir_from_launch, bottles_prefix, detect_local_template_info, extract_remote,
+            find_base_dir_from_exe_path, game_install_dir, zip_entry_name,
+        },
+    };
+
+    /// Manifest with a single save dir at `<saves>`
+    fn save_dir_manifest() -> GameManifest {
+        GameManifest {
+            files: [(
+                TemplatePath::new("<saves>"),
+                FileConfig {
+                    preds: vec![],
+                    tags: vec![FileTag::Save],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn save_dir_opts(saves: &ChildPath) -> SyncOptions {
+        SyncOptions {
+            template_vars: [("saves".to_owned(), saves.to_path_buf())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detect_template_info_without_steam_is_native() {
+        let (platform, info) = detect_local_template_info(
+            "Some Game",
+            &GameManifest::default(),
+            &SyncOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(platform, PlatformOpt::Native);
+        assert!(info.win_prefix.is_none());
+        assert_eq!(info.install_dir, Some("Some Game".into()));
+    }
+
+    #[test]
+    fn detect_template_info_when_steam_cant_be_found_is_native() {
+        let home = TempDir::new().unwrap();
+        let manifest = GameManifest {
+            steam: Some(SteamInfo {
+                id: SteamId::new(0),
+            }),
+            ..Default::default()
+        };
+        temp_env::with_vars(
+            [
+                ("HOME", Some(home.path().as_os_str())),
+                ("XDG_DATA_HOME", Some(home.child("data").path().as_os_str())),
+            ],
+            || {
+                let (platform, _) =
+                    detect_local_template_info("Some Game", &manifest, &SyncOptions::default())
+                        .unwrap();
+                assert_eq!(platform, PlatformOpt::Native);
+            },
+        );
+    }
 
-    use crate::{manifest::TemplatePath, sync::find_base_dir_from_exe_path};
+    #[test]
+    fn bottles_prefix_is_found_in_flatpak_dir() {
+        let home = TempDir::new().unwrap();
+        let bottle = home.child(".var/app/com.usebottles.bottles/data/bottles/bottles/Games");
+        bottle.create_dir_all().unwrap();
+        temp_env::with_vars(
+            [
+                ("HOME", Some(home.path().as_os_str())),
+                ("XDG_DATA_HOME", Some(home.child("data").path().as_os_str())),
+            ],
+            || {
+                assert_eq!(bottles_prefix("Games"), Some(bottle.to_path_buf()));
+                assert_eq!(bottles_prefix("Missing"), None);
+            },
+        );
+    }
 
     #[test]
     fn get_base_path_from_exe() {
@@ -416,4 +2161,858 @@ mod tests {
             Some(Path::new("woah/so-cool"))
         )
     }
+
+    #[test]
+    fn install_dir_is_the_one_that_exists() {
+        let manifest: GameManifest =
+            serde_yaml::from_str("installDir:\n  Game: {}\n  Game Deluxe: {}\n").unwrap();
+        let games = TempDir::new().unwrap();
+        assert_eq!(
+            game_install_dir(&manifest, "test", Some(games.path())),
+            Path::new("Game")
+        );
+        games.child("Game Deluxe").create_dir_all().unwrap();
+        assert_eq!(
+            game_install_dir(&manifest, "test", Some(games.path())),
+            Path::new("Game Deluxe")
+        );
+        // the remote doesn't know where it is installed
+        assert_eq!(game_install_dir(&manifest, "test", None), Path::new("Game"));
+    }
+
+    #[test]
+    fn base_dir_from_launch_uses_matching_entry() {
+        let manifest: GameManifest =
+            serde_yaml::from_str("launch:\n  <base>/Game.exe: []\n  <base>/tools/Editor.exe: []\n")
+                .unwrap();
+        assert_eq!(
+            base_dir_from_launch(&manifest, Path::new("/games/Game/tools/editor.exe")),
+            Some("/games/Game".into())
+        );
+        assert_eq!(
+            base_dir_from_launch(&manifest, Path::new("/games/Game/launcher.exe")),
+            None
+        );
+    }
+
+    #[test]
+    fn base_path_from_exe_ignores_case_but_not_other_exes() {
+        let template = TemplatePath::new("<base>/Bin/Game.exe");
+        assert_eq!(
+            find_base_dir_from_exe_path(&template, Path::new("/games/Some Game/bin/game.exe")),
+            Some(Path::new("/games/Some Game"))
+        );
+        assert_eq!(
+            find_base_dir_from_exe_path(&template, Path::new("/games/Some Game/tools/game.exe")),
+            None
+        );
+        assert_eq!(
+            find_base_dir_from_exe_path(&template, Path::new("game.exe")),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_outside_save_dir_are_skipped() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("inner").write_str("hmm").unwrap();
+        root.child("outside").write_str("hmm").unwrap();
+        std::os::unix::fs::symlink(root.child("outside"), saves.child("escaped")).unwrap();
+        std::os::unix::fs::symlink(saves.child("inner"), saves.child("linked")).unwrap();
+
+        let manifest = save_dir_manifest();
+        let mk = |follow_symlinks| {
+            let opts = SyncOptions {
+                follow_symlinks,
+                ..save_dir_opts(&saves)
+            };
+            let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+            let mut files = mgr
+                .files
+                .iter()
+                .map(|f| f.local_path.file_name().unwrap().to_owned())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        assert_eq!(mk(false), vec!["inner"]);
+        assert_eq!(mk(true), vec!["inner", "linked"]);
+    }
+
+    #[test]
+    fn upload_plan_lists_save_files() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a").write_str("hmm").unwrap();
+        saves.child("dir").child("b").write_str("hmm").unwrap();
+
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        // files are sorted so the archive is deterministic
+        let plan = mgr.upload_plan().unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                PlannedTransfer {
+                    local_path: saves.child("a").to_path_buf(),
+                    remote_path: "saves/a".into(),
+                },
+                PlannedTransfer {
+                    local_path: saves.child("dir").child("b").to_path_buf(),
+                    remote_path: "saves/dir/b".into(),
+                },
+            ]
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn failed_archive_upload_does_not_write_metadata() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("save").write_str("hmm").unwrap();
+        let manifest = save_dir_manifest();
+        let mgr =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let _no_meta = server
+            .mock("GET", "/mod-meta.ron")
+            .with_status(404)
+            .create_async()
+            .await;
+        let _get = server
+            .mock("GET", Matcher::Any)
+            .with_status(200)
+            .create_async()
+            .await;
+        let archive = server
+            .mock("PUT", "/archive.tar.xz")
+            .with_status(403)
+            .create_async()
+            .await;
+        let meta = server
+            .mock("PUT", Matcher::Regex("mod-meta".to_owned()))
+            .expect(0)
+            .create_async()
+            .await;
+        let s = SecretsApi::new_unavailable();
+        let backend = StorageBackend::new(
+            WebDavStore::new(
+                WebDavInfo {
+                    url: server.url(),
+                    username: "".to_owned(),
+                    psk: None,
+                    root: "".into(),
+                    auth: WebDavAuth::Basic,
+                    ca_cert_path: None,
+                    danger_accept_invalid_certs: false,
+                },
+                &s,
+                Duration::from_secs(5),
+            )
+            .unwrap(),
+        );
+
+        assert!(mgr.upload(&backend, false).await.is_err());
+        archive.assert_async().await;
+        meta.assert_async().await;
+    }
+
+    #[test(tokio::test)]
+    async fn conflicts_within_skew_are_ignored() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a").write_str("a").unwrap();
+        let manifest = save_dir_manifest();
+        let opts = SyncOptions {
+            conflict_skew: TimeDelta::seconds(60),
+            ..save_dir_opts(&saves)
+        };
+        let mut metadata = SyncMetadata::from_sys_info(
+            FileMetaTable {
+                entries: Vec::new(),
+                oldest_modified_time: Utc::now(),
+            },
+            String::new(),
+        );
+        metadata.last_write_timestamp = Utc::now() - TimeDelta::seconds(30);
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        assert!(
+            mgr.are_local_files_newer(&metadata)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mgr =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+        assert!(
+            mgr.are_local_files_newer(&metadata)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn first_sync_has_no_conflict() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("stray").write_str("made by the game").unwrap();
+        let manifest = save_dir_manifest();
+        let mut metadata = SyncMetadata::from_sys_info(
+            FileMetaTable {
+                entries: vec![FileMetaEntry {
+                    template: TemplatePath::new("<saves>/save1".to_owned()),
+                    remote_path: "save1".into(),
+                    hash: None,
+                }],
+                oldest_modified_time: Utc::now(),
+            },
+            String::new(),
+        );
+        metadata.last_write_timestamp = Utc::now() - TimeDelta::days(1);
+
+        let mgr =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+        assert!(
+            mgr.are_local_files_newer(&metadata)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        saves.child("save1").write_str("local").unwrap();
+        let mgr =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+        assert!(
+            mgr.are_local_files_newer(&metadata)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn incremental_upload_skips_unchanged_files() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a").write_str("a").unwrap();
+        saves.child("b").write_str("b").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = SyncOptions {
+            incremental: true,
+            ..save_dir_opts(&saves)
+        };
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, true).await.unwrap();
+        let remote_a = store.child(INCREMENTAL_DIR).child("saves").child("a");
+        let remote_b = store.child(INCREMENTAL_DIR).child("saves").child("b");
+        assert!(remote_a.exists());
+        assert!(!store.child(ARCHIVE_NAME).exists());
+
+        // if b was uploaded again it would reappear
+        std::fs::remove_file(&remote_b).unwrap();
+        saves.child("a").write_str("changed").unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&remote_a).unwrap(), "changed");
+        assert!(!remote_b.exists());
+    }
+
+    #[cfg(unix)]
+    #[test(tokio::test)]
+    async fn unreadable_files_are_skipped_when_asked() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a").write_str("a").unwrap();
+        saves.child("b").write_str("b").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let strict =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+        let opts = SyncOptions {
+            skip_unreadable_files: true,
+            ..save_dir_opts(&saves)
+        };
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+
+        // a symlink to itself fails to read even as root
+        let b = saves.child("b");
+        std::fs::remove_file(&b).unwrap();
+        std::os::unix::fs::symlink(b.path(), b.path()).unwrap();
+
+        assert!(strict.upload(&backend, false).await.is_err());
+        mgr.upload(&backend, true).await.unwrap();
+        assert_eq!(mgr.skipped_files(), [b.to_path_buf()]);
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        assert_eq!(
+            metadata
+                .file_table
+                .entries
+                .iter()
+                .map(|e| e.remote_path.file_name().unwrap())
+                .collect::<Vec<_>>(),
+            ["a"]
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn verify_reports_what_differs() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a").write_str("a").unwrap();
+        saves.child("b").write_str("b").unwrap();
+        saves.child("c").write_str("c").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        let r = mgr.verify(&backend).await.unwrap();
+        assert!(r.is_consistent());
+        assert_eq!(r.matching, 3);
+
+        saves.child("a").write_str("changed").unwrap();
+        std::fs::remove_file(saves.child("b")).unwrap();
+        saves.child("d").write_str("d").unwrap();
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        let r = mgr.verify(&backend).await.unwrap();
+        assert_eq!(
+            r,
+            Verification {
+                matching: 1,
+                differs: vec![saves.child("a").to_path_buf()],
+                missing_locally: vec![saves.child("b").to_path_buf()],
+                not_uploaded: vec![saves.child("d").to_path_buf()],
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(saves.child("a")).unwrap(),
+            "changed"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn incremental_download_restores_files() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("dir").child("a").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = SyncOptions {
+            incremental: true,
+            ..save_dir_opts(&saves)
+        };
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        std::fs::remove_dir_all(saves.child("dir")).unwrap();
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        assert!(metadata.incremental);
+        mgr.download(&backend, true, &metadata).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(saves.child("dir").child("a")).unwrap(),
+            "a"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn backups_are_mapped_onto_save_paths() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.create_dir_all().unwrap();
+        let backup = root.child("backup");
+        backup.child("b.sav").write_str("b").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+
+        let files = [
+            (saves.join("b.sav"), backup.join("b.sav")),
+            (PathBuf::from("/elsewhere/c.sav"), backup.join("c.sav")),
+        ];
+        let (mgr, unmatched) =
+            SyncMgr::from_backup("test", &manifest, &files, "remote", &opts).unwrap();
+        assert_eq!(unmatched, [PathBuf::from("/elsewhere/c.sav")]);
+        mgr.upload(&backend, false).await.unwrap();
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        mgr.download(&backend, true, &metadata).await.unwrap();
+        assert_eq!(std::fs::read_to_string(saves.child("b.sav")).unwrap(), "b");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backup_with_non_utf8_name_is_skipped() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.create_dir_all().unwrap();
+        let backup = root.child("backup");
+        backup.child("bad.sav").write_str("bad").unwrap();
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+
+        let files = [(
+            saves.join(OsStr::from_bytes(b"bad\xff.sav")),
+            backup.join("bad.sav"),
+        )];
+        let (mgr, unmatched) =
+            SyncMgr::from_backup("test", &manifest, &files, "remote", &opts).unwrap();
+        assert!(unmatched.is_empty());
+        assert!(mgr.upload_plan().unwrap().is_empty());
+    }
+
+    #[test(tokio::test)]
+    async fn saves_are_exported_to_zip() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("dir").child("a").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        saves.child("dir").child("a").write_str("changed").unwrap();
+
+        let name = zip_entry_name(&saves.join("dir").join("a"));
+        assert!(!name.starts_with('/'));
+        for (backend, expected) in [(Some(&backend), "a"), (None, "changed")] {
+            let out = root.child("out.zip");
+            assert_eq!(mgr.export_zip(backend, out.path()).await.unwrap(), 1);
+            let mut zip = zip::ZipArchive::new(std::fs::File::open(out.path()).unwrap()).unwrap();
+            let mut data = String::new();
+            zip.by_name(&name)
+                .unwrap()
+                .read_to_string(&mut data)
+                .unwrap();
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn remote_saves_are_extracted_as_stored() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("dir").child("a").write_str("a").unwrap();
+        let manifest = save_dir_manifest();
+        for incremental in [false, true] {
+            let store = root.child(format!("store-{incremental}"));
+            let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+            let opts = SyncOptions {
+                incremental,
+                ..save_dir_opts(&saves)
+            };
+            let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+            mgr.upload(&backend, false).await.unwrap();
+            let remote_path = &mgr.files[0].remote_path;
+
+            let dest = root.child(format!("dest-{incremental}"));
+            assert_eq!(extract_remote(&backend, dest.path()).await.unwrap(), 1);
+            assert_eq!(
+                std::fs::read_to_string(dest.join(remote_path)).unwrap(),
+                "a"
+            );
+        }
+        assert_eq!(
+            std::fs::read_to_string(saves.child("dir").child("a")).unwrap(),
+            "a"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn read_only_saves_are_overwritten() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        let save = saves.child("dir").child("a");
+        save.write_str("a").unwrap();
+        let manifest = save_dir_manifest();
+        for incremental in [false, true] {
+            let store = root.child(format!("store-{incremental}"));
+            let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+            let opts = SyncOptions {
+                incremental,
+                ..save_dir_opts(&saves)
+            };
+            let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+            mgr.upload(&backend, false).await.unwrap();
+            let mut metadata = backend.read_sync_time().await.unwrap().unwrap();
+            metadata.file_table.oldest_modified_time = Utc::now() + TimeDelta::days(1);
+
+            save.write_str("changed").unwrap();
+            let mut perms = std::fs::metadata(save.path()).unwrap().permissions();
+            perms.set_readonly(true);
+            std::fs::set_permissions(save.path(), perms).unwrap();
+
+            mgr.download(&backend, true, &metadata).await.unwrap();
+            assert_eq!(std::fs::read_to_string(save.path()).unwrap(), "a");
+            let perms = std::fs::metadata(save.path()).unwrap().permissions();
+            assert!(perms.readonly());
+            #[allow(clippy::permissions_set_readonly_false)]
+            let writable = {
+                let mut p = perms;
+                p.set_readonly(false);
+                p
+            };
+            std::fs::set_permissions(save.path(), writable).unwrap();
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn single_file_saves_are_downloaded() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a.sav").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = GameManifest {
+            files: [(
+                TemplatePath::new("<saves>/a.sav"),
+                FileConfig {
+                    preds: vec![],
+                    tags: vec![FileTag::Save],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let opts = save_dir_opts(&saves);
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        std::fs::remove_file(saves.child("a.sav")).unwrap();
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        mgr.download(&backend, true, &metadata).await.unwrap();
+        assert_eq!(std::fs::read_to_string(saves.child("a.sav")).unwrap(), "a");
+    }
+
+    #[test(tokio::test)]
+    async fn config_files_are_kept_apart_from_saves() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a.sav").write_str("a").unwrap();
+        saves
+            .child("settings.ini")
+            .write_str("fullscreen=1")
+            .unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let file = |tags| FileConfig {
+            preds: vec![],
+            tags,
+        };
+        let manifest = GameManifest {
+            files: [
+                (
+                    TemplatePath::new("<saves>/a.sav"),
+                    file(vec![FileTag::Save]),
+                ),
+                (
+                    TemplatePath::new("<saves>/settings.ini"),
+                    file(vec![FileTag::Config]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let opts = save_dir_opts(&saves);
+        let config_opts = SyncOptions {
+            config_files: true,
+            ..save_dir_opts(&saves)
+        };
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        let config = SyncMgr::from_native("test", &manifest, "remote", &config_opts).unwrap();
+        config.upload_config(&backend).await.unwrap();
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        assert_eq!(
+            metadata
+                .file_table
+                .entries
+                .iter()
+                .map(|e| e.template.as_raw_path())
+                .collect::<Vec<_>>(),
+            [Path::new("<saves>/a.sav")]
+        );
+
+        saves
+            .child("settings.ini")
+            .write_str("fullscreen=0")
+            .unwrap();
+        let config = SyncMgr::from_native("test", &manifest, "remote", &config_opts).unwrap();
+        assert_eq!(config.restore_config(&backend).await.unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(saves.child("settings.ini")).unwrap(),
+            "fullscreen=1"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn observer_is_told_about_syncs() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("a.sav").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let observer: Arc<dyn SyncObserver> = Arc::new(tx);
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts)
+            .unwrap()
+            .with_observer(observer.clone());
+        mgr.upload(&backend, false).await.unwrap();
+        std::fs::remove_file(saves.child("a.sav")).unwrap();
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts)
+            .unwrap()
+            .with_observer(observer);
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        mgr.download(&backend, true, &metadata).await.unwrap();
+
+        let archive_len = std::fs::metadata(store.child(ARCHIVE_NAME)).unwrap().len();
+        let events = rx
+            .try_iter()
+            .map(|e| match e {
+                SyncEvent::SyncStarted { direction, .. } => format!("start {direction:?}"),
+                SyncEvent::Uploading(n) => format!("up {n}"),
+                SyncEvent::Downloading(n) => format!("down {n}"),
+                SyncEvent::Finished { direction, .. } => format!("finish {direction:?}"),
+                SyncEvent::ConflictDetected(_) => "conflict".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                "start Upload".to_owned(),
+                format!("up {archive_len}"),
+                "finish Upload".to_owned(),
+                "start Download".to_owned(),
+                format!("down {archive_len}"),
+                "finish Download".to_owned(),
+            ]
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn corrupt_archive_is_not_unpacked() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("dir").child("a").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = save_dir_opts(&saves);
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        saves.child("dir").child("a").write_str("changed").unwrap();
+        let mut metadata = backend.read_sync_time().await.unwrap().unwrap();
+        assert!(metadata.file_table.entries[0].hash.is_some());
+        // make the cloud saves newer so they are downloaded
+        metadata.file_table.oldest_modified_time = Utc::now() + TimeDelta::days(1);
+
+        let mut wrong_hash = metadata.clone();
+        wrong_hash.archive_hash = None;
+        wrong_hash.file_table.entries[0].hash = Some("nope".to_owned());
+        let mut missing_file = metadata.clone();
+        missing_file.archive_hash = None;
+        let mut missing = missing_file.file_table.entries[0].clone();
+        missing.remote_path = PathBuf::from("missing");
+        missing_file.file_table.entries.push(missing);
+        for metadata in [wrong_hash, missing_file] {
+            assert!(mgr.download(&backend, true, &metadata).await.is_err());
+        }
+
+        backend
+            .write_file(Path::new(ARCHIVE_NAME), b"truncated")
+            .await
+            .unwrap();
+        let err = mgr.download(&backend, true, &metadata).await.unwrap_err();
+        assert!(
+            err.downcast_ref::<CorruptArchiveError>().is_some(),
+            "{err:?}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(saves.child("dir").child("a")).unwrap(),
+            "changed"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn local_files_are_backed_up_before_download() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("dir").child("a").write_str("a").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let backups = root.child("backups");
+        let old_backup = backups.child("test").child("20000101-000000");
+        old_backup.create_dir_all().unwrap();
+        let manifest = save_dir_manifest();
+        let opts = SyncOptions {
+            backup_dir: Some(backups.to_path_buf()),
+            max_backups: 1,
+            ..save_dir_opts(&saves)
+        };
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+        saves.child("dir").child("a").write_str("changed").unwrap();
+        let mut metadata = backend.read_sync_time().await.unwrap().unwrap();
+        metadata.file_table.oldest_modified_time = Utc::now() + TimeDelta::days(1);
+
+        mgr.download(&backend, true, &metadata).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(saves.child("dir").child("a")).unwrap(),
+            "a"
+        );
+        let kept = std::fs::read_dir(backups.child("test"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(kept.len(), 1);
+        assert_ne!(kept[0], old_backup.path());
+        let remote_path = &metadata.file_table.entries[0].remote_path;
+        assert_eq!(
+            std::fs::read_to_string(kept[0].join(remote_path)).unwrap(),
+            "changed"
+        );
+    }
+
+    #[test(tokio::test)]
+    async fn registry_keys_are_synced() {
+        const HIVE: &str = "WINE REGISTRY Version 2\n\n[Software\\\\Game] 1700000000\n\"Slot\"=dword:00000001\n\n[Software\\\\Wine] 1700000000\n\"Version\"=\"win10\"\n";
+        let root = TempDir::new().unwrap();
+        let prefix = root.child("prefix");
+        prefix.child("drive_c").create_dir_all().unwrap();
+        prefix.child("user.reg").write_str(HIVE).unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = GameManifest {
+            registry: [(
+                "HKEY_CURRENT_USER/Software/Game".to_owned(),
+                FileConfig {
+                    preds: vec![],
+                    tags: vec![FileTag::Save],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let opts = SyncOptions::default();
+        let mk_mgr = || {
+            temp_env::with_var("WINEPREFIX", Some(prefix.path()), || {
+                SyncMgr::from_bottles_env(
+                    "test",
+                    &manifest,
+                    "bottle",
+                    Path::new("game.exe"),
+                    "remote",
+                    &opts,
+                )
+                .unwrap()
+            })
+        };
+
+        let mgr = mk_mgr();
+        let prefix_files = || {
+            walkdir::WalkDir::new(prefix.path())
+                .into_iter()
+                .filter(|e| e.as_ref().unwrap().file_type().is_file())
+                .count()
+        };
+        assert_eq!(prefix_files(), 1, "keys were exported before uploading");
+        // a dry-run lists the export without writing it
+        let plan = mgr.upload_plan().unwrap();
+        assert_eq!(plan.len(), 1);
+        assert!(!plan[0].local_path.exists());
+        assert_eq!(prefix_files(), 1, "keys were exported for a dry-run");
+        mgr.upload(&backend, false).await.unwrap();
+        assert_eq!(prefix_files(), 2);
+        // the game's keys are lost, e.g. this is a new prefix
+        let without_game = HIVE.replace(
+            "[Software\\\\Game] 1700000000\n\"Slot\"=dword:00000001\n\n",
+            "",
+        );
+        prefix.child("user.reg").write_str(&without_game).unwrap();
+
+        let mgr = mk_mgr();
+        let metadata = backend.read_sync_time().await.unwrap().unwrap();
+        mgr.download(&backend, true, &metadata).await.unwrap();
+        let hive = std::fs::read_to_string(prefix.child("user.reg")).unwrap();
+        assert!(hive.contains("\"Slot\"=dword:00000001"));
+        assert!(hive.contains("\"Version\"=\"win10\""));
+    }
+
+    #[test(tokio::test)]
+    async fn upload_over_size_limit_is_refused() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("big").write_binary(&[0; 2048]).unwrap();
+        saves.child("small").write_binary(&[0; 16]).unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let opts = SyncOptions {
+            max_upload_size: Some(1024),
+            ..save_dir_opts(&saves)
+        };
+
+        let mgr = SyncMgr::from_native("test", &manifest, "remote", &opts).unwrap();
+        let err = mgr.upload(&backend, false).await.unwrap_err().to_string();
+        assert!(err.contains("big"), "{err}");
+        assert!(!store.child(ARCHIVE_NAME).exists());
+    }
+
+    #[test(tokio::test)]
+    async fn remote_from_incompatible_version_is_not_touched() {
+        let root = TempDir::new().unwrap();
+        let saves = root.child("saves");
+        saves.child("save").write_str("hmm").unwrap();
+        let store = root.child("store");
+        let backend = StorageBackend::new(FilesystemStore::new(store.to_path_buf()).unwrap());
+        let manifest = save_dir_manifest();
+        let mgr =
+            SyncMgr::from_native("test", &manifest, "remote", &save_dir_opts(&saves)).unwrap();
+        mgr.upload(&backend, false).await.unwrap();
+
+        let mut metadata = backend.read_sync_time().await.unwrap().unwrap();
+        let curr = curr_crate_ver();
+        metadata.last_write_cinc_version = semver::Version::new(curr.major + 1, 0, 0);
+        backend.write_sync_time(&metadata).await.unwrap();
+
+        let err = mgr.download(&backend, true, &metadata).await.unwrap_err();
+        assert!(
+            err.downcast_ref::<IncomaptibleCincVersionError>()
+                .unwrap()
+                .read
+        );
+        let err = mgr.upload(&backend, false).await.unwrap_err();
+        assert!(
+            !err.downcast_ref::<IncomaptibleCincVersionError>()
+                .unwrap()
+                .read
+        );
+    }
 }
@@ -1,15 +1,59 @@
-use chrono::{DateTime, Utc};
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use popout::{Color32, LogicalSize, RichText, WindowAttributes, egui::TextStyle};
 
-use crate::{curr_crate_ver, platform::IncomaptibleCincVersionError};
+use tracing::warn;
+
+use crate::{
+    backends::{SyncLock, WriterInfo},
+    config::{Config, parse_time_zone},
+    curr_crate_ver,
+    platform::IncomaptibleCincVersionError,
+};
 
+#[derive(Debug, Clone)]
 pub struct SyncIssueInfo {
     pub local_time: DateTime<Utc>,
     pub remote_time: DateTime<Utc>,
     pub remote_name: String,
     pub remote_last_writer: String,
+    /// Recent writes to the remote, newest first
+    pub recent_writers: Vec<WriterInfo>,
+}
+
+/// How times are shown in dialogs
+#[derive(Debug, Clone)]
+pub struct TimeDisplay {
+    /// strftime format string, this must have been checked to be valid
+    pub format: String,
+    /// Fixed timezone to show times in, otherwise the system one is used
+    pub zone: Option<FixedOffset>,
 }
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+
+impl TimeDisplay {
+    /// The config must have been validated
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            format: cfg.dialog_time_format.clone(),
+            zone: cfg.dialog_time_zone.as_deref().and_then(parse_time_zone),
+        }
+    }
+
+    pub fn show(&self, time: DateTime<Utc>) -> String {
+        match self.zone {
+            Some(zone) => time.with_timezone(&zone).format(&self.format).to_string(),
+            None => time.with_timezone(&Local).format(&self.format).to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SyncChoices {
     /// User chose to continue (download changes)
     Download = 0,
@@ -19,22 +63,118 @@ pub enum SyncChoices {
     Exit = 2,
 }
 
+impl SyncChoices {
+    /// Whether this overwrites files, either the local ones or those on the remote
+    pub const fn is_destructive(self) -> bool {
+        match self {
+            Self::Download | Self::Continue => true,
+            Self::Exit => false,
+        }
+    }
+}
+
+/// What [`spawn_sync_confirm`] returns if the window is closed instead of a button being clicked
+pub const SYNC_CONFIRM_ON_CLOSE: SyncChoices = SyncChoices::Exit;
+/// What [`spawn_lock_confirm`] returns if the window is closed, i.e. don't sync
+pub const LOCK_CONFIRM_ON_CLOSE: bool = false;
+/// What [`show_no_download_confirmation`] returns if the window is closed, i.e. don't skip the
+/// download
+pub const NO_DOWNLOAD_CONFIRM_ON_CLOSE: bool = false;
+// closing a dialog must never overwrite anything
+const _: () = assert!(
+    !SYNC_CONFIRM_ON_CLOSE.is_destructive()
+        && !LOCK_CONFIRM_ON_CLOSE
+        && !NO_DOWNLOAD_CONFIRM_ON_CLOSE
+);
+
+/// Show a dialog asking the user to choose, closing the window rather than choosing gives
+/// `on_close`
+///
+/// Someone closing the window hasn't agreed to anything so `on_close` must always be the choice
+/// that leaves the files alone. If the window can't be created, e.g. there is no display, the
+/// user is asked `question` in the terminal instead, or if there isn't one it is `on_close`
+fn ask<R: Copy + std::fmt::Debug>(
+    on_close: R,
+    question: &str,
+    answers: &[(&str, R)],
+    draw: impl FnMut(&mut popout::egui::Ui) -> Option<R>,
+    attrs: WindowAttributes,
+) -> anyhow::Result<R> {
+    match popout::create_window(draw, attrs) {
+        Ok(r) => Ok(r.unwrap_or(on_close)),
+        Err(e) => {
+            warn!("failed to show a dialog, is there a display? {e}");
+            if io::stdin().is_terminal() && io::stderr().is_terminal() {
+                Ok(ask_in_terminal(
+                    io::stdin().lock(),
+                    io::stderr(),
+                    on_close,
+                    question,
+                    answers,
+                )?)
+            } else {
+                warn!("can't ask in a terminal either, going with {on_close:?}");
+                Ok(on_close)
+            }
+        }
+    }
+}
+
+/// Ask `question` until one of `answers` is typed, the end of the input is `on_close`
+fn ask_in_terminal<R: Copy>(
+    mut input: impl BufRead,
+    mut out: impl Write,
+    on_close: R,
+    question: &str,
+    answers: &[(&str, R)],
+) -> io::Result<R> {
+    let names = answers
+        .iter()
+        .map(|(n, _)| *n)
+        .collect::<Vec<_>>()
+        .join("/");
+    writeln!(out, "{question}")?;
+    loop {
+        write!(out, "[{names}]: ")?;
+        out.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(on_close);
+        }
+        let line = line.trim();
+        if let Some((_, r)) = answers.iter().find(|(n, _)| n.eq_ignore_ascii_case(line)) {
+            return Ok(*r);
+        }
+    }
+}
+
 /// Spawn a dialog warning the user of sync issues and asking them whether to
-/// continue. Returns whether the user elected to continue
-pub fn spawn_sync_confirm(info: SyncIssueInfo) -> anyhow::Result<SyncChoices> {
+/// continue. Returns what they chose, closing the window is [`SYNC_CONFIRM_ON_CLOSE`]
+pub fn spawn_sync_confirm(
+    info: SyncIssueInfo,
+    time_display: &TimeDisplay,
+) -> anyhow::Result<SyncChoices> {
     let min_sz = popout::PhysicalSize::new(500.0, 200.0);
-    let r = popout::create_window(
+    let question = format!(
+        "Cloud conflict: local changes are from {}, remote changes on '{}' are from {} by {}.
+continue uploads your local saves when the game closes, download overwrites them with the remote
+ones. EITHER MAY RESULT IN DATA LOSS",
+        time_display.show(info.local_time),
+        info.remote_name,
+        time_display.show(info.remote_time),
+        info.remote_last_writer,
+    );
+    ask(
+        SYNC_CONFIRM_ON_CLOSE,
+        &question,
+        &[
+            ("continue", SyncChoices::Continue),
+            ("download", SyncChoices::Download),
+            ("exit", SyncChoices::Exit),
+        ],
         |ui| {
-            let local_time = info
-                .local_time
-                .with_timezone(&chrono::Local)
-                .format("%c")
-                .to_string();
-            let remote_time = info
-                .remote_time
-                .with_timezone(&chrono::Local)
-                .format("%c")
-                .to_string();
+            let local_time = time_display.show(info.local_time);
+            let remote_time = time_display.show(info.remote_time);
             ui.vertical_centered(|ui| {
                     ui.label(
                         RichText::new("Cloud conflict detected")
@@ -56,6 +196,18 @@ pub fn spawn_sync_confirm(info: SyncIssueInfo) -> anyhow::Result<SyncChoices> {
                         ui.label(RichText::new(format!("({remote_name})")));
                     });
 
+                    if !info.recent_writers.is_empty() {
+                        ui.label("Recent uploads to the remote:");
+                        for w in &info.recent_writers {
+                            ui.label(format!(
+                                "{} by {} (cinc {})",
+                                time_display.show(w.timestamp),
+                                w.hostname,
+                                w.cinc_version
+                            ));
+                        }
+                    }
+
                     ui.label(
                         r"
 If you continue, your local changes will be overwrite the remote changes when you close the game.
@@ -89,12 +241,86 @@ you have made any progress since the time displayed above for the remote changes
             .with_title("Cloud conflict")
             .with_inner_size(popout::LogicalSize::new(min_sz.width, min_sz.height))
             .with_min_inner_size(min_sz),
-    )?;
-    Ok(r.unwrap_or(SyncChoices::Exit))
+    )
 }
 
-pub fn version_mismatch(err: &IncomaptibleCincVersionError) -> anyhow::Result<()> {
+/// Ask the user whether to sync while another device has the remote locked. Returns whether
+/// they chose to continue, closing the window is [`LOCK_CONFIRM_ON_CLOSE`]
+pub fn spawn_lock_confirm(lock: &SyncLock, time_display: &TimeDisplay) -> anyhow::Result<bool> {
+    let title = "Game running on another device";
+    let question = format!(
+        "{} has been running this game since {}, only continue if you are sure it has stopped",
+        lock.hostname,
+        time_display.show(lock.timestamp)
+    );
+    ask(
+        LOCK_CONFIRM_ON_CLOSE,
+        &question,
+        &[("continue", true), ("exit", false)],
+        |ui| {
+            ui.label(RichText::new(title).heading().color(Color32::YELLOW));
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&lock.hostname).color(Color32::CYAN));
+                ui.label("has been running this game since");
+                ui.label(RichText::new(time_display.show(lock.timestamp)).color(Color32::CYAN));
+            });
+            ui.label(
+                r"
+If it is still running, its saves will overwrite yours (or yours will overwrite its) when the game
+is closed. Only continue if you are sure it has stopped
+                "
+                .replace('\n', " "),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Continue").clicked() {
+                    return Some(true);
+                }
+                if ui.button("Exit").clicked() {
+                    return Some(false);
+                }
+                None
+            })
+            .inner
+        },
+        WindowAttributes::default()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(500.0, 200.0)),
+    )
+}
+
+/// Tracks when a dialog should close itself, the window is redrawn continuously so checking
+/// this every frame is enough
+struct AutoClose {
+    deadline: Option<Instant>,
+}
+
+impl AutoClose {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            deadline: timeout.map(|t| Instant::now() + t),
+        }
+    }
+
+    /// Show the time left and whether it has run out
+    fn show(&self, ui: &mut popout::egui::Ui) -> bool {
+        let Some(deadline) = self.deadline else {
+            return false;
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        ui.label(RichText::new(format!("closing in {}s", left.as_secs() + 1)).weak());
+        left.is_zero()
+    }
+}
+
+/// Tell the user the remote was written by an incompatible cinc. Nothing is synced whichever
+/// way this is closed
+pub fn version_mismatch(
+    err: &IncomaptibleCincVersionError,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
     let title = "Incompatible cinc version detected";
+    let auto_close = AutoClose::new(timeout);
     popout::create_window(
         |ui| {
             ui.label(RichText::new(title).heading().color(Color32::YELLOW));
@@ -120,7 +346,7 @@ pub fn version_mismatch(err: &IncomaptibleCincVersionError) -> anyhow::Result<()
                 ui.label("You can solve this by upgrading your version of cinc to match the version on the server");
             }
 
-            if ui.button("Close").clicked() {
+            if ui.button("Close").clicked() || auto_close.show(ui) {
                 Some(())
             } else {
                 None
@@ -133,12 +359,20 @@ pub fn version_mismatch(err: &IncomaptibleCincVersionError) -> anyhow::Result<()
     Ok(())
 }
 
+/// Ask the user to confirm `--upload-only`, closing the window is
+/// [`NO_DOWNLOAD_CONFIRM_ON_CLOSE`]
 pub fn show_no_download_confirmation() -> anyhow::Result<bool> {
     let mut txt_entry = String::new();
     let title = "Potentially destructive action";
     let confirmation = "trust me";
     let mut mismatch = false;
-    let r = popout::create_window(
+    ask(
+        NO_DOWNLOAD_CONFIRM_ON_CLOSE,
+        &format!(
+            "You have passed --upload-only, if you have made progress on another computer and not \
+            run the game on this one since YOU WILL LOSE IT. Enter '{confirmation}' to continue"
+        ),
+        &[(confirmation, true), ("cancel", false)],
         |ui| {
             if mismatch && !txt_entry.is_empty() {
                 mismatch = false;
@@ -182,39 +416,150 @@ this one you will LOSE YOUR PROGRESS FROM THE OTHER COMPUTER
         WindowAttributes::default()
             .with_title(title)
             .with_inner_size(LogicalSize::new(500.0, 200.0)),
-    )?;
-    Ok(r == Some(true))
+    )
 }
 
-pub fn show_error_dialog(err: &impl std::fmt::Debug) -> anyhow::Result<()> {
-    popout::dialog::Dialog::new()
-        .with_line(
-            RichText::new("error encountered")
-                .heading()
-                .color(Color32::RED),
-        )
-        .with_line(format!("{err:?}"))
-        .with_button("Exit")
-        .with_title("Error")
-        .with_size(LogicalSize::new(300, 100))
-        .show()?;
+/// Dialog showing `lines` under a red heading with an Exit button
+fn show_exit_dialog(
+    title: &str,
+    heading: &str,
+    lines: &[String],
+    size: LogicalSize<f64>,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    let auto_close = AutoClose::new(timeout);
+    popout::create_window(
+        |ui| {
+            ui.label(RichText::new(heading).heading().color(Color32::RED));
+            for line in lines {
+                ui.label(line);
+            }
+            ui.horizontal(|ui| {
+                let exit = ui.button("Exit").clicked();
+                (auto_close.show(ui) || exit).then_some(())
+            })
+            .inner
+        },
+        WindowAttributes::default()
+            .with_title(title)
+            .with_inner_size(size),
+    )?;
     Ok(())
 }
 
+pub fn show_error_dialog(
+    err: &impl std::fmt::Debug,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    show_exit_dialog(
+        "Error",
+        "error encountered",
+        &[format!("{err:?}")],
+        LogicalSize::new(300.0, 100.0),
+        timeout,
+    )
+}
+
 pub fn show_panic_dialog(
     msg: impl Into<String>,
     loc: Option<&std::panic::Location>,
+    timeout: Option<Duration>,
 ) -> anyhow::Result<()> {
-    let mut dialog = popout::dialog::Dialog::new()
-        .with_line(RichText::new("panic!").heading().color(Color32::RED))
-        .with_line(msg.into())
-        .with_button("Exit")
-        .with_title("Panic")
-        .with_size(LogicalSize::new(200, 100));
-
+    let mut lines = vec![msg.into()];
     if let Some(loc) = loc {
-        dialog = dialog.with_line(format!("at {}:{}:{}", loc.file(), loc.line(), loc.column()));
+        lines.push(format!("at {}:{}:{}", loc.file(), loc.line(), loc.column()));
+    }
+    show_exit_dialog(
+        "Panic",
+        "panic!",
+        &lines,
+        LogicalSize::new(200.0, 100.0),
+        timeout,
+    )
+}
+
+/// Show a desktop notification, failing to is only logged as they are never essential
+pub fn notify(summary: &str, body: &str) {
+    if let Err(e) = notify_rust::Notification::new()
+        .appname("cinc")
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        warn!("failed to show notification: {e}");
+    }
+}
+
+/// Every progress bar is drawn through this so transfers running at once don't draw over each
+/// other
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(|| {
+    MultiProgress::with_draw_target(if io::stderr().is_terminal() {
+        ProgressDrawTarget::stderr()
+    } else {
+        ProgressDrawTarget::hidden()
+    })
+});
+
+/// Progress bar for something `len` bytes long, or a spinner if the length isn't known
+///
+/// Nothing is drawn unless stderr is a terminal, e.g. when launched by steam
+pub fn byte_progress(msg: &'static str, len: Option<u64>) -> ProgressBar {
+    let (bar, style) = match len {
+        Some(len) => (
+            ProgressBar::new(len),
+            ProgressStyle::with_template(
+                "{msg} [{wide_bar}] {binary_bytes}/{binary_total_bytes} ({percent}%)",
+            ),
+        ),
+        None => (
+            ProgressBar::new_spinner(),
+            ProgressStyle::with_template("{spinner} {msg} {binary_bytes}"),
+        ),
+    };
+    bar.set_style(style.expect("invalid progress bar template"));
+    PROGRESS.add(bar.with_message(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SyncChoices, ask_in_terminal};
+
+    #[test]
+    fn only_exit_is_not_destructive() {
+        assert!(SyncChoices::Download.is_destructive());
+        assert!(SyncChoices::Continue.is_destructive());
+        assert!(!SyncChoices::Exit.is_destructive());
+    }
+
+    #[test]
+    fn terminal_answer_is_asked_for_until_valid() {
+        let answers = [
+            ("continue", SyncChoices::Continue),
+            ("exit", SyncChoices::Exit),
+        ];
+        let mut out = Vec::new();
+        let r = ask_in_terminal(
+            "what\nContinue\n".as_bytes(),
+            &mut out,
+            SyncChoices::Exit,
+            "?",
+            &answers,
+        )
+        .unwrap();
+        assert_eq!(r, SyncChoices::Continue);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "?\n[continue/exit]: [continue/exit]: "
+        );
+
+        let r = ask_in_terminal(
+            "what\n".as_bytes(),
+            Vec::new(),
+            SyncChoices::Exit,
+            "?",
+            &answers,
+        )
+        .unwrap();
+        assert_eq!(r, SyncChoices::Exit);
     }
-    dialog.show()?;
-    Ok(())
 }